@@ -3,8 +3,8 @@ use std::{
     net::{ToSocketAddrs, UdpSocket},
 };
 
-pub fn connect(socket: UdpSocket, addr: impl ToSocketAddrs) -> Result<(), Error> {
-    let addr = match addr.to_socket_addrs()?.next() {
+pub fn connect(_socket: UdpSocket, addr: impl ToSocketAddrs) -> Result<(), Error> {
+    let _addr = match addr.to_socket_addrs()?.next() {
         Some(a) => a,
         None => {
             return Err(Error::new(
@@ -1,36 +1,306 @@
-use std::sync::{
-    Arc,
-    atomic::{AtomicBool, Ordering},
+use std::{
+    io::Write,
+    net::{IpAddr, Ipv6Addr},
+    path::PathBuf,
+    process::exit,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
 };
 
 use argh::FromArgs;
-use flashbang::server::{Server, config::ServerConfig, runtime::tokio_server::TokioServerRuntime};
+use flashbang::{
+    server::{
+        Server,
+        auth::Authenticator,
+        config::{ConfigHandle, ListenerConfig, LogConfig, LogFormat, ServerConfig, Transport},
+        runtime::tokio_server::TokioServerRuntime,
+    },
+    user::FileStore,
+};
+
+/// How often the credentials file is checked for changes.
+const CREDENTIALS_CHECK_PERIOD: Duration = Duration::from_secs(5);
 
 #[derive(FromArgs)]
 /// A STUN/TURN server.
 struct ServerArgs {
-    
+    /// accept classic STUN (RFC 3489) requests without a magic cookie
+    #[argh(switch)]
+    classic: bool,
+
+    /// address to listen on, can be repeated (default: [::], which includes IPv4)
+    #[argh(option)]
+    listen: Vec<IpAddr>,
+
+    /// port of the UDP and TCP listeners (default: 3478)
+    #[argh(option)]
+    port: Option<u16>,
+
+    /// port of the TLS and DTLS listeners (not supported yet)
+    #[argh(option)]
+    secure_port: Option<u16>,
+
+    /// transport to enable: udp or tcp, can be repeated (default: udp and tcp)
+    #[argh(option, from_str_fn(parse_transport))]
+    transport: Vec<Transport>,
+
+    /// realm of long-term credentials
+    #[argh(option)]
+    realm: Option<String>,
+
+    /// file of `username:realm:key` lines with the long-term keys of the users, which is reloaded when it changes
+    #[argh(option)]
+    credentials: Option<PathBuf>,
+
+    /// PEM file of the TLS certificate (not supported yet)
+    #[argh(option)]
+    tls_certificate: Option<PathBuf>,
+
+    /// PEM file of the TLS private key (not supported yet)
+    #[argh(option)]
+    tls_key: Option<PathBuf>,
+
+    /// log level: off, error, warn, info, debug or trace (default: info, or RUST_LOG)
+    #[argh(option)]
+    log_level: Option<log::LevelFilter>,
+
+    /// log format: text or json (default: text)
+    #[argh(option)]
+    log_format: Option<LogFormat>,
+
+    /// TOML configuration file, which is reloaded on SIGHUP; other options override its values
+    #[argh(option)]
+    config: Option<PathBuf>,
+}
+
+impl ServerArgs {
+    /// Maps the arguments into the configuration of the server,
+    /// on top of the configuration file if there is one.
+    fn to_config(&self) -> Result<ServerConfig, String> {
+        // TLS and DTLS listeners aren't implemented yet, so their options can't be used
+        if self.secure_port.is_some() || self.tls_certificate.is_some() || self.tls_key.is_some() {
+            return Err("TLS and DTLS aren't supported yet, so `--secure-port`, `--tls-certificate` and `--tls-key` can't be used.".into());
+        }
+
+        let mut config = match &self.config {
+            Some(path) => ServerConfig::load(path).map_err(|err| err.to_string())?,
+            None => ServerConfig::default(),
+        };
+
+        config.classic |= self.classic;
+
+        // the listeners of the file are only replaced if any of them are given
+        if !self.listen.is_empty() || !self.transport.is_empty() || self.port.is_some() || self.secure_port.is_some() {
+            config.listeners = self.listeners()?;
+        }
+
+        if let Some(realm) = &self.realm {
+            config.auth.realm = Some(realm.clone());
+        }
+
+        if let Some(credentials) = &self.credentials {
+            config.auth.credentials = Some(credentials.clone());
+        }
+
+        if let Some(level) = self.log_level {
+            config.log.level = level;
+        }
+
+        if let Some(format) = self.log_format {
+            config.log.format = format;
+        }
+
+        config.validate().map_err(|err| err.to_string())?;
+
+        Ok(config)
+    }
+
+    fn listeners(&self) -> Result<Vec<ListenerConfig>, String> {
+        let addresses = match self.listen.is_empty() {
+            true => vec![IpAddr::V6(Ipv6Addr::UNSPECIFIED)],
+            false => self.listen.clone(),
+        };
+
+        let transports = match self.transport.is_empty() {
+            true => vec![Transport::Udp, Transport::Tcp],
+            false => self.transport.clone(),
+        };
+
+        let secure = transports.iter().any(|t| matches!(t, Transport::Tls | Transport::Dtls));
+        let insecure = transports.iter().any(|t| matches!(t, Transport::Udp | Transport::Tcp));
+
+        if self.port.is_some() && !insecure {
+            return Err("`--port` requires a UDP or TCP transport.".into());
+        }
+
+        if self.secure_port.is_some() && !secure {
+            return Err("`--secure-port` requires a TLS or DTLS transport.".into());
+        }
+
+        let mut listeners = vec![];
+
+        for address in &addresses {
+            for transport in &transports {
+                let mut listener = ListenerConfig::new(*transport, *address);
+
+                let port = match transport {
+                    Transport::Udp | Transport::Tcp => self.port,
+                    Transport::Tls | Transport::Dtls => self.secure_port,
+                };
+
+                if let Some(port) = port {
+                    listener.port = port;
+                }
+
+                listeners.push(listener);
+            }
+        }
+
+        Ok(listeners)
+    }
+}
+
+/// Parses a transport of `--transport`, rejecting the transports that aren't implemented yet.
+fn parse_transport(s: &str) -> Result<Transport, String> {
+    match s.parse()? {
+        Transport::Tls | Transport::Dtls => Err(format!("`{s}` isn't supported yet, expected `udp` or `tcp`.")),
+        transport => Ok(transport),
+    }
+}
+
+/// Initializes the logger and returns whether the configured level is used,
+/// which is the case unless RUST_LOG is set and no level was given explicitly.
+fn init_logger(log: &LogConfig, explicit: bool) -> bool {
+    let configured = explicit || std::env::var_os("RUST_LOG").is_none();
+
+    let mut builder = env_logger::Builder::from_env(env_logger::Env::default());
+
+    // everything passes the logger, so the level can be changed by reloading
+    if configured {
+        builder.filter_level(log::LevelFilter::Trace);
+    }
+
+    if log.format == LogFormat::Json {
+        builder.format(|buf, record| {
+            let line = serde_json::json!({
+                "timestamp": buf.timestamp().to_string(),
+                "level": record.level().as_str(),
+                "target": record.target(),
+                "message": record.args().to_string(),
+            });
+
+            writeln!(buf, "{line}")
+        });
+    }
+
+    builder.init();
+
+    if configured {
+        log::set_max_level(log.level);
+    }
+
+    configured
+}
+
+/// Reloads the configuration file and applies the options on top of it again.
+fn reload(args: &ServerArgs, handle: &ConfigHandle, configured_level: bool) {
+    if args.config.is_none() {
+        log::warn!("There's no configuration file to reload");
+        return;
+    }
+
+    let result = args.to_config().and_then(|config| {
+        let level = config.log.level;
+
+        let pending = handle.reload(config).map_err(|err| err.to_string())?;
+
+        if configured_level {
+            log::set_max_level(level);
+        }
+
+        Ok(pending)
+    });
+
+    match result {
+        Ok(pending) => {
+            log::info!("Reloaded the configuration");
+
+            for change in pending {
+                log::warn!("{change}");
+            }
+        }
+        Err(err) => log::error!("Failed to reload the configuration, keeping the current one: {err}"),
+    }
+}
+
+/// Stops the server on Ctrl-C or SIGTERM, and reloads the configuration on SIGHUP.
+#[cfg(unix)]
+async fn handle_signals(running: Arc<AtomicBool>, args: ServerArgs, handle: ConfigHandle, configured_level: bool) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut terminate = signal(SignalKind::terminate()).expect("Error setting SIGTERM handler");
+    let mut hangup = signal(SignalKind::hangup()).expect("Error setting SIGHUP handler");
+
+    loop {
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => break,
+            _ = terminate.recv() => break,
+            _ = hangup.recv() => reload(&args, &handle, configured_level),
+        }
+    }
+
+    running.store(false, Ordering::Relaxed);
+}
+
+/// Stops the server on Ctrl-C.
+#[cfg(not(unix))]
+async fn handle_signals(running: Arc<AtomicBool>, _args: ServerArgs, _handle: ConfigHandle, _configured_level: bool) {
+    tokio::signal::ctrl_c().await.expect("Error setting Ctrl-C handler");
+
+    running.store(false, Ordering::Relaxed);
 }
 
 #[tokio::main]
 async fn main() {
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("debug")).init();
-
     let server_args: ServerArgs = argh::from_env();
 
+    let explicit_level = server_args.log_level.is_some();
+
+    let config = match server_args.to_config() {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Error: {err}");
+            exit(1);
+        }
+    };
+
+    let configured_level = init_logger(&config.log, explicit_level);
+
     let running = Arc::new(AtomicBool::new(true));
-    let running_handler = running.clone();
 
-    ctrlc::set_handler(move || {
-        running_handler.store(false, Ordering::Relaxed);
-    }).expect("Error setting Ctrl-C handler");
+    let credentials = config.auth.credentials.clone();
 
-    let config = ServerConfig {
+    let mut server: Server<TokioServerRuntime> = Server::new(running.clone(), config);
 
-    };
+    if let Some(path) = credentials {
+        let store = match FileStore::load(path) {
+            Ok(store) => Arc::new(store),
+            Err(err) => {
+                log::error!("{err}");
+                exit(1);
+            }
+        };
+
+        store.clone().watch(CREDENTIALS_CHECK_PERIOD);
+
+        server = server.authenticator(Authenticator::new(store));
+    }
+
+    tokio::spawn(handle_signals(running, server_args, server.config(), configured_level));
 
-    let mut server: Server<TokioServerRuntime> = Server::new(running, config);
-    
     server.run().await;
 
     log::info!("Shutdown gracefully");
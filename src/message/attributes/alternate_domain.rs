@@ -7,6 +7,8 @@ use super::*;
 ///
 /// See [RFC8489 Section 14.16](https://datatracker.ietf.org/doc/html/rfc8489#section-14.16) for more details.
 #[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct AlternateDomain {
     alternate_domain: String,
 }
@@ -21,12 +23,10 @@ impl Attribute for AlternateDomain {
         buf[offset..(offset + len)].copy_from_slice(bytes);
     }
 
-    fn decode(buf: &[u8], meta: &AttributeMeta) -> Self {
-        let alternate_domain = std::str::from_utf8(&buf[meta.offset..(meta.offset + meta.len)])
-            .unwrap()
-            .into();
+    fn decode(buf: &[u8], meta: &AttributeMeta) -> Result<Self, AttributeError> {
+        let alternate_domain = decode_str::<Self>(buf, meta)?;
 
-        Self { alternate_domain }
+        Ok(Self { alternate_domain })
     }
 
     fn size(&self) -> usize {
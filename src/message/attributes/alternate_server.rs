@@ -1,14 +1,14 @@
-
 use super::*;
 use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
 
-
 /// The ALTERNATE-SERVER attribute.
 ///
 /// Represents an alternate transport address identifying a different
 /// STUN server that the STUN client should try.
 ///
 /// See [RFC8489 Section 14.11](https://datatracker.ietf.org/doc/html/rfc8489#section-14.11) for more details.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct AlternateServer {
     addr: SocketAddr,
 }
@@ -50,11 +50,19 @@ impl Attribute for AlternateServer {
         }
     }
 
-    fn decode(buf: &[u8], meta: &AttributeMeta) -> Self {
-        // TODO: check first 8 bits
+    fn decode(buf: &[u8], meta: &AttributeMeta) -> Result<Self, AttributeError> {
+        if meta.len < 4 {
+            return Err(AttributeError::new(
+                Self::TY,
+                AttributeErrorTy::BadLength(meta.len),
+            ));
+        }
 
+        // the first 8 bits are ignored by receivers
         let addr = match buf[meta.offset + 1] {
             0x01 => {
+                check_len::<Self>(meta, 8)?;
+
                 let port = u16::from_be_bytes(
                     buf[(meta.offset + 2)..(meta.offset + 4)]
                         .try_into()
@@ -69,6 +77,8 @@ impl Attribute for AlternateServer {
                 SocketAddrV4::new(ip, port).into()
             }
             0x02 => {
+                check_len::<Self>(meta, 20)?;
+
                 let port = u16::from_be_bytes(
                     buf[(meta.offset + 2)..(meta.offset + 4)]
                         .try_into()
@@ -82,18 +92,21 @@ impl Attribute for AlternateServer {
 
                 SocketAddrV6::new(ip, port, 0, 0).into()
             }
-            _ => {
-                panic!("Family must be 1 or 2"); // TODO: add better handling
+            family => {
+                return Err(AttributeError::new(
+                    Self::TY,
+                    AttributeErrorTy::BadFamily(family),
+                ));
             }
         };
 
-        Self { addr }
+        Ok(Self { addr })
     }
 
     fn size(&self) -> usize {
         match self.addr {
-            SocketAddr::V4(_) => 64,
-            SocketAddr::V6(_) => 160,
+            SocketAddr::V4(_) => 8,
+            SocketAddr::V6(_) => 20,
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,104 @@
+use std::{any::Any, fmt::Debug};
+
+use bytes::Bytes;
+
+use super::*;
+
+/// Trait for attribute types defined outside of this crate,
+/// such as vendor-specific attributes.
+///
+/// Every custom attribute is also an [Attribute],
+/// so it can be encoded and decoded like the built-in attributes.
+/// To decode it as part of an [IncomingMessage](crate::message::IncomingMessage),
+/// register it with a [Registry](crate::message::Registry).
+pub trait CustomAttribute: Debug + PartialEq + Send + Sync + 'static {
+    /// The type of attribute.
+    ///
+    /// See [Attribute::TY].
+    const TY: u16;
+
+    /// The size of the body of the attribute.
+    ///
+    /// See [Attribute::SIZE].
+    const SIZE: usize;
+
+    /// Encode the body of the attribute.
+    fn encode(&self, buf: &mut [u8], offset: usize);
+
+    /// Decode the body of the attribute.
+    fn decode(buf: &[u8], meta: &AttributeMeta) -> Result<Self, AttributeError>
+    where
+        Self: Sized;
+
+    /// Size of the body of the attribute.
+    fn size(&self) -> usize {
+        Self::SIZE
+    }
+}
+
+impl<T: CustomAttribute> sealed::Sealed for T {}
+
+impl<T: CustomAttribute> Attribute for T {
+    const TY: u16 = <T as CustomAttribute>::TY;
+    const SIZE: usize = <T as CustomAttribute>::SIZE;
+
+    fn encode(&self, buf: &mut [u8], offset: usize) {
+        CustomAttribute::encode(self, buf, offset)
+    }
+
+    fn decode(buf: &[u8], meta: &AttributeMeta) -> Result<Self, AttributeError> {
+        <T as CustomAttribute>::decode(buf, meta)
+    }
+
+    fn size(&self) -> usize {
+        CustomAttribute::size(self)
+    }
+}
+
+/// A type-erased [CustomAttribute].
+pub trait DynAttribute: Debug + Send + Sync {
+    /// The type of attribute.
+    fn ty(&self) -> u16;
+
+    fn as_any(&self) -> &dyn Any;
+
+    fn dyn_eq(&self, other: &dyn DynAttribute) -> bool;
+
+    /// Encodes the body of the attribute, without the header and padding.
+    fn encode_body(&self) -> Bytes;
+}
+
+impl<T: CustomAttribute> DynAttribute for T {
+    fn ty(&self) -> u16 {
+        <T as CustomAttribute>::TY
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn dyn_eq(&self, other: &dyn DynAttribute) -> bool {
+        other.as_any().downcast_ref::<T>() == Some(self)
+    }
+
+    fn encode_body(&self) -> Bytes {
+        let mut buf = vec![0; CustomAttribute::size(self)];
+
+        CustomAttribute::encode(self, &mut buf, 0);
+
+        buf.into()
+    }
+}
+
+impl dyn DynAttribute {
+    /// Returns the attribute if it is of type `T`.
+    pub fn downcast_ref<T: CustomAttribute>(&self) -> Option<&T> {
+        self.as_any().downcast_ref()
+    }
+}
+
+impl PartialEq for dyn DynAttribute {
+    fn eq(&self, other: &Self) -> bool {
+        self.dyn_eq(other)
+    }
+}
@@ -1,10 +1,14 @@
-use std::str::FromStr;
-
 use super::*;
 
 /// The ERROR-CODE attribute.
 ///
 /// See [RFC8489 Section 14.8](https://datatracker.ietf.org/doc/html/rfc8489#section-14.8) for more details.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(into = "ErrorCodeRepr", from = "ErrorCodeRepr")
+)]
 pub enum ErrorCode {
     TryAlternate,
     BadRequest,
@@ -16,7 +20,8 @@ pub enum ErrorCode {
 }
 
 impl ErrorCode {
-    fn code(&self) -> u32 {
+    /// The numeric error code, in the range of 300 to 699.
+    pub fn code(&self) -> u32 {
         match self {
             Self::TryAlternate => 300,
             Self::BadRequest => 400,
@@ -28,7 +33,8 @@ impl ErrorCode {
         }
     }
 
-    fn reason(&self) -> &str {
+    /// The reason phrase of the error.
+    pub fn reason(&self) -> &str {
         match self {
             Self::TryAlternate => "Try Alternate",
             Self::BadRequest => "Bad Request",
@@ -53,6 +59,31 @@ impl ErrorCode {
     }
 }
 
+/// The serde representation of [ErrorCode], with the numeric code and the reason phrase.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ErrorCodeRepr {
+    code: u32,
+    reason: String,
+}
+
+#[cfg(feature = "serde")]
+impl From<ErrorCode> for ErrorCodeRepr {
+    fn from(error_code: ErrorCode) -> Self {
+        Self {
+            code: error_code.code(),
+            reason: error_code.reason().to_string(),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<ErrorCodeRepr> for ErrorCode {
+    fn from(repr: ErrorCodeRepr) -> Self {
+        Self::from_parts(repr.code, repr.reason)
+    }
+}
+
 impl Attribute for ErrorCode {
     const TY: u16 = 0x0009;
 
@@ -72,10 +103,17 @@ impl Attribute for ErrorCode {
 
         let len = reason.len();
 
-        buf[(offset + 4)..(offset + len)].copy_from_slice(reason.as_bytes());
+        buf[(offset + 4)..(offset + 4 + len)].copy_from_slice(reason.as_bytes());
     }
 
-    fn decode(buf: &[u8], meta: &AttributeMeta) -> Self {
+    fn decode(buf: &[u8], meta: &AttributeMeta) -> Result<Self, AttributeError> {
+        if meta.len < 4 {
+            return Err(AttributeError::new(
+                Self::TY,
+                AttributeErrorTy::BadLength(meta.len),
+            ));
+        }
+
         let encoded_code =
             u32::from_be_bytes(buf[meta.offset..(meta.offset + 4)].try_into().unwrap()) & 0x07FF;
 
@@ -84,10 +122,12 @@ impl Attribute for ErrorCode {
 
         let code = class + number;
 
-        let s = std::str::from_utf8(&buf[(meta.offset + 4)..(meta.offset + meta.len - 4)]).unwrap(); // TODO: Handle bad strings
-        let reason = String::from_str(s).unwrap();
+        let reason = match std::str::from_utf8(&buf[(meta.offset + 4)..(meta.offset + meta.len)]) {
+            Ok(r) => r.into(),
+            Err(_) => return Err(AttributeError::new(Self::TY, AttributeErrorTy::BadUtf8)),
+        };
 
-        Self::from_parts(code, reason)
+        Ok(Self::from_parts(code, reason))
     }
 
     fn size(&self) -> usize {
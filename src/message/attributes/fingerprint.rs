@@ -17,6 +17,22 @@ pub enum Fingerprint {
     Outgoing,
 }
 
+const FINGERPRINT_XOR: u32 = 0x5354554E;
+
+impl Fingerprint {
+    /// Computes the fingerprint of the message that precedes the attribute at `offset`.
+    pub(crate) fn compute(buf: &[u8], offset: usize) -> u32 {
+        crc32fast::hash(&buf[0..offset - 4]) ^ FINGERPRINT_XOR
+    }
+
+    /// Checks that the fingerprint in the attribute matches the message.
+    pub(crate) fn verify(buf: &[u8], meta: &AttributeMeta) -> bool {
+        meta.len == Self::SIZE
+            && buf[meta.offset..(meta.offset + Self::SIZE)]
+                == Self::compute(buf, meta.offset).to_be_bytes()
+    }
+}
+
 impl Attribute for Fingerprint {
     const TY: u16 = 0x8028;
     const SIZE: usize = 4;
@@ -26,18 +42,41 @@ impl Attribute for Fingerprint {
             panic!("Needs to be outgoing");
         }
 
-        let hash = crc32fast::hash(&buf[0..offset - 4]) ^ 0x5354554E;
+        let hash = Self::compute(buf, offset);
 
         buf[offset..(offset + Self::SIZE)].copy_from_slice(&hash.to_be_bytes());
     }
 
-    fn decode(buf: &[u8], meta: &AttributeMeta) -> Self {
+    fn decode(buf: &[u8], meta: &AttributeMeta) -> Result<Self, AttributeError> {
+        check_len::<Self>(meta, Self::SIZE)?;
+
         let fingerprint = u32::from_be_bytes(
             buf[meta.offset..(meta.offset + Self::SIZE)]
                 .try_into()
                 .unwrap(),
         );
 
-        Self::Incoming { fingerprint }
+        Ok(Self::Incoming { fingerprint })
+    }
+}
+
+/// Represented as the CRC-32 of an incoming message, or `null` for an outgoing message.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Fingerprint {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Incoming { fingerprint } => s.serialize_some(fingerprint),
+            Self::Outgoing => s.serialize_none(),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Fingerprint {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        Ok(match <Option<u32>>::deserialize(d)? {
+            Some(fingerprint) => Self::Incoming { fingerprint },
+            None => Self::Outgoing,
+        })
     }
 }
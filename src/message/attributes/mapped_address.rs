@@ -8,6 +8,8 @@ use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
 /// Represents a reflexive transport address of the client.
 ///
 /// See [RFC8489 Section 14.1](https://datatracker.ietf.org/doc/html/rfc8489#section-14.1) for more details.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct MappedAddress {
     addr: SocketAddr,
 }
@@ -49,11 +51,19 @@ impl Attribute for MappedAddress {
         }
     }
 
-    fn decode(buf: &[u8], meta: &AttributeMeta) -> Self {
-        // TODO: check first 8 bits
+    fn decode(buf: &[u8], meta: &AttributeMeta) -> Result<Self, AttributeError> {
+        if meta.len < 4 {
+            return Err(AttributeError::new(
+                Self::TY,
+                AttributeErrorTy::BadLength(meta.len),
+            ));
+        }
 
+        // the first 8 bits are ignored by receivers
         let addr = match buf[meta.offset + 1] {
             0x01 => {
+                check_len::<Self>(meta, 8)?;
+
                 let port = u16::from_be_bytes(
                     buf[(meta.offset + 2)..(meta.offset + 4)]
                         .try_into()
@@ -68,6 +78,8 @@ impl Attribute for MappedAddress {
                 SocketAddrV4::new(ip, port).into()
             }
             0x02 => {
+                check_len::<Self>(meta, 20)?;
+
                 let port = u16::from_be_bytes(
                     buf[(meta.offset + 2)..(meta.offset + 4)]
                         .try_into()
@@ -81,18 +93,21 @@ impl Attribute for MappedAddress {
 
                 SocketAddrV6::new(ip, port, 0, 0).into()
             }
-            _ => {
-                panic!("Family must be 1 or 2"); // TODO: add better handling
+            family => {
+                return Err(AttributeError::new(
+                    Self::TY,
+                    AttributeErrorTy::BadFamily(family),
+                ));
             }
         };
 
-        Self { addr }
+        Ok(Self { addr })
     }
 
     fn size(&self) -> usize {
         match self.addr {
-            SocketAddr::V4(_) => 64,
-            SocketAddr::V6(_) => 160,
+            SocketAddr::V4(_) => 8,
+            SocketAddr::V6(_) => 20,
         }
     }
 }
@@ -102,6 +117,8 @@ impl Attribute for MappedAddress {
 /// Identical to [MappedAddress] but obfuscated through the XOR function.
 ///
 /// See [RFC8489 Section 14.2](https://datatracker.ietf.org/doc/html/rfc8489#section-14.2) for more details.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct XorMappedAddress {
     addr: SocketAddr,
 }
@@ -120,6 +137,12 @@ impl XorMappedAddress {
     }
 }
 
+/// The type of the XOR-PEER-ADDRESS attribute of TURN, encoded like [XorMappedAddress].
+pub(crate) const XOR_PEER_ADDRESS_TY: u16 = 0x0012;
+
+/// The type of the XOR-RELAYED-ADDRESS attribute of TURN, encoded like [XorMappedAddress].
+pub(crate) const XOR_RELAYED_ADDRESS_TY: u16 = 0x0016;
+
 impl Attribute for XorMappedAddress {
     const TY: u16 = 0x0020;
     const SIZE: usize = 0;
@@ -148,11 +171,19 @@ impl Attribute for XorMappedAddress {
         }
     }
 
-    fn decode(buf: &[u8], meta: &AttributeMeta) -> Self {
-        // TODO: check first 8 bits
+    fn decode(buf: &[u8], meta: &AttributeMeta) -> Result<Self, AttributeError> {
+        if meta.len < 4 {
+            return Err(AttributeError::new(
+                Self::TY,
+                AttributeErrorTy::BadLength(meta.len),
+            ));
+        }
 
+        // the first 8 bits are ignored by receivers
         let addr = match buf[meta.offset + 1] {
             0x01 => {
+                check_len::<Self>(meta, 8)?;
+
                 let port = u16::from_be_bytes(
                     buf[(meta.offset + 2)..(meta.offset + 4)]
                         .try_into()
@@ -169,6 +200,8 @@ impl Attribute for XorMappedAddress {
                 SocketAddrV4::new(ip, port).into()
             }
             0x02 => {
+                check_len::<Self>(meta, 20)?;
+
                 let port = u16::from_be_bytes(
                     buf[(meta.offset + 2)..(meta.offset + 4)]
                         .try_into()
@@ -184,18 +217,21 @@ impl Attribute for XorMappedAddress {
 
                 SocketAddrV6::new(ip, port, 0, 0).into()
             }
-            _ => {
-                panic!("Family must be 1 or 2"); // TODO: add better handling
+            family => {
+                return Err(AttributeError::new(
+                    Self::TY,
+                    AttributeErrorTy::BadFamily(family),
+                ));
             }
         };
 
-        Self { addr }
+        Ok(Self { addr })
     }
 
     fn size(&self) -> usize {
         match self.addr {
-            SocketAddr::V4(_) => 64,
-            SocketAddr::V6(_) => 160,
+            SocketAddr::V4(_) => 8,
+            SocketAddr::V6(_) => 20,
         }
     }
 }
@@ -1,5 +1,6 @@
 use bytes::Bytes;
 use hmac::Mac;
+use zeroize::Zeroizing;
 
 use super::*;
 
@@ -9,13 +10,18 @@ use super::*;
 ///
 /// See [RFC8489 Section 14.5](https://datatracker.ietf.org/doc/html/rfc8489#section-14.5) for more details.
 pub enum MessageIntegrity {
-    Incoming { integrity: [u8; Self::SIZE] },
-    Outgoing { key: Bytes },
+    Incoming {
+        integrity: [u8; Self::SIZE],
+    },
+    /// The key is wiped from memory on drop.
+    Outgoing {
+        key: Zeroizing<Vec<u8>>,
+    },
 }
 
 impl MessageIntegrity {
     pub fn new(key: &[u8]) -> Self {
-        let key = Bytes::copy_from_slice(key);
+        let key = Zeroizing::new(key.to_vec());
 
         Self::Outgoing { key }
     }
@@ -23,6 +29,28 @@ impl MessageIntegrity {
     pub fn from_array(integrity: [u8; Self::SIZE]) -> Self {
         Self::Incoming { integrity }
     }
+
+    /// Verifies the HMAC in the attribute against the message in constant time.
+    pub(crate) fn verify(buf: &[u8], meta: &AttributeMeta, key: &[u8]) -> bool {
+        let mut mac = HmacSha1::new_from_slice(key).unwrap();
+
+        update_prefix(&mut mac, buf, meta);
+
+        mac.verify_slice(&buf[meta.offset..(meta.offset + meta.len)])
+            .is_ok()
+    }
+}
+
+/// Updates the HMAC with the message up to the attribute header.
+///
+/// The message length is adjusted to end at the attribute,
+/// since the attributes that follow weren't included when the HMAC was computed.
+fn update_prefix(mac: &mut impl Mac, buf: &[u8], meta: &AttributeMeta) {
+    let len = (meta.offset + meta.len - 20) as u16;
+
+    mac.update(&buf[0..2]);
+    mac.update(&len.to_be_bytes());
+    mac.update(&buf[4..(meta.offset - 4)]);
 }
 
 type HmacSha1 = hmac::Hmac<sha1::Sha1>;
@@ -41,76 +69,190 @@ impl Attribute for MessageIntegrity {
         mac.update(&buf[0..(offset - 4)]);
 
         let result = mac.finalize().into_bytes();
-        let integrity = result.as_slice().try_into().unwrap();
 
-        buf[offset..(offset + Self::SIZE)].copy_from_slice(integrity);
+        buf[offset..(offset + Self::SIZE)].copy_from_slice(&result);
     }
 
-    fn decode(buf: &[u8], meta: &AttributeMeta) -> Self {
-        // TODO: Assert length matches
+    fn decode(buf: &[u8], meta: &AttributeMeta) -> Result<Self, AttributeError> {
+        check_len::<Self>(meta, Self::SIZE)?;
 
-        let integrity = buf[meta.offset..(meta.offset + meta.len)]
+        // the integrity is verified with `IncomingAuthorization::verify`
+        let integrity = buf[meta.offset..(meta.offset + Self::SIZE)]
             .try_into()
             .unwrap();
 
-        // TODO: Verify integrity matches generated result.
-
-        Self::Incoming { integrity }
+        Ok(Self::Incoming { integrity })
     }
 }
 
 /// The MESSAGE-INTEGRITY-SHA256 attribute.
 ///
-/// Contains an HMAC-SHA256 of the STUN message.
+/// Contains an HMAC-SHA256 of the STUN message,
+/// which may be truncated to 16 to 32 bytes in multiples of 4.
 ///
 /// See [RFC8489 Section 14.6](https://datatracker.ietf.org/doc/html/rfc8489#section-14.6) for more details.
 pub enum MessageIntegritySha256 {
-    Incoming { integrity: [u8; Self::SIZE] },
-    Outgoing { key: Bytes },
+    Incoming {
+        integrity: Bytes,
+    },
+    /// The key is wiped from memory on drop.
+    ///
+    /// An invalid `len` is clamped to the nearest valid length when encoding,
+    /// use [MessageIntegritySha256::truncated] to reject it instead.
+    Outgoing {
+        key: Zeroizing<Vec<u8>>,
+        len: usize,
+    },
 }
 
 type HmacSha256 = hmac::Hmac<sha2::Sha256>;
 
 impl MessageIntegritySha256 {
+    /// The length of an HMAC-SHA256 that isn't truncated.
+    pub const FULL_LEN: usize = 32;
+
     pub fn new(key: &[u8]) -> Self {
-        let key = Bytes::copy_from_slice(key);
+        let key = Zeroizing::new(key.to_vec());
 
-        Self::Outgoing { key }
+        Self::Outgoing {
+            key,
+            len: Self::FULL_LEN,
+        }
     }
 
-    pub fn from_array(integrity: [u8; Self::SIZE]) -> Self {
-        Self::Incoming { integrity }
+    /// Creates an attribute whose HMAC is truncated to `len` bytes.
+    ///
+    /// Truncation must be agreed on with the other agent, e.g. per credential.
+    pub fn truncated(key: &[u8], len: usize) -> Result<Self, AttributeError> {
+        Self::check_len(len)?;
+
+        let key = Zeroizing::new(key.to_vec());
+
+        Ok(Self::Outgoing { key, len })
+    }
+
+    pub fn from_array(integrity: [u8; Self::FULL_LEN]) -> Self {
+        Self::Incoming {
+            integrity: Bytes::copy_from_slice(&integrity),
+        }
+    }
+
+    /// Clamps the length of the HMAC to 16 to 32 bytes, rounded down to a multiple of 4.
+    pub(crate) fn clamp_len(len: usize) -> usize {
+        len.clamp(16, Self::FULL_LEN) & !3
+    }
+
+    /// Checks that the HMAC is 16 to 32 bytes long, in multiples of 4.
+    pub(crate) fn check_len(len: usize) -> Result<(), AttributeError> {
+        if !(16..=Self::FULL_LEN).contains(&len) || !len.is_multiple_of(4) {
+            return Err(AttributeError::new(
+                Self::TY,
+                AttributeErrorTy::BadLength(len),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Verifies the (possibly truncated) HMAC in the attribute against the message in constant time.
+    pub(crate) fn verify(buf: &[u8], meta: &AttributeMeta, key: &[u8]) -> bool {
+        if Self::check_len(meta.len).is_err() {
+            return false;
+        }
+
+        let mut mac = HmacSha256::new_from_slice(key).unwrap();
+
+        update_prefix(&mut mac, buf, meta);
+
+        mac.verify_truncated_left(&buf[meta.offset..(meta.offset + meta.len)])
+            .is_ok()
     }
 }
 
 impl Attribute for MessageIntegritySha256 {
     const TY: u16 = 0x001c;
-    const SIZE: usize = 32;
+    // dynamically-sized, since the HMAC may be truncated
+    const SIZE: usize = 0;
 
     fn encode(&self, buf: &mut [u8], offset: usize) {
-        let Self::Outgoing { key } = self else {
+        let Self::Outgoing { key, len } = self else {
             panic!("Attribute needs to be outgoing");
         };
 
+        let len = Self::clamp_len(*len);
+
         let mut mac = HmacSha256::new_from_slice(key).unwrap();
 
         mac.update(&buf[0..(offset - 4)]);
 
         let result = mac.finalize().into_bytes();
-        let integrity = result.as_slice().try_into().unwrap();
 
-        buf[offset..(offset + Self::SIZE)].copy_from_slice(integrity);
+        buf[offset..(offset + len)].copy_from_slice(&result[..len]);
     }
 
-    fn decode(buf: &[u8], meta: &AttributeMeta) -> Self {
-        // TODO: Assert length matches
+    fn decode(buf: &[u8], meta: &AttributeMeta) -> Result<Self, AttributeError> {
+        Self::check_len(meta.len)?;
 
-        let integrity = buf[meta.offset..(meta.offset + meta.len)]
-            .try_into()
-            .unwrap();
+        // the integrity is verified with `IncomingAuthorization::verify`
+        let integrity = Bytes::copy_from_slice(&buf[meta.offset..(meta.offset + meta.len)]);
 
-        // TODO: Verify integrity matches generated result.
+        Ok(Self::Incoming { integrity })
+    }
 
-        Self::Incoming { integrity }
+    fn size(&self) -> usize {
+        match self {
+            Self::Incoming { integrity } => integrity.len(),
+            Self::Outgoing { len, .. } => Self::clamp_len(*len),
+        }
+    }
+}
+
+cfg_if::cfg_if! {
+    if #[cfg(feature = "serde")] {
+        use crate::message::serde_util::hex;
+
+        /// Represented as the hex HMAC of an incoming message.
+        ///
+        /// The key of an outgoing attribute is secret, so it can't be serialized.
+        impl serde::Serialize for MessageIntegrity {
+            fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+                match self {
+                    Self::Incoming { integrity } => hex::serialize(integrity, s),
+                    Self::Outgoing { .. } => Err(serde::ser::Error::custom(
+                        "the key of an outgoing MESSAGE-INTEGRITY can't be serialized",
+                    )),
+                }
+            }
+        }
+
+        impl<'de> serde::Deserialize<'de> for MessageIntegrity {
+            fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+                Ok(Self::from_array(hex::deserialize(d)?))
+            }
+        }
+
+        /// Represented as the hex HMAC of an incoming message.
+        ///
+        /// The key of an outgoing attribute is secret, so it can't be serialized.
+        impl serde::Serialize for MessageIntegritySha256 {
+            fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+                match self {
+                    Self::Incoming { integrity } => hex::serialize(integrity, s),
+                    Self::Outgoing { .. } => Err(serde::ser::Error::custom(
+                        "the key of an outgoing MESSAGE-INTEGRITY-SHA256 can't be serialized",
+                    )),
+                }
+            }
+        }
+
+        impl<'de> serde::Deserialize<'de> for MessageIntegritySha256 {
+            fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+                let integrity: Bytes = hex::deserialize(d)?;
+
+                Self::check_len(integrity.len()).map_err(serde::de::Error::custom)?;
+
+                Ok(Self::Incoming { integrity })
+            }
+        }
     }
 }
@@ -2,6 +2,7 @@
 
 mod alternate_domain;
 mod alternate_server;
+mod custom;
 mod error_code;
 mod fingerprint;
 mod mapped_address;
@@ -15,6 +16,7 @@ mod user;
 
 pub use alternate_domain::*;
 pub use alternate_server::*;
+pub use custom::*;
 pub use error_code::*;
 pub use fingerprint::*;
 pub use mapped_address::*;
@@ -49,6 +51,8 @@ mod sealed {
 
 /// Sealed trait for attribute types.
 ///
+/// Attributes defined outside of this crate implement [CustomAttribute] instead.
+///
 /// See [RFC8489 Section 14](https://datatracker.ietf.org/doc/html/rfc8489#section-14) for more details.
 pub trait Attribute: sealed::Sealed {
     /// The type of attribute.
@@ -72,7 +76,9 @@ pub trait Attribute: sealed::Sealed {
     fn encode(&self, buf: &mut [u8], offset: usize);
 
     /// Decode the body of the attribute.
-    fn decode(buf: &[u8], meta: &AttributeMeta) -> Self;
+    fn decode(buf: &[u8], meta: &AttributeMeta) -> Result<Self, AttributeError>
+    where
+        Self: Sized;
 
     /// Size of the body of the attribute.
     fn size(&self) -> usize {
@@ -80,18 +86,120 @@ pub trait Attribute: sealed::Sealed {
     }
 }
 
-/// Encodes the attribute header and body.
-pub(crate) fn encode_attribute<T: Attribute>(attr: &T, buf: &mut [u8], offset: &mut usize) {
-    // adds the attribute size to the message length
-    let size = attr.size();
-    let aligned_size = (size + 4 + 3) & !3;
-    let old_size = u16::from_be_bytes(buf[2..4].try_into().unwrap());
-    let new_size_bytes = (old_size + aligned_size as u16).to_be_bytes();
-    buf[2..4].copy_from_slice(&new_size_bytes);
+/// An error from decoding the body of an attribute.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttributeError {
+    /// The type of the attribute.
+    pub attribute: u16,
+    pub ty: AttributeErrorTy,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttributeErrorTy {
+    /// The length of the attribute is invalid.
+    BadLength(usize),
+    /// The address family is neither IPv4 (0x01) nor IPv6 (0x02).
+    BadFamily(u8),
+    /// The attribute contains invalid UTF-8.
+    BadUtf8,
+    /// The password algorithm isn't supported.
+    UnknownAlgorithm(u16),
+}
+
+impl AttributeError {
+    pub(crate) fn new(attribute: u16, ty: AttributeErrorTy) -> Self {
+        Self { attribute, ty }
+    }
+}
+
+impl std::fmt::Display for AttributeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Attribute {:#06x}: ", self.attribute)?;
+
+        match self.ty {
+            AttributeErrorTy::BadLength(len) => write!(f, "Invalid length of {len} bytes."),
+            AttributeErrorTy::BadFamily(family) => {
+                write!(
+                    f,
+                    "Address family must be 0x01 or 0x02, but was {family:#04x}."
+                )
+            }
+            AttributeErrorTy::BadUtf8 => write!(f, "Value is not valid UTF-8."),
+            AttributeErrorTy::UnknownAlgorithm(id) => {
+                write!(f, "Unknown password algorithm {id:#06x}.")
+            }
+        }
+    }
+}
+
+impl std::error::Error for AttributeError {}
+
+/// Checks that the attribute has the expected length.
+pub(crate) fn check_len<T: Attribute>(
+    meta: &AttributeMeta,
+    len: usize,
+) -> Result<(), AttributeError> {
+    if meta.len != len {
+        return Err(AttributeError::new(
+            T::TY,
+            AttributeErrorTy::BadLength(meta.len),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Decodes the body of a UTF-8 attribute.
+pub(crate) fn decode_str<T: Attribute>(
+    buf: &[u8],
+    meta: &AttributeMeta,
+) -> Result<String, AttributeError> {
+    match std::str::from_utf8(&buf[meta.offset..(meta.offset + meta.len)]) {
+        Ok(s) => Ok(s.into()),
+        Err(_) => Err(AttributeError::new(T::TY, AttributeErrorTy::BadUtf8)),
+    }
+}
+
+/// The CHANGE-REQUEST attribute of classic STUN, which has been deprecated.
+pub(crate) const CHANGE_REQUEST_TY: u16 = 0x0003;
+
+/// The SOURCE-ADDRESS attribute of classic STUN, which has been deprecated.
+pub(crate) const SOURCE_ADDRESS_TY: u16 = 0x0004;
+
+/// Checks whether the attribute type is understood by this implementation.
+pub(crate) fn is_known(ty: u16) -> bool {
+    matches!(
+        ty,
+        MappedAddress::TY
+            | XorMappedAddress::TY
+            | Username::TY
+            | Userhash::TY
+            | MessageIntegrity::TY
+            | MessageIntegritySha256::TY
+            | Fingerprint::TY
+            | ErrorCode::TY
+            | Realm::TY
+            | Nonce::TY
+            | PasswordAlgorithms::TY
+            | PasswordAlgorithm::TY
+            | UnknownAttributes::TY
+            | Software::TY
+            | AlternateServer::TY
+            | AlternateDomain::TY
+    )
+}
+
+/// Checks whether the attribute type is comprehension-required (0x0000 to 0x7FFF).
+pub fn is_comprehension_required(ty: u16) -> bool {
+    ty & 0x8000 == 0
+}
 
-    // encodes the attribute header
-    buf[*offset..(*offset + 2)].copy_from_slice(&T::TY.to_be_bytes());
-    buf[(*offset + 2)..(*offset + 4)].copy_from_slice(&(size as u16).to_be_bytes());
+/// Encodes the attribute header and body.
+///
+/// The message length in the header is incremented by the size of the attribute,
+/// so `buf` must contain the message header.
+pub fn encode_attribute<T: Attribute>(attr: &T, buf: &mut [u8], offset: &mut usize) {
+    let aligned_size = encode_attribute_header(T::TY, attr.size(), buf, *offset);
 
     // encodes the attribute
     attr.encode(buf, *offset + 4);
@@ -100,19 +208,51 @@ pub(crate) fn encode_attribute<T: Attribute>(attr: &T, buf: &mut [u8], offset: &
     *offset += aligned_size;
 }
 
+/// Encodes the header of an attribute with a body of `size` bytes at `offset`,
+/// and adds the attribute size to the message length.
+///
+/// Returns the size of the attribute, including the header and padding.
+pub(crate) fn encode_attribute_header(
+    ty: u16,
+    size: usize,
+    buf: &mut [u8],
+    offset: usize,
+) -> usize {
+    let aligned_size = (size + 4 + 3) & !3;
+
+    // messages that exceed the maximum length are rejected before they're encoded,
+    // so the length can't overflow for correctly sized attributes
+    let old_size = u16::from_be_bytes([buf[2], buf[3]]);
+    let new_size = old_size.wrapping_add(aligned_size as u16);
+    buf[2..4].copy_from_slice(&new_size.to_be_bytes());
+
+    buf[offset..(offset + 2)].copy_from_slice(&ty.to_be_bytes());
+    buf[(offset + 2)..(offset + 4)].copy_from_slice(&(size as u16).to_be_bytes());
+
+    aligned_size
+}
+
 /// Adds 4 bytes for the attribute header, then aligns to the 32-bit boundary.
 ///
 /// By using the `static` keyword, the calculation can be optimized for statically-sized attributes.
 ///
-/// If the attribute is dynamically-sized, the `dyn` keyword can be used instead.
+/// If the attribute is dynamically-sized, the `dyn` keyword must be used instead,
+/// which is checked at compile time.
 macro_rules! attribute_size {
     (dyn $name:ident) => {
         ($name.size() + 4 + 3) & !3
     };
 
-    (static $name:ty) => {
+    (static $name:ty) => {{
+        const {
+            assert!(
+                <$name as Attribute>::SIZE != 0,
+                "Dynamically-sized attributes need `attribute_size!(dyn ..)`"
+            )
+        };
+
         (<$name as Attribute>::SIZE + 4 + 3) & !3
-    };
+    }};
 
     () => {
         compile_error!("Expected argument");
@@ -121,4 +261,4 @@ macro_rules! attribute_size {
 
 pub(crate) use attribute_size;
 
-use super::meta::{AttributeMeta, MessageMeta};
+use super::meta::AttributeMeta;
@@ -1,9 +1,13 @@
+use std::fmt::Display;
+
 use super::*;
 
 /// The NONCE attribute.
 ///
 /// See [RFC8489 Section 14.10](https://datatracker.ietf.org/doc/html/rfc8489#section-14.10) for more details.
-#[derive(Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct Nonce {
     nonce: String,
 }
@@ -14,6 +18,105 @@ impl Nonce {
             nonce: nonce.to_string(), // TODO: validate nonce construction
         }
     }
+
+    /// Creates a nonce that starts with the nonce cookie and the security features,
+    /// followed by `nonce`.
+    pub fn with_features(features: SecurityFeatures, nonce: impl Display) -> Self {
+        let bits = features.bits().to_be_bytes();
+
+        Self::new(format!(
+            "{NONCE_COOKIE}{}{nonce}",
+            base64_encode([bits[1], bits[2], bits[3]])
+        ))
+    }
+
+    /// The security features of the nonce.
+    ///
+    /// Returns `None` if the nonce doesn't start with the nonce cookie.
+    pub fn features(&self) -> Option<SecurityFeatures> {
+        let encoded = self.nonce.strip_prefix(NONCE_COOKIE)?.get(..4)?;
+
+        let [a, b, c] = base64_decode(encoded.as_bytes().try_into().unwrap())?;
+
+        Some(SecurityFeatures::from_bits(u32::from_be_bytes([
+            0, a, b, c,
+        ])))
+    }
+}
+
+impl Display for Nonce {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.nonce)
+    }
+}
+
+/// The prefix of a nonce that contains security features.
+///
+/// See [RFC8489 Section 9.2](https://datatracker.ietf.org/doc/html/rfc8489#section-9.2) for more details.
+pub const NONCE_COOKIE: &str = "obMatJos2";
+
+/// The STUN security features that are encoded in a nonce,
+/// which protect them from being stripped by an attacker.
+///
+/// See [RFC8489 Section 18.1](https://datatracker.ietf.org/doc/html/rfc8489#section-18.1) for more details.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SecurityFeatures {
+    /// The server supports PASSWORD-ALGORITHMS (bit 0).
+    pub password_algorithms: bool,
+    /// The server supports USERHASH (bit 1).
+    pub username_anonymity: bool,
+}
+
+impl SecurityFeatures {
+    // bits are numbered from the most significant bit of the 24-bit set
+    const PASSWORD_ALGORITHMS: u32 = 1 << 23;
+    const USERNAME_ANONYMITY: u32 = 1 << 22;
+
+    pub fn bits(&self) -> u32 {
+        let mut bits = 0;
+
+        if self.password_algorithms {
+            bits |= Self::PASSWORD_ALGORITHMS;
+        }
+
+        if self.username_anonymity {
+            bits |= Self::USERNAME_ANONYMITY;
+        }
+
+        bits
+    }
+
+    /// Unassigned bits are ignored.
+    pub fn from_bits(bits: u32) -> Self {
+        Self {
+            password_algorithms: bits & Self::PASSWORD_ALGORITHMS != 0,
+            username_anonymity: bits & Self::USERNAME_ANONYMITY != 0,
+        }
+    }
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64_encode(bytes: [u8; 3]) -> String {
+    let bits = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+
+    (0..4)
+        .rev()
+        .map(|i| BASE64[((bits >> (i * 6)) & 0x3F) as usize] as char)
+        .collect()
+}
+
+fn base64_decode(chars: [u8; 4]) -> Option<[u8; 3]> {
+    let mut bits = 0u32;
+
+    for c in chars {
+        let value = BASE64.iter().position(|b| *b == c)?;
+        bits = (bits << 6) | value as u32;
+    }
+
+    let bytes = bits.to_be_bytes();
+
+    Some([bytes[1], bytes[2], bytes[3]])
 }
 
 impl Attribute for Nonce {
@@ -26,12 +129,10 @@ impl Attribute for Nonce {
         buf[offset..(offset + len)].copy_from_slice(bytes);
     }
 
-    fn decode(buf: &[u8], meta: &AttributeMeta) -> Self {
-        let nonce = std::str::from_utf8(&buf[meta.offset..(meta.offset + meta.len)])
-            .unwrap()
-            .into();
+    fn decode(buf: &[u8], meta: &AttributeMeta) -> Result<Self, AttributeError> {
+        let nonce = decode_str::<Self>(buf, meta)?;
 
-        Self { nonce }
+        Ok(Self { nonce })
     }
 
     fn size(&self) -> usize {
@@ -1,7 +1,10 @@
+use std::fmt::Debug;
+
 use bytes::Bytes;
 use md5::{Digest, Md5};
-use once_cell::unsync::Lazy;
+use once_cell::sync::Lazy;
 use sha2::Sha256;
+use zeroize::Zeroizing;
 
 use super::*;
 
@@ -9,9 +12,56 @@ use super::*;
 ///
 /// Contains the list of algorithms that the server can use to derive the long-term password.
 ///
+/// Algorithms that aren't supported are kept when the attribute is decoded,
+/// since clients must echo the list exactly as the server advertised it.
+/// They are encoded again, but aren't serialized.
+///
 /// See [RFC8489 Section 14.11](https://datatracker.ietf.org/doc/html/rfc8489#section-14.11) for more details.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct PasswordAlgorithms {
     algorithms: Vec<PasswordAlgorithm>,
+    /// The unsupported algorithms, with their position in the list.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    unknown: Vec<UnknownAlgorithm>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct UnknownAlgorithm {
+    position: usize,
+    id: u16,
+    params: Bytes,
+}
+
+impl UnknownAlgorithm {
+    fn size(&self) -> usize {
+        4 + self.params.len()
+    }
+}
+
+impl PasswordAlgorithms {
+    /// Creates the list of algorithms, in decreasing order of preference.
+    pub fn new(algorithms: Vec<PasswordAlgorithm>) -> Self {
+        Self {
+            algorithms,
+            unknown: vec![],
+        }
+    }
+
+    /// The supported algorithms, in decreasing order of preference.
+    pub fn algorithms(&self) -> &[PasswordAlgorithm] {
+        &self.algorithms
+    }
+
+    /// The IDs of the algorithms that aren't supported.
+    pub fn unknown(&self) -> impl Iterator<Item = u16> + '_ {
+        self.unknown.iter().map(|unknown| unknown.id)
+    }
+
+    pub fn contains(&self, algorithm: &PasswordAlgorithm) -> bool {
+        self.algorithms.contains(algorithm)
+    }
 }
 
 impl Attribute for PasswordAlgorithms {
@@ -20,33 +70,87 @@ impl Attribute for PasswordAlgorithms {
     const SIZE: usize = 0;
 
     fn encode(&self, buf: &mut [u8], offset: usize) {
+        let mut algorithms = self.algorithms.iter();
+        let mut unknown = self.unknown.iter().peekable();
         let mut i = 0;
-        for alg in &self.algorithms {
-            alg.encode(buf, offset + i);
-            i += (alg.size() + 3) & !3;
+
+        for position in 0..(self.algorithms.len() + self.unknown.len()) {
+            // the unknown algorithms are put back where they were advertised
+            match unknown.next_if(|unknown| unknown.position == position) {
+                Some(unknown) => {
+                    let offset = offset + i;
+                    let len = unknown.params.len();
+
+                    buf[offset..(offset + 2)].copy_from_slice(&unknown.id.to_be_bytes());
+                    buf[(offset + 2)..(offset + 4)].copy_from_slice(&(len as u16).to_be_bytes());
+                    buf[(offset + 4)..(offset + 4 + len)].copy_from_slice(&unknown.params);
+
+                    i += (unknown.size() + 3) & !3;
+                }
+                None => {
+                    let Some(alg) = algorithms.next() else {
+                        break;
+                    };
+
+                    alg.encode(buf, offset + i);
+                    i += (alg.size() + 3) & !3;
+                }
+            }
         }
     }
 
-    fn decode(buf: &[u8], meta: &AttributeMeta) -> Self {
+    fn decode(buf: &[u8], meta: &AttributeMeta) -> Result<Self, AttributeError> {
         let mut i = 0;
         let mut algorithms = vec![];
+        let mut unknown = vec![];
 
-        while i != meta.len {
+        while i < meta.len {
             let offset = meta.offset + i;
+
+            if meta.len - i < 4 {
+                return Err(AttributeError::new(
+                    Self::TY,
+                    AttributeErrorTy::BadLength(meta.len),
+                ));
+            }
+
             let len = 4 + u16::from_be_bytes(buf[(offset + 2)..(offset + 4)].try_into().unwrap())
                 as usize;
+
+            if i + len > meta.len {
+                return Err(AttributeError::new(
+                    Self::TY,
+                    AttributeErrorTy::BadLength(meta.len),
+                ));
+            }
+
             let alg_meta = AttributeMeta {
                 ty: PasswordAlgorithm::TY,
                 offset,
                 len,
             };
 
-            algorithms.push(PasswordAlgorithm::decode(buf, &alg_meta));
+            // unsupported algorithms can't be picked, but are kept for echoing the list
+            match PasswordAlgorithm::decode(buf, &alg_meta) {
+                Ok(alg) => algorithms.push(alg),
+                Err(AttributeError {
+                    ty: AttributeErrorTy::UnknownAlgorithm(id),
+                    ..
+                }) => unknown.push(UnknownAlgorithm {
+                    position: algorithms.len() + unknown.len(),
+                    id,
+                    params: Bytes::copy_from_slice(&buf[(offset + 4)..(offset + len)]),
+                }),
+                Err(e) => return Err(AttributeError::new(Self::TY, e.ty)),
+            }
 
-            i += len;
+            i += (len + 3) & !3;
         }
 
-        Self { algorithms }
+        Ok(Self {
+            algorithms,
+            unknown,
+        })
     }
 
     fn size(&self) -> usize {
@@ -55,6 +159,10 @@ impl Attribute for PasswordAlgorithms {
             offset += (alg.size() + 3) & !3;
         }
 
+        for unknown in &self.unknown {
+            offset += (unknown.size() + 3) & !3;
+        }
+
         offset
     }
 }
@@ -75,6 +183,14 @@ pub struct PasswordAlgorithm {
     algorithm: Box<dyn Algorithm>,
 }
 
+impl Debug for PasswordAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PasswordAlgorithm")
+            .field("id", &self.id)
+            .finish()
+    }
+}
+
 impl PartialEq for PasswordAlgorithm {
     fn eq(&self, other: &Self) -> bool {
         self.id == other.id
@@ -82,7 +198,17 @@ impl PartialEq for PasswordAlgorithm {
 }
 
 impl PasswordAlgorithm {
+    /// The ID of the algorithm in the IANA registry.
+    pub fn id(&self) -> u16 {
+        self.id
+    }
+
     pub fn hash(&self, input: &[u8]) -> Bytes {
+        Bytes::copy_from_slice(&self.algorithm.hash(input))
+    }
+
+    /// Hashes `input` into a buffer that is wiped on drop, for deriving keys.
+    pub(crate) fn hash_secret(&self, input: &[u8]) -> Zeroizing<Vec<u8>> {
         self.algorithm.hash(input)
     }
 }
@@ -101,7 +227,14 @@ impl Attribute for PasswordAlgorithm {
         self.algorithm.encode(buf, offset + 4);
     }
 
-    fn decode(buf: &[u8], meta: &AttributeMeta) -> Self {
+    fn decode(buf: &[u8], meta: &AttributeMeta) -> Result<Self, AttributeError> {
+        if meta.len < 4 {
+            return Err(AttributeError::new(
+                Self::TY,
+                AttributeErrorTy::BadLength(meta.len),
+            ));
+        }
+
         let id = u16::from_be_bytes(buf[meta.offset..(meta.offset + 2)].try_into().unwrap());
 
         let len = u16::from_be_bytes(
@@ -110,6 +243,13 @@ impl Attribute for PasswordAlgorithm {
                 .unwrap(),
         );
 
+        if 4 + len as usize > meta.len {
+            return Err(AttributeError::new(
+                Self::TY,
+                AttributeErrorTy::BadLength(meta.len),
+            ));
+        }
+
         let algorithm: Box<dyn Algorithm> = match id {
             MD5_PASSWORD_ALGORITHM_TY => {
                 Box::new(Md5Algorithm::decode(buf, meta.offset, len as usize))
@@ -117,10 +257,15 @@ impl Attribute for PasswordAlgorithm {
             SHA256_PASSWORD_ALGORITHM_TY => {
                 Box::new(Sha256Algorithm::decode(buf, meta.offset, len as usize))
             }
-            _ => panic!(),
+            _ => {
+                return Err(AttributeError::new(
+                    Self::TY,
+                    AttributeErrorTy::UnknownAlgorithm(id),
+                ))
+            }
         };
 
-        Self { id, algorithm }
+        Ok(Self { id, algorithm })
     }
 
     fn size(&self) -> usize {
@@ -128,6 +273,31 @@ impl Attribute for PasswordAlgorithm {
     }
 }
 
+/// Represented by the IANA name of the algorithm, such as `"SHA-256"`.
+#[cfg(feature = "serde")]
+impl serde::Serialize for PasswordAlgorithm {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        match self.id {
+            MD5_PASSWORD_ALGORITHM_TY => s.serialize_str("MD5"),
+            SHA256_PASSWORD_ALGORITHM_TY => s.serialize_str("SHA-256"),
+            id => s.serialize_u16(id),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for PasswordAlgorithm {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        match String::deserialize(d)?.as_str() {
+            "MD5" => Ok(MD5_PASSWORD_ALGORITHM.clone()),
+            "SHA-256" => Ok(SHA256_PASSWORD_ALGORITHM.clone()),
+            name => Err(serde::de::Error::custom(format!(
+                "unknown password algorithm: {name}"
+            ))),
+        }
+    }
+}
+
 /// Sealed trait for password algorithms.
 ///
 /// NOTE: Since the current algorithms don't have parameters,
@@ -136,10 +306,11 @@ impl Attribute for PasswordAlgorithm {
 ///
 /// See the [IANA Registry for STUN Attributes](https://www.iana.org/assignments/stun-parameters/stun-parameters.xhtml)
 /// for a list of the current password algorithms.
-pub trait Algorithm: sealed::Sealed {
+pub trait Algorithm: sealed::Sealed + Send + Sync {
     fn dyn_clone(&self) -> Box<dyn Algorithm>;
 
-    fn hash(&self, input: &[u8]) -> Bytes;
+    /// Hashes `input` into a buffer that is wiped on drop, since the hash is usually a key.
+    fn hash(&self, input: &[u8]) -> Zeroizing<Vec<u8>>;
 
     fn encode(&self, _buf: &mut [u8], _offset: usize) {}
 
@@ -167,7 +338,7 @@ mod sealed {
 const MD5_PASSWORD_ALGORITHM_TY: u16 = 0x0001;
 
 /// The MD5 PASSWORD-ALGORITHM attribute.
-pub const MD5_PASSWORD_ALGORITHM: Lazy<PasswordAlgorithm> = Lazy::new(|| PasswordAlgorithm {
+pub static MD5_PASSWORD_ALGORITHM: Lazy<PasswordAlgorithm> = Lazy::new(|| PasswordAlgorithm {
     id: MD5_PASSWORD_ALGORITHM_TY,
     algorithm: Box::new(Md5Algorithm),
 });
@@ -180,28 +351,29 @@ impl Algorithm for Md5Algorithm {
         Box::new(self.clone())
     }
 
-    fn hash(&self, input: &[u8]) -> Bytes {
+    fn hash(&self, input: &[u8]) -> Zeroizing<Vec<u8>> {
         let mut hasher = Md5::new();
 
         hasher.update(input);
 
-        let result = hasher.finalize();
+        let mut result = Zeroizing::new(vec![0; Md5::output_size()]);
+        hasher.finalize_into(result.as_mut_slice().into());
 
-        Bytes::copy_from_slice(&*result)
+        result
     }
 
     fn decode(_buf: &[u8], _offset: usize, _len: usize) -> Self
     where
         Self: Sized,
     {
-        Self::default()
+        Self
     }
 }
 
 const SHA256_PASSWORD_ALGORITHM_TY: u16 = 0x0002;
 
 /// The SHA-256 PASSWORD-ALGORITHM attribute.
-pub const SHA256_PASSWORD_ALGORITHM: Lazy<PasswordAlgorithm> = Lazy::new(|| PasswordAlgorithm {
+pub static SHA256_PASSWORD_ALGORITHM: Lazy<PasswordAlgorithm> = Lazy::new(|| PasswordAlgorithm {
     id: SHA256_PASSWORD_ALGORITHM_TY,
     algorithm: Box::new(Sha256Algorithm),
 });
@@ -214,20 +386,21 @@ impl Algorithm for Sha256Algorithm {
         Box::new(self.clone())
     }
 
-    fn hash(&self, input: &[u8]) -> Bytes {
+    fn hash(&self, input: &[u8]) -> Zeroizing<Vec<u8>> {
         let mut hasher = Sha256::new();
 
         hasher.update(input);
 
-        let result = hasher.finalize();
+        let mut result = Zeroizing::new(vec![0; Sha256::output_size()]);
+        hasher.finalize_into(result.as_mut_slice().into());
 
-        Bytes::copy_from_slice(&*result)
+        result
     }
 
     fn decode(_buf: &[u8], _offset: usize, _len: usize) -> Self
     where
         Self: Sized,
     {
-        Self::default()
+        Self
     }
 }
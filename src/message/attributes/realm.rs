@@ -11,7 +11,9 @@ use super::*;
 /// in that realm for authentication.
 ///
 /// See [RFC8489 Section 14.9](https://datatracker.ietf.org/doc/html/rfc8489#section-14.9) for more details.
-#[derive(Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct Realm {
     realm: String,
 }
@@ -40,12 +42,10 @@ impl Attribute for Realm {
         buf[offset..(offset + len)].copy_from_slice(bytes);
     }
 
-    fn decode(buf: &[u8], meta: &AttributeMeta) -> Self {
-        let realm = std::str::from_utf8(&buf[meta.offset..(meta.offset + meta.len)])
-            .unwrap()
-            .into();
+    fn decode(buf: &[u8], meta: &AttributeMeta) -> Result<Self, AttributeError> {
+        let realm = decode_str::<Self>(buf, meta)?;
 
-        Self { realm }
+        Ok(Self { realm })
     }
 
     fn size(&self) -> usize {
@@ -7,11 +7,28 @@ use super::*;
 /// Serves as a tool for diagnostic and debugging purposes.
 ///
 /// See [RFC8489 Section 14.14](https://datatracker.ietf.org/doc/html/rfc8489#section-14.14) for more details.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct Software {
     software: String,
 }
 
+impl Software {
+    pub fn new(software: impl ToString) -> Self {
+        Self {
+            software: software.to_string(),
+        }
+    }
+}
+
+impl Default for Software {
+    /// The name and version of this crate.
+    fn default() -> Self {
+        Self::new(concat!("flashbang ", env!("CARGO_PKG_VERSION")))
+    }
+}
+
 impl Attribute for Software {
     const TY: u16 = 0x8022;
     const SIZE: usize = 0;
@@ -22,12 +39,10 @@ impl Attribute for Software {
         buf[offset..(offset + len)].copy_from_slice(bytes);
     }
 
-    fn decode(buf: &[u8], meta: &AttributeMeta) -> Self {
-        let software = std::str::from_utf8(&buf[meta.offset..(meta.offset + meta.len)])
-            .unwrap()
-            .into();
+    fn decode(buf: &[u8], meta: &AttributeMeta) -> Result<Self, AttributeError> {
+        let software = decode_str::<Self>(buf, meta)?;
 
-        Self { software }
+        Ok(Self { software })
     }
 
     fn size(&self) -> usize {
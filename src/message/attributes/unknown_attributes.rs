@@ -6,10 +6,23 @@ use super::*;
 /// understood by the server.
 ///
 /// See [RFC8489 Section 14.13](https://datatracker.ietf.org/doc/html/rfc8489#section-14.13) for more details.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct UnknownAttributes {
     attributes: Vec<u16>,
 }
 
+impl UnknownAttributes {
+    pub fn new(attributes: Vec<u16>) -> Self {
+        Self { attributes }
+    }
+
+    pub fn attributes(&self) -> &[u16] {
+        &self.attributes
+    }
+}
+
 impl Attribute for UnknownAttributes {
     const TY: u16 = 0x000A;
 
@@ -18,24 +31,26 @@ impl Attribute for UnknownAttributes {
     fn encode(&self, buf: &mut [u8], offset: usize) {
         for (i, attr) in self.attributes.iter().enumerate() {
             let i = i * 2;
-            buf[(i + offset)..(i + 1 + offset)].copy_from_slice(&attr.to_be_bytes());
+            buf[(i + offset)..(i + 2 + offset)].copy_from_slice(&attr.to_be_bytes());
         }
     }
 
-    fn decode(buf: &[u8], meta: &AttributeMeta) -> Self {
-        if meta.len % 2 != 0 {
-            panic!("Each attribute type must be two bytes"); // TODO: Remove panic
+    fn decode(buf: &[u8], meta: &AttributeMeta) -> Result<Self, AttributeError> {
+        // each attribute type must be two bytes
+        if !meta.len.is_multiple_of(2) {
+            return Err(AttributeError::new(
+                Self::TY,
+                AttributeErrorTy::BadLength(meta.len),
+            ));
         }
 
-        let mut attributes = vec!();
+        let mut attributes = vec![];
 
         for i in (meta.offset..(meta.offset + meta.len)).step_by(2) {
-            attributes.push(u16::from_be_bytes(buf[i..(i+1)].try_into().unwrap()));
+            attributes.push(u16::from_be_bytes(buf[i..(i + 2)].try_into().unwrap()));
         }
 
-        Self {
-            attributes,
-        }
+        Ok(Self { attributes })
     }
 
     fn size(&self) -> usize {
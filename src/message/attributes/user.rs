@@ -3,13 +3,16 @@ use std::fmt::Display;
 use sha2::{Digest, Sha256};
 
 use super::*;
+use crate::message::{opaque_string, PrecisError};
 
 /// The USERNAME attribute.
 ///
 /// Represents the username of the current client.
 ///
 /// See [RFC8489 Section 14.3](https://datatracker.ietf.org/doc/html/rfc8489#section-14.3) for more details.
-#[derive(Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct Username {
     username: String,
 }
@@ -38,12 +41,10 @@ impl Attribute for Username {
         buf[offset..(offset + len)].copy_from_slice(bytes);
     }
 
-    fn decode(buf: &[u8], meta: &AttributeMeta) -> Self {
-        let username = std::str::from_utf8(&buf[meta.offset..(meta.offset + meta.len)])
-            .unwrap()
-            .into();
+    fn decode(buf: &[u8], meta: &AttributeMeta) -> Result<Self, AttributeError> {
+        let username = decode_str::<Self>(buf, meta)?;
 
-        Self { username }
+        Ok(Self { username })
     }
 
     fn size(&self) -> usize {
@@ -56,13 +57,25 @@ impl Attribute for Username {
 /// Used as a replacement for the USERNAME attribute when username anonymity is supported.
 ///
 /// See [RFC8489 Section 14.4](https://datatracker.ietf.org/doc/html/rfc8489#section-14.4) for more details.
-#[derive(Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct Userhash {
+    #[cfg_attr(feature = "serde", serde(with = "crate::message::serde_util::hex"))]
     userhash: [u8; 32],
 }
 
 impl Userhash {
-    pub fn new(username: Username, realm: Realm) -> Self {
+    /// Hashes the username and realm after preparing them with [opaque_string].
+    pub fn new(username: Username, realm: Realm) -> Result<Self, PrecisError> {
+        let username = opaque_string(&username.username)?;
+        let realm = opaque_string(&realm.to_string())?;
+
+        Ok(Self::hash(&username, &realm))
+    }
+
+    /// Hashes the username and realm, which must already be prepared.
+    pub(crate) fn hash(username: &str, realm: &str) -> Self {
         let mut hasher = Sha256::new();
 
         hasher.update(format!("{username}:{realm}"));
@@ -73,6 +86,14 @@ impl Userhash {
 
         Self { userhash }
     }
+
+    pub fn from_array(userhash: [u8; Self::SIZE]) -> Self {
+        Self { userhash }
+    }
+
+    pub fn userhash(&self) -> &[u8; Self::SIZE] {
+        &self.userhash
+    }
 }
 
 impl Attribute for Userhash {
@@ -83,9 +104,13 @@ impl Attribute for Userhash {
         buf[offset..(offset + 32)].copy_from_slice(&self.userhash);
     }
 
-    fn decode(buf: &[u8], meta: &AttributeMeta) -> Self {
-        assert_eq!(meta.len, 32);
-        let userhash = buf[meta.offset..(meta.offset + 32)].try_into().unwrap();
-        Self { userhash }
+    fn decode(buf: &[u8], meta: &AttributeMeta) -> Result<Self, AttributeError> {
+        check_len::<Self>(meta, Self::SIZE)?;
+
+        let userhash = buf[meta.offset..(meta.offset + Self::SIZE)]
+            .try_into()
+            .unwrap();
+
+        Ok(Self { userhash })
     }
 }
@@ -1,8 +1,25 @@
 use std::fmt::Debug;
 
-use super::{attributes::*, meta::MessageMeta};
+use bytes::Bytes;
+use zeroize::Zeroizing;
 
+use super::{
+    attributes::*, meta::MessageMeta, opaque_string, IncomingError, IncomingErrorTy, PrecisError,
+};
+
+/// Creates MESSAGE-INTEGRITY-SHA256, truncated to the length of the credentials.
+///
+/// An invalid length is clamped when the attribute is encoded.
+fn integrity_sha256(key: &[u8], truncation: Option<usize>) -> MessageIntegritySha256 {
+    MessageIntegritySha256::Outgoing {
+        key: Zeroizing::new(key.to_vec()),
+        len: truncation.unwrap_or(MessageIntegritySha256::FULL_LEN),
+    }
+}
+
+/// Only serializable, since the password of the credentials is never serialized.
 #[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Authorization {
     pub credentials: Credentials,
     pub integrity: Integrity,
@@ -11,18 +28,18 @@ pub struct Authorization {
 impl Authorization {
     /// Encodes the authorization attributes.
     pub(crate) fn encode(&self, buf: &mut [u8], offset: &mut usize) {
-        match &self.credentials {
-            Credentials::LongTerm {
+        match self.credentials.long_term_attributes() {
+            Some(LongTermAttributes {
                 username,
                 nonce,
                 realm,
-                password,
                 anonymity,
+                algorithms,
                 algorithm,
-            } => {
+            }) => {
                 match anonymity {
                     true => encode_attribute(
-                        &Userhash::new(username.clone(), realm.clone()),
+                        &Userhash::hash(&username.to_string(), &realm.to_string()),
                         buf,
                         offset,
                     ),
@@ -33,74 +50,42 @@ impl Authorization {
 
                 encode_attribute(realm, buf, offset);
 
-                let default = MD5_PASSWORD_ALGORITHM;
-
-                let alg = match algorithm {
-                    Some(alg) => {
-                        encode_attribute(alg, buf, offset);
-                        &alg
-                    }
-                    None => &*default,
-                };
-
-                let key = format!("{username}:{realm}:{password}");
-
-                let key = alg.hash(key.as_bytes());
-
-                if (self.integrity == Integrity::Both) | (self.integrity == Integrity::Sha1) {
-                    encode_attribute(&MessageIntegrity::new(&*key), buf, offset)
+                if let Some(algs) = algorithms {
+                    encode_attribute(algs, buf, offset);
                 }
 
-                if (self.integrity == Integrity::Both) | (self.integrity == Integrity::Sha256) {
-                    encode_attribute(&MessageIntegritySha256::new(&*key), buf, offset)
-                }
-            }
-            Credentials::ShortTerm { username, password } => {
-                encode_attribute(username, buf, offset);
-
-                if (self.integrity == Integrity::Both) | (self.integrity == Integrity::Sha1) {
-                    encode_attribute(&MessageIntegrity::new(password.as_bytes()), buf, offset);
-                }
-
-                if (self.integrity == Integrity::Both) | (self.integrity == Integrity::Sha256) {
-                    encode_attribute(
-                        &MessageIntegritySha256::new(password.as_bytes()),
-                        buf,
-                        offset,
-                    );
+                if let Some(alg) = algorithm {
+                    encode_attribute(alg, buf, offset);
                 }
             }
+            None => encode_attribute(self.credentials.username(), buf, offset),
         }
-    }
 
-    pub(crate) fn decode(buf: &[u8], meta: &MessageMeta) -> Option<Self> {
-        let mut userhash = None;
+        let key = self.credentials.integrity_key();
+        let truncation = self.credentials.truncation();
 
-        for attr in &meta.attributes {
-            match attr.ty {
-                Userhash::TY => {
-                    userhash = Some(Userhash::decode(buf, attr));
-                }
-                _ => (),
-            }
+        if (self.integrity == Integrity::Both) | (self.integrity == Integrity::Sha1) {
+            encode_attribute(&MessageIntegrity::new(&key), buf, offset)
         }
 
-        None
+        if (self.integrity == Integrity::Both) | (self.integrity == Integrity::Sha256) {
+            encode_attribute(&integrity_sha256(&key, truncation), buf, offset)
+        }
     }
 
     /// Calculates the size of the authorization attributes.
     pub(crate) fn size(&self) -> usize {
         let mut size = 0;
 
-        match &self.credentials {
-            Credentials::LongTerm {
+        match self.credentials.long_term_attributes() {
+            Some(LongTermAttributes {
                 username,
                 nonce,
                 realm,
                 anonymity,
+                algorithms,
                 algorithm,
-                ..
-            } => {
+            }) => {
                 size += match anonymity {
                     true => attribute_size!(static Userhash),
                     false => attribute_size!(dyn username),
@@ -110,11 +95,17 @@ impl Authorization {
 
                 size += attribute_size!(dyn realm);
 
+                if let Some(algs) = algorithms {
+                    size += attribute_size!(dyn algs);
+                }
+
                 if let Some(alg) = algorithm {
                     size += attribute_size!(dyn alg);
                 }
             }
-            Credentials::ShortTerm { username, .. } => {
+            None => {
+                let username = self.credentials.username();
+
                 size += attribute_size!(dyn username);
             }
         }
@@ -124,28 +115,271 @@ impl Authorization {
         }
 
         if (self.integrity == Integrity::Both) | (self.integrity == Integrity::Sha256) {
-            size += attribute_size!(static MessageIntegritySha256);
+            let len = self
+                .credentials
+                .truncation()
+                .unwrap_or(MessageIntegritySha256::FULL_LEN);
+
+            size += 4 + MessageIntegritySha256::clamp_len(len);
         }
 
         size
     }
 }
 
+/// The authorization attributes of an incoming message.
+///
+/// Unlike [Authorization], this only contains what was sent over the wire.
+/// Use [IncomingAuthorization::verify] to check the integrity of the message.
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IncomingAuthorization {
+    pub user: Option<UserTy>,
+    pub realm: Option<Realm>,
+    pub nonce: Option<Nonce>,
+    pub algorithms: Option<PasswordAlgorithms>,
+    pub algorithm: Option<PasswordAlgorithm>,
+    pub integrity: Option<Integrity>,
+}
+
+impl IncomingAuthorization {
+    /// Decodes the authorization attributes.
+    ///
+    /// Returns `None` if the message doesn't contain any authorization attributes.
+    pub(crate) fn decode(buf: &[u8], meta: &MessageMeta) -> Result<Option<Self>, AttributeError> {
+        let mut user = None;
+        let mut realm = None;
+        let mut nonce = None;
+        let mut algorithms = None;
+        let mut algorithm = None;
+        let mut sha1 = false;
+        let mut sha256 = false;
+
+        for attr in &meta.attributes {
+            // only MESSAGE-INTEGRITY-SHA256 may follow MESSAGE-INTEGRITY,
+            // everything after the integrity attributes is ignored
+            if sha256 || (sha1 && attr.ty != MessageIntegritySha256::TY) {
+                break;
+            }
+
+            match attr.ty {
+                Username::TY => user = Some(UserTy::Username(Username::decode(buf, attr)?)),
+                Userhash::TY => user = Some(UserTy::Userhash(Userhash::decode(buf, attr)?)),
+                Realm::TY => realm = Some(Realm::decode(buf, attr)?),
+                Nonce::TY => nonce = Some(Nonce::decode(buf, attr)?),
+                PasswordAlgorithms::TY => algorithms = Some(PasswordAlgorithms::decode(buf, attr)?),
+                PasswordAlgorithm::TY => algorithm = Some(PasswordAlgorithm::decode(buf, attr)?),
+                MessageIntegrity::TY => sha1 = true,
+                MessageIntegritySha256::TY => sha256 = true,
+                _ => (),
+            }
+        }
+
+        let integrity = match (sha1, sha256) {
+            (true, true) => Some(Integrity::Both),
+            (true, false) => Some(Integrity::Sha1),
+            (false, true) => Some(Integrity::Sha256),
+            (false, false) => None,
+        };
+
+        if user.is_none()
+            && realm.is_none()
+            && nonce.is_none()
+            && algorithms.is_none()
+            && algorithm.is_none()
+            && integrity.is_none()
+        {
+            return Ok(None);
+        }
+
+        Ok(Some(Self {
+            user,
+            realm,
+            nonce,
+            algorithms,
+            algorithm,
+            integrity,
+        }))
+    }
+
+    /// Negotiates the password algorithm with the algorithms the server advertised.
+    ///
+    /// If the nonce has the password algorithms security feature,
+    /// the request must echo the advertised PASSWORD-ALGORITHMS and
+    /// pick one of them with PASSWORD-ALGORITHM, or include neither to use MD5.
+    /// Otherwise, a [ErrorCode::BadRequest] should be sent,
+    /// since the algorithms may have been altered by an attacker.
+    /// Without the security feature, PASSWORD-ALGORITHM is used if it was advertised,
+    /// falling back to MD5 if it's absent.
+    ///
+    /// See [RFC8489 Section 9.2.4](https://datatracker.ietf.org/doc/html/rfc8489#section-9.2.4) for more details.
+    pub fn negotiate(
+        &self,
+        advertised: &PasswordAlgorithms,
+    ) -> Result<PasswordAlgorithm, ErrorCode> {
+        let features = self
+            .nonce
+            .as_ref()
+            .and_then(Nonce::features)
+            .unwrap_or_default();
+
+        // without the security feature, the algorithms can't be protected against bid-down attacks
+        if !features.password_algorithms {
+            return match &self.algorithm {
+                Some(algorithm) if advertised.contains(algorithm) => Ok(algorithm.clone()),
+                Some(_) => Err(ErrorCode::BadRequest),
+                None => Ok(MD5_PASSWORD_ALGORITHM.clone()),
+            };
+        }
+
+        match (&self.algorithms, &self.algorithm) {
+            (None, None) if advertised.contains(&MD5_PASSWORD_ALGORITHM) => {
+                Ok(MD5_PASSWORD_ALGORITHM.clone())
+            }
+            (Some(algorithms), Some(algorithm))
+                if algorithms == advertised && advertised.contains(algorithm) =>
+            {
+                Ok(algorithm.clone())
+            }
+            _ => Err(ErrorCode::BadRequest),
+        }
+    }
+
+    /// Verifies the integrity of the message in `buf`.
+    ///
+    /// `lookup` maps the authorization attributes of the message to the key of the HMAC.
+    /// For long-term credentials, that is the hash of `username:realm:password`
+    /// using the password algorithm of the message, see [LongTermKey::derive].
+    /// For short-term credentials, that is the password prepared with [opaque_string].
+    ///
+    /// MESSAGE-INTEGRITY-SHA256 is preferred over MESSAGE-INTEGRITY when both are present.
+    pub fn verify<F>(buf: &[u8], lookup: F) -> Result<Verification, IncomingError>
+    where
+        F: FnOnce(&IncomingAuthorization) -> Option<Bytes>,
+    {
+        let meta = MessageMeta::decode(buf)?;
+
+        Self::verify_meta(buf, &meta, lookup)
+    }
+
+    /// Verifies the integrity of the message in `buf` with a key that was already looked up,
+    /// e.g. [LongTermKey::as_bytes], without copying the key.
+    pub fn verify_key(buf: &[u8], key: &[u8]) -> Result<Verification, IncomingError> {
+        let meta = MessageMeta::decode(buf)?;
+
+        Self::verify_meta(buf, &meta, |_| Some(key))
+    }
+
+    pub(crate) fn verify_meta<F, K>(
+        buf: &[u8],
+        meta: &MessageMeta,
+        lookup: F,
+    ) -> Result<Verification, IncomingError>
+    where
+        F: FnOnce(&IncomingAuthorization) -> Option<K>,
+        K: AsRef<[u8]>,
+    {
+        let Some(authorization) = Self::decode(buf, meta)? else {
+            return Ok(Verification::Missing);
+        };
+
+        let integrity = match authorization.integrity {
+            Some(Integrity::Both) | Some(Integrity::Sha256) => Integrity::Sha256,
+            Some(Integrity::Sha1) => Integrity::Sha1,
+            None => return Ok(Verification::Missing),
+        };
+
+        let Some(key) = lookup(&authorization) else {
+            return Ok(Verification::WrongKey);
+        };
+
+        let ty = match integrity {
+            Integrity::Sha256 => MessageIntegritySha256::TY,
+            _ => MessageIntegrity::TY,
+        };
+
+        let Some(attr) = meta.attributes.iter().find(|a| a.ty == ty) else {
+            return Ok(Verification::Missing);
+        };
+
+        let valid = match integrity {
+            Integrity::Sha256 => MessageIntegritySha256::verify(buf, attr, key.as_ref()),
+            _ => MessageIntegrity::verify(buf, attr, key.as_ref()),
+        };
+
+        Ok(match valid {
+            true => Verification::Ok(integrity),
+            false => Verification::Mismatch,
+        })
+    }
+}
+
+/// Identifies the user of an incoming message.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum UserTy {
+    Username(Username),
+    Userhash(Userhash),
+}
+
+/// The result of verifying the integrity of an incoming message.
+#[derive(Debug, PartialEq)]
+pub enum Verification {
+    /// The message doesn't contain a MESSAGE-INTEGRITY or MESSAGE-INTEGRITY-SHA256 attribute.
+    Missing,
+    /// No key was found for the authorization attributes of the message.
+    WrongKey,
+    /// The HMAC doesn't match the message.
+    Mismatch,
+    /// The HMAC of the given attribute matches the message.
+    Ok(Integrity),
+}
+
 #[derive(PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Credentials {
     /// Long-term credentials.
     LongTerm {
         username: Username,
         nonce: Nonce,
         realm: Realm,
-        password: String,
+        /// The password, which is wiped from memory on drop.
+        #[cfg_attr(feature = "serde", serde(skip))]
+        password: Zeroizing<String>,
         anonymity: bool,
+        /// The PASSWORD-ALGORITHMS advertised by the server, which are echoed back.
+        algorithms: Option<PasswordAlgorithms>,
         algorithm: Option<PasswordAlgorithm>,
+        /// The length MESSAGE-INTEGRITY-SHA256 is truncated to, see [Credentials::truncate].
+        /// Invalid lengths are clamped to 16 to 32 bytes in multiples of 4.
+        truncation: Option<usize>,
+    },
+    /// Long-term credentials with a precomputed key instead of the password.
+    ///
+    /// PASSWORD-ALGORITHM is sent with the algorithm of the key,
+    /// unless the key uses MD5 and the server didn't advertise PASSWORD-ALGORITHMS.
+    LongTermKey {
+        username: Username,
+        nonce: Nonce,
+        realm: Realm,
+        #[cfg_attr(feature = "serde", serde(skip))]
+        key: LongTermKey,
+        anonymity: bool,
+        /// The PASSWORD-ALGORITHMS advertised by the server, which are echoed back.
+        algorithms: Option<PasswordAlgorithms>,
+        /// The length MESSAGE-INTEGRITY-SHA256 is truncated to, see [Credentials::truncate].
+        /// Invalid lengths are clamped to 16 to 32 bytes in multiples of 4.
+        truncation: Option<usize>,
     },
     /// Short-term credentials.
     ShortTerm {
         username: Username,
-        password: String,
+        /// The password, which is wiped from memory on drop.
+        #[cfg_attr(feature = "serde", serde(skip))]
+        password: Zeroizing<String>,
+        /// The length MESSAGE-INTEGRITY-SHA256 is truncated to, see [Credentials::truncate].
+        /// Invalid lengths are clamped to 16 to 32 bytes in multiples of 4.
+        truncation: Option<usize>,
     },
 }
 
@@ -157,6 +391,9 @@ impl Debug for Credentials {
 }
 
 impl Credentials {
+    /// Creates long-term credentials.
+    ///
+    /// The username, realm and password are prepared with [opaque_string].
     pub fn new_long_term(
         username: Username,
         nonce: Nonce,
@@ -164,26 +401,402 @@ impl Credentials {
         password: impl ToString,
         anonymity: bool,
         algorithm: Option<PasswordAlgorithm>,
-    ) -> Self {
-        Self::LongTerm {
-            username,
+    ) -> Result<Self, PrecisError> {
+        let password = Zeroizing::new(password.to_string());
+
+        Ok(Self::LongTerm {
+            username: Username::new(opaque_string(&username.to_string())?),
             nonce,
-            realm,
-            password: password.to_string(),
+            realm: Realm::new(opaque_string(&realm.to_string())?),
+            password: Zeroizing::new(opaque_string(&password)?),
+            anonymity,
+            algorithms: None,
+            algorithm,
+            truncation: None,
+        })
+    }
+
+    /// Creates long-term credentials with a key that was derived with [LongTermKey::derive],
+    /// so the password doesn't need to be kept in memory.
+    ///
+    /// The username and realm are prepared with [opaque_string],
+    /// and must be the ones the key was derived from.
+    pub fn new_long_term_key(
+        username: Username,
+        nonce: Nonce,
+        realm: Realm,
+        key: LongTermKey,
+        anonymity: bool,
+    ) -> Result<Self, PrecisError> {
+        Ok(Self::LongTermKey {
+            username: Username::new(opaque_string(&username.to_string())?),
+            nonce,
+            realm: Realm::new(opaque_string(&realm.to_string())?),
+            key,
             anonymity,
+            algorithms: None,
+            truncation: None,
+        })
+    }
+
+    /// Creates short-term credentials.
+    ///
+    /// The username and password are prepared with [opaque_string].
+    pub fn new_short_term(
+        username: Username,
+        password: impl ToString,
+    ) -> Result<Self, PrecisError> {
+        let password = Zeroizing::new(password.to_string());
+
+        Ok(Self::ShortTerm {
+            username: Username::new(opaque_string(&username.to_string())?),
+            password: Zeroizing::new(opaque_string(&password)?),
+            truncation: None,
+        })
+    }
+
+    pub fn username(&self) -> &Username {
+        match self {
+            Self::LongTerm { username, .. }
+            | Self::LongTermKey { username, .. }
+            | Self::ShortTerm { username, .. } => username,
+        }
+    }
+
+    /// Truncates the HMAC of MESSAGE-INTEGRITY-SHA256 to `len` bytes,
+    /// which must be 16 to 32 bytes in multiples of 4.
+    ///
+    /// Truncation isn't signaled in the message,
+    /// so it must be agreed on with the other agent for these credentials.
+    ///
+    /// See [RFC8489 Section 14.6](https://datatracker.ietf.org/doc/html/rfc8489#section-14.6) for more details.
+    pub fn truncate(mut self, len: usize) -> Result<Self, AttributeError> {
+        MessageIntegritySha256::check_len(len)?;
+
+        match &mut self {
+            Self::LongTerm { truncation, .. }
+            | Self::LongTermKey { truncation, .. }
+            | Self::ShortTerm { truncation, .. } => *truncation = Some(len),
+        }
+
+        Ok(self)
+    }
+
+    /// The length MESSAGE-INTEGRITY-SHA256 is truncated to.
+    pub fn truncation(&self) -> Option<usize> {
+        match self {
+            Self::LongTerm { truncation, .. }
+            | Self::LongTermKey { truncation, .. }
+            | Self::ShortTerm { truncation, .. } => *truncation,
+        }
+    }
+
+    /// The attributes of long-term credentials, which precede the integrity attributes.
+    fn long_term_attributes(&self) -> Option<LongTermAttributes<'_>> {
+        match self {
+            Self::LongTerm {
+                username,
+                nonce,
+                realm,
+                anonymity,
+                algorithms,
+                algorithm,
+                ..
+            } => Some(LongTermAttributes {
+                username,
+                nonce,
+                realm,
+                anonymity: *anonymity,
+                algorithms: algorithms.as_ref(),
+                algorithm: algorithm.as_ref(),
+            }),
+            Self::LongTermKey {
+                username,
+                nonce,
+                realm,
+                key,
+                anonymity,
+                algorithms,
+                ..
+            } => {
+                // MD5 is the default, so it's only sent if the server advertised the algorithms
+                let algorithm =
+                    match algorithms.is_some() || *key.algorithm() != *MD5_PASSWORD_ALGORITHM {
+                        true => Some(key.algorithm()),
+                        false => None,
+                    };
+
+                Some(LongTermAttributes {
+                    username,
+                    nonce,
+                    realm,
+                    anonymity: *anonymity,
+                    algorithms: algorithms.as_ref(),
+                    algorithm,
+                })
+            }
+            Self::ShortTerm { .. } => None,
+        }
+    }
+
+    /// The key of the integrity attributes.
+    fn integrity_key(&self) -> Zeroizing<Vec<u8>> {
+        match self {
+            Self::LongTerm {
+                username,
+                realm,
+                password,
+                algorithm,
+                ..
+            } => {
+                let algorithm = algorithm.as_ref().unwrap_or(&MD5_PASSWORD_ALGORITHM);
+
+                let input = Zeroizing::new(format!("{username}:{realm}:{}", password.as_str()));
+
+                algorithm.hash_secret(input.as_bytes())
+            }
+            Self::LongTermKey { key, .. } => Zeroizing::new(key.as_bytes().to_vec()),
+            Self::ShortTerm { password, .. } => Zeroizing::new(password.as_bytes().to_vec()),
+        }
+    }
+}
+
+struct LongTermAttributes<'a> {
+    username: &'a Username,
+    nonce: &'a Nonce,
+    realm: &'a Realm,
+    anonymity: bool,
+    algorithms: Option<&'a PasswordAlgorithms>,
+    algorithm: Option<&'a PasswordAlgorithm>,
+}
+
+/// The key of long-term credentials, which is the hash of `username:realm:password`.
+///
+/// Keeping the key instead of the password means the password doesn't have to stay in memory.
+/// The key itself is wiped from memory on drop.
+///
+/// See [RFC8489 Section 9.2.2](https://datatracker.ietf.org/doc/html/rfc8489#section-9.2.2) for more details.
+#[derive(Clone, PartialEq)]
+pub struct LongTermKey {
+    algorithm: PasswordAlgorithm,
+    key: Zeroizing<Vec<u8>>,
+}
+
+// Implement Debug manually to prevent the key from being leaked to logs.
+impl Debug for LongTermKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LongTermKey")
+            .field("algorithm", &self.algorithm)
+            .finish()
+    }
+}
+
+impl LongTermKey {
+    /// Derives the key, e.g. for provisioning a credential store.
+    ///
+    /// The username, realm and password are prepared with [opaque_string]
+    /// before `username:realm:password` is hashed with the password algorithm.
+    pub fn derive(
+        username: &str,
+        realm: &str,
+        password: &str,
+        algorithm: &PasswordAlgorithm,
+    ) -> Result<Self, PrecisError> {
+        let password = Zeroizing::new(opaque_string(password)?);
+
+        let input = Zeroizing::new(format!(
+            "{}:{}:{}",
+            opaque_string(username)?,
+            opaque_string(realm)?,
+            password.as_str()
+        ));
+
+        Ok(Self {
+            algorithm: algorithm.clone(),
+            key: algorithm.hash_secret(input.as_bytes()),
+        })
+    }
+
+    /// Wraps a key that was derived with `algorithm`, e.g. when it's loaded from storage.
+    pub fn from_bytes(algorithm: PasswordAlgorithm, key: impl Into<Zeroizing<Vec<u8>>>) -> Self {
+        Self {
             algorithm,
+            key: key.into(),
+        }
+    }
+
+    pub fn algorithm(&self) -> &PasswordAlgorithm {
+        &self.algorithm
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.key
+    }
+
+    /// Encodes the key in hex, e.g. for the lines of a [FileStore](crate::user::FileStore).
+    pub fn to_hex(&self) -> Zeroizing<String> {
+        let mut hex = Zeroizing::new(String::with_capacity(self.key.len() * 2));
+
+        for byte in self.key.iter() {
+            hex.push(char::from_digit((byte >> 4) as u32, 16).unwrap());
+            hex.push(char::from_digit((byte & 0xF) as u32, 16).unwrap());
+        }
+
+        hex
+    }
+}
+
+/// The long-term credential challenge of an error response, such as 401 (Unauthenticated).
+///
+/// Used by clients to pick the password algorithm and to detect bid-down attacks.
+///
+/// See [RFC8489 Section 9.2.4](https://datatracker.ietf.org/doc/html/rfc8489#section-9.2.4) for more details.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Challenge {
+    pub realm: Realm,
+    pub nonce: Nonce,
+    pub algorithms: Option<PasswordAlgorithms>,
+}
+
+impl Challenge {
+    /// Decodes the challenge from the message in `buf`.
+    ///
+    /// Returns `None` if the message doesn't contain both REALM and NONCE.
+    pub fn decode(buf: &[u8]) -> Result<Option<Self>, IncomingError> {
+        let meta = MessageMeta::decode(buf)?;
+
+        let mut realm = None;
+        let mut nonce = None;
+        let mut algorithms = None;
+
+        for attr in &meta.attributes {
+            match attr.ty {
+                Realm::TY => realm = Some(Realm::decode(buf, attr)?),
+                Nonce::TY => nonce = Some(Nonce::decode(buf, attr)?),
+                PasswordAlgorithms::TY => algorithms = Some(PasswordAlgorithms::decode(buf, attr)?),
+                _ => (),
+            }
+        }
+
+        let (Some(realm), Some(nonce)) = (realm, nonce) else {
+            return Ok(None);
+        };
+
+        Ok(Some(Self {
+            realm,
+            nonce,
+            algorithms,
+        }))
+    }
+
+    /// Picks the first of the advertised algorithms that is supported.
+    ///
+    /// Returns `None` if the server doesn't support password algorithms, so MD5 is used.
+    /// Fails if PASSWORD-ALGORITHMS or the security feature in the nonce were stripped.
+    pub fn select_algorithm(&self) -> Result<Option<PasswordAlgorithm>, IncomingError> {
+        let features = self.nonce.features().unwrap_or_default();
+
+        match (&self.algorithms, features.password_algorithms) {
+            (None, false) => Ok(None),
+            (Some(algorithms), true) => match algorithms.algorithms().first() {
+                Some(algorithm) => Ok(Some(algorithm.clone())),
+                None => Err(IncomingError {
+                    ty: IncomingErrorTy::UnsupportedAlgorithm,
+                    reason: "None of the password algorithms are supported.".into(),
+                }),
+            },
+            (None, true) => Err(IncomingError {
+                ty: IncomingErrorTy::BidDown,
+                reason: "PASSWORD-ALGORITHMS is missing, but the nonce has the security feature."
+                    .into(),
+            }),
+            (Some(_), false) => Err(IncomingError {
+                ty: IncomingErrorTy::BidDown,
+                reason: "PASSWORD-ALGORITHMS is present, but the nonce lacks the security feature."
+                    .into(),
+            }),
+        }
+    }
+
+    /// Creates the long-term credentials for answering the challenge.
+    ///
+    /// The advertised algorithms are echoed back with the selected algorithm,
+    /// and USERHASH is used if the nonce has the username anonymity security feature.
+    pub fn credentials(
+        &self,
+        username: Username,
+        password: impl ToString,
+    ) -> Result<Credentials, IncomingError> {
+        let algorithm = self.select_algorithm()?;
+
+        let anonymity = self
+            .nonce
+            .features()
+            .is_some_and(|features| features.username_anonymity);
+
+        let mut credentials = Credentials::new_long_term(
+            username,
+            self.nonce.clone(),
+            self.realm.clone(),
+            password,
+            anonymity,
+            algorithm.clone(),
+        )?;
+
+        if let Credentials::LongTerm { algorithms, .. } = &mut credentials {
+            *algorithms = algorithm.and(self.algorithms.clone());
         }
+
+        Ok(credentials)
     }
 
-    pub fn new_short_term(username: Username, password: impl ToString) -> Self {
-        Self::ShortTerm {
+    /// Creates the long-term credentials for answering the challenge with a precomputed key.
+    ///
+    /// The key must use one of the advertised algorithms, or MD5 if the server doesn't
+    /// support password algorithms. Otherwise, the key can't be used and the password is needed.
+    pub fn credentials_with_key(
+        &self,
+        username: Username,
+        key: LongTermKey,
+    ) -> Result<Credentials, IncomingError> {
+        let supported = match self.select_algorithm()? {
+            Some(_) => self
+                .algorithms
+                .as_ref()
+                .is_some_and(|algorithms| algorithms.contains(key.algorithm())),
+            None => *key.algorithm() == *MD5_PASSWORD_ALGORITHM,
+        };
+
+        if !supported {
+            return Err(IncomingError {
+                ty: IncomingErrorTy::UnsupportedAlgorithm,
+                reason: "The password algorithm of the key isn't supported by the server.".into(),
+            });
+        }
+
+        let anonymity = self
+            .nonce
+            .features()
+            .is_some_and(|features| features.username_anonymity);
+
+        let mut credentials = Credentials::new_long_term_key(
             username,
-            password: password.to_string(),
+            self.nonce.clone(),
+            self.realm.clone(),
+            key,
+            anonymity,
+        )?;
+
+        if let Credentials::LongTermKey { algorithms, .. } = &mut credentials {
+            *algorithms = self.algorithms.clone();
         }
+
+        Ok(credentials)
     }
 }
 
 #[derive(Default, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Integrity {
     /// Both MESSAGE-INTEGRITY and MESSAGE-INTEGRITY-SHA256 attributes.
     #[default]
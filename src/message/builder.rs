@@ -0,0 +1,297 @@
+use zeroize::Zeroizing;
+
+use super::*;
+
+/// A builder for outgoing messages whose shape is decided at runtime.
+///
+/// Attributes are encoded in the order they were added.
+/// The integrity attributes and FINGERPRINT are always encoded last,
+/// so they can only be added with [MessageBuilder::integrity] and [MessageBuilder::fingerprint].
+///
+/// Use [OutgoingMessage] when the shape of the message is known at compile time.
+pub struct MessageBuilder {
+    class: u16,
+    method: u16,
+    transaction_id: TransactionId,
+    classic: Option<ClassicTransactionId>,
+    attributes: Vec<Box<dyn EncodeAttribute>>,
+    integrity: Option<(Zeroizing<Vec<u8>>, Integrity)>,
+    fingerprint: bool,
+}
+
+impl MessageBuilder {
+    /// Creates a builder for a message with a random transaction ID.
+    pub fn new(class: u16, method: u16) -> Self {
+        Self {
+            class,
+            method,
+            transaction_id: TransactionId::default(),
+            classic: None,
+            attributes: vec![],
+            integrity: None,
+            fingerprint: false,
+        }
+    }
+
+    /// Creates a builder for a request.
+    pub fn request(method: u16) -> Self {
+        Self::new(REQUEST_CLASS, method)
+    }
+
+    /// Creates a builder for an indication.
+    pub fn indication(method: u16) -> Self {
+        Self::new(INDICATION_CLASS, method)
+    }
+
+    /// Creates a builder for a success response to `message`.
+    ///
+    /// The response reuses the transaction ID and method of the message.
+    pub fn success_response(message: &IncomingMessage) -> Self {
+        Self::new(SUCCESS_RESPONSE_CLASS, message.body.method().method())
+            .transaction_id(message.transaction_id)
+            .classic(message.classic)
+    }
+
+    /// Creates a builder for an error response to `message`.
+    ///
+    /// The response reuses the transaction ID and method of the message.
+    pub fn error_response(message: &IncomingMessage, error_code: ErrorCode) -> Self {
+        let builder = Self::new(ERROR_RESPONSE_CLASS, message.body.method().method())
+            .transaction_id(message.transaction_id)
+            .classic(message.classic);
+
+        builder.push(error_code)
+    }
+
+    pub fn class(mut self, class: u16) -> Self {
+        self.class = class;
+        self
+    }
+
+    pub fn method(mut self, method: u16) -> Self {
+        self.method = method;
+        self
+    }
+
+    pub fn transaction_id(mut self, transaction_id: TransactionId) -> Self {
+        self.transaction_id = transaction_id;
+        self
+    }
+
+    /// Encodes a classic STUN message with a 128-bit transaction ID instead of the magic cookie.
+    ///
+    /// Classic messages must not contain FINGERPRINT or MESSAGE-INTEGRITY-SHA256,
+    /// nor any of the XOR-encoded attributes, otherwise encoding fails.
+    pub fn classic(mut self, transaction_id: Option<ClassicTransactionId>) -> Self {
+        self.classic = transaction_id;
+        self
+    }
+
+    /// Adds an attribute after the previously added attributes.
+    ///
+    /// Fails for MESSAGE-INTEGRITY, MESSAGE-INTEGRITY-SHA256 and FINGERPRINT,
+    /// which must be added with [MessageBuilder::integrity] and [MessageBuilder::fingerprint].
+    pub fn attribute<T: Attribute + 'static>(self, attr: T) -> Result<Self, OutgoingError> {
+        match T::TY {
+            MessageIntegrity::TY | MessageIntegritySha256::TY | Fingerprint::TY => {
+                Err(OutgoingError {
+                    ty: OutgoingErrorTy::MisplacedAttribute,
+                    reason: format!(
+                        "Attribute {:#06x} must be added with `integrity` or `fingerprint`.",
+                        T::TY
+                    ),
+                })
+            }
+            _ => Ok(self.push(attr)),
+        }
+    }
+
+    /// Adds the integrity attributes, which are computed with `key`.
+    ///
+    /// For long-term credentials, `key` is the hash of `username:realm:password`.
+    /// For short-term credentials, `key` is the password.
+    pub fn integrity(mut self, key: &[u8], integrity: Integrity) -> Self {
+        self.integrity = Some((Zeroizing::new(key.to_vec()), integrity));
+        self
+    }
+
+    /// Adds the FINGERPRINT attribute.
+    pub fn fingerprint(mut self) -> Self {
+        self.fingerprint = true;
+        self
+    }
+
+    /// Encodes the message.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the message exceeds the limits of RFC 8489.
+    /// Use [MessageBuilder::try_encode] to handle the error instead.
+    pub fn encode(&self) -> Bytes {
+        self.try_encode(None).expect("Failed to encode message")
+    }
+
+    /// Encodes the message, failing if it exceeds the limits of RFC 8489
+    /// or is larger than `mtu` bytes.
+    pub fn try_encode(&self, mtu: Option<usize>) -> Result<Bytes, OutgoingError> {
+        let mut buf = BytesMut::zeroed(self.size());
+
+        self.encode_into(&mut buf, mtu)?;
+
+        Ok(buf.into())
+    }
+
+    /// Encodes the message into the start of `buf`, returning the size of the message.
+    ///
+    /// Fails if the message exceeds the limits of RFC 8489,
+    /// is larger than `mtu` bytes or doesn't fit into `buf`,
+    /// or if a classic message has attributes that RFC 3489 doesn't know.
+    pub fn encode_into(&self, buf: &mut [u8], mtu: Option<usize>) -> Result<usize, OutgoingError> {
+        if self.classic.is_some() {
+            self.check_classic()?;
+        }
+
+        encode_checked(buf, self.size(), mtu, |buf| {
+            encode_header(buf, self.class, self.method, &self.transaction_id);
+
+            if let Some(classic) = &self.classic {
+                buf[4..20].copy_from_slice(&classic.0);
+            }
+
+            let mut offset = 20;
+
+            for attr in &self.attributes {
+                attr.encode(buf, &mut offset);
+            }
+
+            if let Some((key, integrity)) = &self.integrity {
+                if (*integrity == Integrity::Both) | (*integrity == Integrity::Sha1) {
+                    encode_attribute(&MessageIntegrity::new(key), buf, &mut offset);
+                }
+
+                if (*integrity == Integrity::Both) | (*integrity == Integrity::Sha256) {
+                    encode_attribute(&MessageIntegritySha256::new(key), buf, &mut offset);
+                }
+            }
+
+            if self.fingerprint {
+                encode_attribute(&Fingerprint::Outgoing, buf, &mut offset);
+            }
+        })
+    }
+
+    pub fn size(&self) -> usize {
+        let mut size = 20;
+
+        for attr in &self.attributes {
+            size += attr.encoded_size();
+        }
+
+        if let Some((_, integrity)) = &self.integrity {
+            if (*integrity == Integrity::Both) | (*integrity == Integrity::Sha1) {
+                size += attribute_size!(static MessageIntegrity);
+            }
+
+            if (*integrity == Integrity::Both) | (*integrity == Integrity::Sha256) {
+                size += 4 + MessageIntegritySha256::FULL_LEN;
+            }
+        }
+
+        if self.fingerprint {
+            size += attribute_size!(static Fingerprint);
+        }
+
+        size
+    }
+
+    /// Checks that a classic message has no attributes which were introduced after RFC 3489.
+    fn check_classic(&self) -> Result<(), OutgoingError> {
+        let sha256 = matches!(
+            self.integrity,
+            Some((_, Integrity::Sha256 | Integrity::Both))
+        );
+
+        let ty = self
+            .attributes
+            .iter()
+            .map(|attr| attr.ty())
+            .find(|ty| XOR_ATTRIBUTES.contains(ty))
+            .or(sha256.then_some(MessageIntegritySha256::TY))
+            .or(self.fingerprint.then_some(Fingerprint::TY));
+
+        match ty {
+            Some(ty) => Err(OutgoingError {
+                ty: OutgoingErrorTy::NotClassic(ty),
+                reason: format!("Attribute {ty:#06x} can't be encoded in a classic message."),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    pub(super) fn push<T: Attribute + 'static>(mut self, attr: T) -> Self {
+        self.attributes.push(Box::new(attr));
+        self
+    }
+
+    /// Adds an attribute of type `ty` with an already encoded body.
+    pub(super) fn push_raw(mut self, ty: u16, value: Bytes) -> Self {
+        self.attributes.push(Box::new(RawAttribute { ty, value }));
+        self
+    }
+}
+
+/// The XOR-encoded address attributes: XOR-MAPPED-ADDRESS,
+/// and XOR-PEER-ADDRESS and XOR-RELAYED-ADDRESS of TURN.
+const XOR_ATTRIBUTES: [u16; 3] = [
+    XorMappedAddress::TY,
+    XOR_PEER_ADDRESS_TY,
+    XOR_RELAYED_ADDRESS_TY,
+];
+
+/// Object-safe encoding of an [Attribute].
+trait EncodeAttribute {
+    fn ty(&self) -> u16;
+
+    fn encode(&self, buf: &mut [u8], offset: &mut usize);
+
+    fn encoded_size(&self) -> usize;
+}
+
+/// An attribute with an already encoded body, such as an unknown attribute.
+struct RawAttribute {
+    ty: u16,
+    value: Bytes,
+}
+
+impl EncodeAttribute for RawAttribute {
+    fn ty(&self) -> u16 {
+        self.ty
+    }
+
+    fn encode(&self, buf: &mut [u8], offset: &mut usize) {
+        let size = self.value.len();
+        let aligned_size = encode_attribute_header(self.ty, size, buf, *offset);
+
+        buf[(*offset + 4)..(*offset + 4 + size)].copy_from_slice(&self.value);
+
+        *offset += aligned_size;
+    }
+
+    fn encoded_size(&self) -> usize {
+        (self.value.len() + 4 + 3) & !3
+    }
+}
+
+impl<T: Attribute> EncodeAttribute for T {
+    fn ty(&self) -> u16 {
+        T::TY
+    }
+
+    fn encode(&self, buf: &mut [u8], offset: &mut usize) {
+        encode_attribute(self, buf, offset);
+    }
+
+    fn encoded_size(&self) -> usize {
+        attribute_size!(dyn self)
+    }
+}
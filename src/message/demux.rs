@@ -0,0 +1,210 @@
+//! Demultiplexing of the protocols sharing a transport address.
+//!
+//! WebRTC multiplexes STUN, DTLS, SRTP/SRTCP and TURN ChannelData on a single UDP port,
+//! which are told apart by the first byte of each packet.
+//!
+//! See [RFC7983 Section 7](https://datatracker.ietf.org/doc/html/rfc7983#section-7) for more details.
+
+use super::is_stun;
+
+/// The protocol of a packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PacketClass {
+    /// A STUN message, with a valid magic cookie and fingerprint (if present).
+    Stun,
+    /// A ZRTP packet.
+    Zrtp,
+    /// A DTLS record.
+    Dtls,
+    /// A TURN ChannelData message.
+    ChannelData,
+    /// An RTP or RTCP packet.
+    ///
+    /// RTCP can be told apart from RTP by the payload type in the second byte,
+    /// see [RFC5761 Section 4](https://datatracker.ietf.org/doc/html/rfc5761#section-4).
+    Rtp,
+    /// A packet that doesn't belong to any of the protocols, which should be dropped.
+    Unknown,
+}
+
+/// Classifies a packet by its first byte.
+///
+/// Packets in the STUN range are only classified as [PacketClass::Stun]
+/// if they pass [is_stun], and ChannelData must fit into `buf`.
+pub fn classify(buf: &[u8]) -> PacketClass {
+    let Some(first) = buf.first() else {
+        return PacketClass::Unknown;
+    };
+
+    match first {
+        0..=3 if is_stun(buf) => PacketClass::Stun,
+        16..=19 => PacketClass::Zrtp,
+        20..=63 => PacketClass::Dtls,
+        64..=79 if is_channel_data(buf) => PacketClass::ChannelData,
+        128..=191 => PacketClass::Rtp,
+        _ => PacketClass::Unknown,
+    }
+}
+
+/// Checks the length of a ChannelData message.
+///
+/// Over UDP, the data doesn't need to be padded, but it may be.
+fn is_channel_data(buf: &[u8]) -> bool {
+    if buf.len() < 4 {
+        return false;
+    }
+
+    let len = u16::from_be_bytes([buf[2], buf[3]]) as usize;
+
+    4 + len <= buf.len() && buf.len() <= 4 + ((len + 3) & !3)
+}
+
+cfg_if::cfg_if! {
+    if #[cfg(feature = "async_tokio")] {
+        use std::{io, net::SocketAddr, sync::Arc};
+
+        use bytes::Bytes;
+        use tokio::{
+            net::UdpSocket,
+            sync::mpsc::{self, error::TrySendError, Receiver, Sender},
+            task::JoinHandle,
+        };
+
+        const MAX_PACKET_SIZE: usize = 65535;
+
+        /// A UDP socket that routes each class of packets to its own channel.
+        ///
+        /// Packets are received in a background task, which stops when the socket is dropped.
+        /// Packets are dropped if their channel is full or its receiver was dropped,
+        /// so a slow consumer of one protocol doesn't hold up the others.
+        /// If the socket fails with an error that isn't transient, the task stops
+        /// and the channels are closed.
+        pub struct DemuxSocket {
+            socket: Arc<UdpSocket>,
+            task: JoinHandle<()>,
+        }
+
+        /// The receiving ends of a [DemuxSocket].
+        ///
+        /// Each packet is received along with the address it came from.
+        /// Receivers that aren't needed can be dropped.
+        pub struct DemuxReceivers {
+            pub stun: Receiver<(Bytes, SocketAddr)>,
+            pub zrtp: Receiver<(Bytes, SocketAddr)>,
+            pub dtls: Receiver<(Bytes, SocketAddr)>,
+            pub channel_data: Receiver<(Bytes, SocketAddr)>,
+            pub rtp: Receiver<(Bytes, SocketAddr)>,
+        }
+
+        struct DemuxSenders {
+            stun: Sender<(Bytes, SocketAddr)>,
+            zrtp: Sender<(Bytes, SocketAddr)>,
+            dtls: Sender<(Bytes, SocketAddr)>,
+            channel_data: Sender<(Bytes, SocketAddr)>,
+            rtp: Sender<(Bytes, SocketAddr)>,
+        }
+
+        impl DemuxSocket {
+            /// Wraps `socket`, with channels that buffer up to `capacity` packets each.
+            pub fn new(socket: UdpSocket, capacity: usize) -> (Self, DemuxReceivers) {
+                let socket = Arc::new(socket);
+
+                let (stun, stun_rx) = mpsc::channel(capacity);
+                let (zrtp, zrtp_rx) = mpsc::channel(capacity);
+                let (dtls, dtls_rx) = mpsc::channel(capacity);
+                let (channel_data, channel_data_rx) = mpsc::channel(capacity);
+                let (rtp, rtp_rx) = mpsc::channel(capacity);
+
+                let senders = DemuxSenders {
+                    stun,
+                    zrtp,
+                    dtls,
+                    channel_data,
+                    rtp,
+                };
+
+                let task = tokio::spawn(Self::recv_loop(socket.clone(), senders));
+
+                let receivers = DemuxReceivers {
+                    stun: stun_rx,
+                    zrtp: zrtp_rx,
+                    dtls: dtls_rx,
+                    channel_data: channel_data_rx,
+                    rtp: rtp_rx,
+                };
+
+                (Self { socket, task }, receivers)
+            }
+
+            /// The wrapped socket, for sending packets of any class.
+            pub fn socket(&self) -> &UdpSocket {
+                &self.socket
+            }
+
+            pub async fn send_to(&self, buf: &[u8], addr: SocketAddr) -> io::Result<usize> {
+                self.socket.send_to(buf, addr).await
+            }
+
+            async fn recv_loop(socket: Arc<UdpSocket>, senders: DemuxSenders) {
+                let mut buf = vec![0u8; MAX_PACKET_SIZE];
+
+                loop {
+                    let (size, addr) = match socket.recv_from(&mut buf).await {
+                        Ok(r) => r,
+                        Err(err) if is_transient(&err) => {
+                            log::debug!("Failed to receive packet: {err}");
+                            continue;
+                        }
+                        Err(err) => {
+                            log::error!("Failed to receive packet, closing the channels: {err}");
+                            break;
+                        }
+                    };
+
+                    let packet = &buf[..size];
+
+                    let class = classify(packet);
+
+                    let sender = match class {
+                        PacketClass::Stun => &senders.stun,
+                        PacketClass::Zrtp => &senders.zrtp,
+                        PacketClass::Dtls => &senders.dtls,
+                        PacketClass::ChannelData => &senders.channel_data,
+                        PacketClass::Rtp => &senders.rtp,
+                        PacketClass::Unknown => {
+                            log::trace!("Dropped unknown packet from {addr}");
+                            continue;
+                        }
+                    };
+
+                    match sender.try_send((Bytes::copy_from_slice(packet), addr)) {
+                        Ok(()) | Err(TrySendError::Closed(_)) => (),
+                        Err(TrySendError::Full(_)) => {
+                            log::trace!("Dropped {class:?} packet from {addr}, channel is full");
+                        }
+                    }
+                }
+            }
+        }
+
+        /// Whether a receive error only affects a single packet.
+        ///
+        /// ICMP errors of earlier sends, e.g. port unreachable, are reported on the next receive.
+        fn is_transient(err: &io::Error) -> bool {
+            matches!(
+                err.kind(),
+                io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionRefused
+                    | io::ErrorKind::Interrupted
+                    | io::ErrorKind::WouldBlock
+                    | io::ErrorKind::TimedOut
+            )
+        }
+
+        impl Drop for DemuxSocket {
+            fn drop(&mut self) {
+                self.task.abort();
+            }
+        }
+    }
+}
@@ -0,0 +1,489 @@
+use std::fmt::{self, Display, Write};
+
+use super::*;
+
+/// Dissects a packet for debugging, similar to a packet analyzer.
+///
+/// Unlike [MessageMeta::decode], this never fails:
+/// as much of the packet as possible is dissected,
+/// and malformed parts and RFC violations are reported as issues.
+///
+/// If `key` is given, the integrity attributes are verified with it.
+pub fn dissect(buf: &[u8], key: Option<&[u8]>) -> Dissection {
+    let mut dissection = Dissection {
+        header: None,
+        attributes: vec![],
+        issues: vec![],
+    };
+
+    if buf.len() < 20 {
+        dissection.issues.push(format!(
+            "Packet of {} bytes is too small for the 20-byte header.",
+            buf.len()
+        ));
+
+        return dissection;
+    }
+
+    let header = DissectedHeader {
+        ty: u16::from_be_bytes([buf[0], buf[1]]),
+        length: u16::from_be_bytes([buf[2], buf[3]]),
+        magic: u32::from_be_bytes(buf[4..8].try_into().unwrap()),
+        transaction_id: buf[8..20].try_into().unwrap(),
+    };
+
+    if header.ty & 0xC000 != 0 {
+        dissection
+            .issues
+            .push("The first two bits of the message type aren't zero.".into());
+    }
+
+    if header.magic != MAGIC {
+        dissection
+            .issues
+            .push("The magic cookie is missing, this may be a classic STUN message.".into());
+    }
+
+    if !header.length.is_multiple_of(4) {
+        dissection.issues.push(format!(
+            "Message length of {} bytes isn't aligned to a 32-bit boundary.",
+            header.length
+        ));
+    }
+
+    if 20 + header.length as usize != buf.len() {
+        dissection.issues.push(format!(
+            "Message length of {} bytes doesn't match the {} bytes after the header.",
+            header.length,
+            buf.len() - 20
+        ));
+    }
+
+    dissection.header = Some(header);
+
+    // the last integrity attribute or FINGERPRINT, for checking the order of the attributes
+    let mut last = None;
+    let mut idx = 20;
+
+    while idx < buf.len() {
+        if buf.len() - idx < 4 {
+            dissection.issues.push(format!(
+                "Attribute header at offset {idx} is truncated to {} bytes.",
+                buf.len() - idx
+            ));
+            break;
+        }
+
+        let ty = u16::from_be_bytes([buf[idx], buf[idx + 1]]);
+        let len = u16::from_be_bytes([buf[idx + 2], buf[idx + 3]]) as usize;
+        let offset = idx + 4;
+
+        let name = attribute_name(ty).unwrap_or("Unknown");
+
+        if let Some(last) = last {
+            let allowed = matches!(
+                (last, ty),
+                (
+                    MessageIntegrity::TY,
+                    MessageIntegritySha256::TY | Fingerprint::TY
+                ) | (MessageIntegritySha256::TY, Fingerprint::TY)
+            );
+
+            if !allowed {
+                dissection.issues.push(format!(
+                    "{name} ({ty:#06x}) follows {}.",
+                    attribute_name(last).unwrap()
+                ));
+            }
+        }
+
+        if matches!(
+            ty,
+            MessageIntegrity::TY | MessageIntegritySha256::TY | Fingerprint::TY
+        ) {
+            last = Some(ty);
+        }
+
+        if offset + len > buf.len() {
+            dissection.issues.push(format!(
+                "{name} ({ty:#06x}) has a length of {len} bytes, but only {} remain.",
+                buf.len() - offset
+            ));
+
+            dissection.attributes.push(DissectedAttribute {
+                ty,
+                offset,
+                len,
+                padding: 0,
+                value: format!("{} (truncated)", hex(&buf[offset..])),
+                valid: None,
+            });
+            break;
+        }
+
+        let padding = ((len + 3) & !3) - len;
+
+        if offset + len + padding > buf.len() {
+            dissection.issues.push(format!(
+                "{name} ({ty:#06x}) is missing its padding to a 32-bit boundary."
+            ));
+        }
+
+        let meta = AttributeMeta { ty, offset, len };
+
+        let (value, valid) = describe(buf, &meta, key);
+
+        let value = value.unwrap_or_else(|err| {
+            dissection.issues.push(err.to_string());
+
+            format!("{} (malformed)", hex(&buf[offset..(offset + len)]))
+        });
+
+        if valid == Some(false) {
+            dissection
+                .issues
+                .push(format!("{name} ({ty:#06x}) doesn't match the message."));
+        }
+
+        dissection.attributes.push(DissectedAttribute {
+            ty,
+            offset,
+            len,
+            padding,
+            value,
+            valid,
+        });
+
+        idx = offset + len + padding;
+    }
+
+    dissection
+}
+
+/// The result of [dissect].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dissection {
+    /// The header, or `None` if the packet was too small for it.
+    pub header: Option<DissectedHeader>,
+    pub attributes: Vec<DissectedAttribute>,
+    /// Malformed parts of the packet and RFC violations.
+    pub issues: Vec<String>,
+}
+
+/// The header of a dissected message.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DissectedHeader {
+    /// The message type, combining the class and method.
+    pub ty: u16,
+    pub length: u16,
+    pub magic: u32,
+    pub transaction_id: [u8; 12],
+}
+
+impl DissectedHeader {
+    pub fn class(&self) -> u16 {
+        (self.ty & 0x0100) >> 7 | (self.ty & 0x0010) >> 4
+    }
+
+    pub fn method(&self) -> u16 {
+        (self.ty & 0x3E00) >> 2 | (self.ty & 0x00E0) >> 1 | (self.ty & 0x000F)
+    }
+}
+
+/// A dissected attribute.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DissectedAttribute {
+    pub ty: u16,
+    /// The offset of the attribute body in the message.
+    pub offset: usize,
+    /// The length of the attribute body, excluding padding.
+    pub len: usize,
+    pub padding: usize,
+    /// The decoded value.
+    pub value: String,
+    /// Whether FINGERPRINT or an integrity attribute matches the message.
+    ///
+    /// `None` if the attribute can't be checked.
+    pub valid: Option<bool>,
+}
+
+impl Display for Dissection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some(header) = &self.header else {
+            writeln!(f, "Malformed STUN packet")?;
+
+            return write_issues(f, &self.issues);
+        };
+
+        let class = match header.class() {
+            REQUEST_CLASS => "Request",
+            INDICATION_CLASS => "Indication",
+            SUCCESS_RESPONSE_CLASS => "Success Response",
+            _ => "Error Response",
+        };
+
+        let method = method_name(header.method()).unwrap_or("Unknown");
+
+        writeln!(f, "STUN {method} {class}")?;
+        writeln!(
+            f,
+            "    Message Type: {:#06x} (class: {class}, method: {method} {:#05x})",
+            header.ty,
+            header.method()
+        )?;
+        writeln!(f, "    Message Length: {}", header.length)?;
+        writeln!(f, "    Magic Cookie: {:#010x}", header.magic)?;
+        writeln!(f, "    Transaction ID: {}", hex(&header.transaction_id))?;
+
+        if !self.attributes.is_empty() {
+            writeln!(f, "    Attributes:")?;
+        }
+
+        for attr in &self.attributes {
+            let name = attribute_name(attr.ty).unwrap_or("Unknown");
+
+            write!(f, "        {name} ({:#06x}), length: {}", attr.ty, attr.len)?;
+
+            if attr.padding > 0 {
+                write!(f, " (+{} bytes of padding)", attr.padding)?;
+            }
+
+            write!(f, ": {}", attr.value)?;
+
+            match attr.valid {
+                Some(true) => writeln!(f, " [correct]")?,
+                Some(false) => writeln!(f, " [incorrect]")?,
+                None => writeln!(f)?,
+            }
+        }
+
+        write_issues(f, &self.issues)
+    }
+}
+
+fn write_issues(f: &mut fmt::Formatter<'_>, issues: &[String]) -> fmt::Result {
+    if !issues.is_empty() {
+        writeln!(f, "    Issues:")?;
+    }
+
+    for issue in issues {
+        writeln!(f, "        {issue}")?;
+    }
+
+    Ok(())
+}
+
+/// Decodes the value of an attribute and checks FINGERPRINT and the integrity attributes.
+fn describe(
+    buf: &[u8],
+    meta: &AttributeMeta,
+    key: Option<&[u8]>,
+) -> (Result<String, AttributeError>, Option<bool>) {
+    let value = &buf[meta.offset..(meta.offset + meta.len)];
+
+    let description = match meta.ty {
+        MappedAddress::TY | RESPONSE_ORIGIN_TY | OTHER_ADDRESS_TY => {
+            MappedAddress::decode(buf, meta).map(|a| a.addr().to_string())
+        }
+        XorMappedAddress::TY | XOR_PEER_ADDRESS_TY | XOR_RELAYED_ADDRESS_TY => {
+            XorMappedAddress::decode(buf, meta).map(|a| a.addr().to_string())
+        }
+        AlternateServer::TY => AlternateServer::decode(buf, meta).map(|a| a.addr().to_string()),
+        Username::TY => decode_str::<Username>(buf, meta).map(|s| format!("{s:?}")),
+        Realm::TY => decode_str::<Realm>(buf, meta).map(|s| format!("{s:?}")),
+        Nonce::TY => decode_str::<Nonce>(buf, meta).map(|s| format!("{s:?}")),
+        Software::TY => decode_str::<Software>(buf, meta).map(|s| format!("{s:?}")),
+        AlternateDomain::TY => decode_str::<AlternateDomain>(buf, meta).map(|s| format!("{s:?}")),
+        ErrorCode::TY => {
+            ErrorCode::decode(buf, meta).map(|e| format!("{} {:?}", e.code(), e.reason()))
+        }
+        UnknownAttributes::TY => UnknownAttributes::decode(buf, meta).map(|u| {
+            let names: Vec<_> = u
+                .attributes()
+                .iter()
+                .map(|ty| match attribute_name(*ty) {
+                    Some(name) => format!("{name} ({ty:#06x})"),
+                    None => format!("{ty:#06x}"),
+                })
+                .collect();
+
+            names.join(", ")
+        }),
+        PasswordAlgorithm::TY => Ok(describe_algorithms(&value[..value.len().min(4)])),
+        PasswordAlgorithms::TY => Ok(describe_algorithms(value)),
+        Fingerprint::TY => {
+            let valid = Fingerprint::verify(buf, meta);
+
+            return (Ok(hex(value)), Some(valid));
+        }
+        MessageIntegrity::TY => {
+            let valid = key.map(|key| MessageIntegrity::verify(buf, meta, key));
+
+            return (Ok(hex(value)), valid);
+        }
+        MessageIntegritySha256::TY => {
+            let valid = key.map(|key| MessageIntegritySha256::verify(buf, meta, key));
+
+            return (Ok(hex(value)), valid);
+        }
+        CHANGE_REQUEST_TY if meta.len == 4 => {
+            let flags = value[3];
+
+            Ok(format!(
+                "change IP: {}, change port: {}",
+                flags & 0x04 != 0,
+                flags & 0x02 != 0
+            ))
+        }
+        PRIORITY_TY | LIFETIME_TY if meta.len == 4 => {
+            Ok(u32::from_be_bytes(value.try_into().unwrap()).to_string())
+        }
+        ICE_CONTROLLED_TY | ICE_CONTROLLING_TY if meta.len == 8 => Ok(format!(
+            "tie-breaker {:#018x}",
+            u64::from_be_bytes(value.try_into().unwrap())
+        )),
+        CHANNEL_NUMBER_TY if meta.len == 4 => {
+            Ok(format!("{:#06x}", u16::from_be_bytes([value[0], value[1]])))
+        }
+        _ if value.is_empty() => Ok("(empty)".into()),
+        _ => Ok(hex(value)),
+    };
+
+    (description, None)
+}
+
+/// Lists the password algorithms, skipping their parameters.
+fn describe_algorithms(value: &[u8]) -> String {
+    let mut algorithms = vec![];
+    let mut i = 0;
+
+    while i + 4 <= value.len() {
+        let id = u16::from_be_bytes([value[i], value[i + 1]]);
+        let len = u16::from_be_bytes([value[i + 2], value[i + 3]]) as usize;
+
+        algorithms.push(match id {
+            0x0001 => "MD5".to_string(),
+            0x0002 => "SHA-256".to_string(),
+            id => format!("{id:#06x}"),
+        });
+
+        i += 4 + ((len + 3) & !3);
+    }
+
+    algorithms.join(", ")
+}
+
+fn hex(bytes: &[u8]) -> String {
+    let mut s = String::with_capacity(bytes.len() * 2);
+
+    for b in bytes {
+        let _ = write!(s, "{b:02x}");
+    }
+
+    s
+}
+
+const RESPONSE_ORIGIN_TY: u16 = 0x802B;
+const OTHER_ADDRESS_TY: u16 = 0x802C;
+const CHANNEL_NUMBER_TY: u16 = 0x000C;
+const LIFETIME_TY: u16 = 0x000D;
+const PRIORITY_TY: u16 = 0x0024;
+const ICE_CONTROLLED_TY: u16 = 0x8029;
+const ICE_CONTROLLING_TY: u16 = 0x802A;
+
+/// Looks up the name of an attribute type.
+///
+/// Covers the [IANA Registry for STUN Attributes](https://www.iana.org/assignments/stun-parameters/stun-parameters.xhtml#stun-parameters-4).
+/// Reserved types that were defined by RFC 3489 are named after their original attributes.
+pub fn attribute_name(ty: u16) -> Option<&'static str> {
+    Some(match ty {
+        // comprehension-required
+        0x0001 => "MAPPED-ADDRESS",
+        0x0002 => "RESPONSE-ADDRESS",
+        0x0003 => "CHANGE-REQUEST",
+        0x0004 => "SOURCE-ADDRESS",
+        0x0005 => "CHANGED-ADDRESS",
+        0x0006 => "USERNAME",
+        0x0007 => "PASSWORD",
+        0x0008 => "MESSAGE-INTEGRITY",
+        0x0009 => "ERROR-CODE",
+        0x000A => "UNKNOWN-ATTRIBUTES",
+        0x000B => "REFLECTED-FROM",
+        0x000C => "CHANNEL-NUMBER",
+        0x000D => "LIFETIME",
+        0x0010 => "BANDWIDTH",
+        0x0012 => "XOR-PEER-ADDRESS",
+        0x0013 => "DATA",
+        0x0014 => "REALM",
+        0x0015 => "NONCE",
+        0x0016 => "XOR-RELAYED-ADDRESS",
+        0x0017 => "REQUESTED-ADDRESS-FAMILY",
+        0x0018 => "EVEN-PORT",
+        0x0019 => "REQUESTED-TRANSPORT",
+        0x001A => "DONT-FRAGMENT",
+        0x001B => "ACCESS-TOKEN",
+        0x001C => "MESSAGE-INTEGRITY-SHA256",
+        0x001D => "PASSWORD-ALGORITHM",
+        0x001E => "USERHASH",
+        0x0020 => "XOR-MAPPED-ADDRESS",
+        0x0021 => "TIMER-VAL",
+        0x0022 => "RESERVATION-TOKEN",
+        0x0024 => "PRIORITY",
+        0x0025 => "USE-CANDIDATE",
+        0x0026 => "PADDING",
+        0x0027 => "RESPONSE-PORT",
+        0x002A => "CONNECTION-ID",
+        // comprehension-optional
+        0x8000 => "ADDITIONAL-ADDRESS-FAMILY",
+        0x8001 => "ADDRESS-ERROR-CODE",
+        0x8002 => "PASSWORD-ALGORITHMS",
+        0x8003 => "ALTERNATE-DOMAIN",
+        0x8004 => "ICMP",
+        0x8022 => "SOFTWARE",
+        0x8023 => "ALTERNATE-SERVER",
+        0x8025 => "TRANSACTION_TRANSMIT_COUNTER",
+        0x8027 => "CACHE-TIMEOUT",
+        0x8028 => "FINGERPRINT",
+        0x8029 => "ICE-CONTROLLED",
+        0x802A => "ICE-CONTROLLING",
+        0x802B => "RESPONSE-ORIGIN",
+        0x802C => "OTHER-ADDRESS",
+        0x802D => "ECN-CHECK STUN",
+        0x802E => "THIRD-PARTY-AUTHORIZATION",
+        0x8030 => "MOBILITY-TICKET",
+        0xC000 => "CISCO-STUN-FLOWDATA",
+        0xC001 => "ENF-FLOW-DESCRIPTION",
+        0xC002 => "ENF-NETWORK-STATUS",
+        0xC057 => "GOOG-NETWORK-INFO",
+        0xC058 => "GOOG-LAST-ICE-CHECK-RECEIVED",
+        0xC059 => "GOOG-MISC-INFO",
+        0xC05A => "GOOG-OBSOLETE-1",
+        0xC05B => "GOOG-CONNECTION-ID",
+        0xC05C => "GOOG-DELTA",
+        0xC05D => "GOOG-DELTA-ACK",
+        0xC05E => "GOOG-DELTA-SYNC-REQ",
+        0xC060 => "GOOG-MESSAGE-INTEGRITY-32",
+        _ => return None,
+    })
+}
+
+/// Looks up the name of a method.
+///
+/// Covers the [IANA Registry for STUN Methods](https://www.iana.org/assignments/stun-parameters/stun-parameters.xhtml#stun-parameters-2).
+pub fn method_name(method: u16) -> Option<&'static str> {
+    Some(match method {
+        0x001 => "Binding",
+        0x002 => "SharedSecret",
+        0x003 => "Allocate",
+        0x004 => "Refresh",
+        0x006 => "Send",
+        0x007 => "Data",
+        0x008 => "CreatePermission",
+        0x009 => "ChannelBind",
+        0x00A => "Connect",
+        0x00B => "ConnectionBind",
+        0x00C => "ConnectionAttempt",
+        0x080 => "GOOG-PING",
+        _ => return None,
+    })
+}
@@ -0,0 +1,224 @@
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+/// A frame on a byte stream, such as TCP or TLS.
+///
+/// Stream transports don't preserve message boundaries,
+/// so frames are delimited by the length in their headers.
+///
+/// See [RFC8489 Section 6.2.2](https://datatracker.ietf.org/doc/html/rfc8489#section-6.2.2)
+/// and [RFC8656 Section 12.5](https://datatracker.ietf.org/doc/html/rfc8656#section-12.5) for more details.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Frame {
+    /// A STUN message, including its header.
+    Message(Bytes),
+    /// A TURN ChannelData message.
+    ChannelData { channel: u16, data: Bytes },
+}
+
+impl Frame {
+    /// Calculates the size of the frame on the stream, including padding.
+    pub fn size(&self) -> usize {
+        match self {
+            Self::Message(message) => message.len(),
+            Self::ChannelData { data, .. } => 4 + ((data.len() + 3) & !3),
+        }
+    }
+
+    /// Encodes the frame, padding ChannelData to a multiple of 4 bytes.
+    pub fn encode(&self, buf: &mut impl BufMut) -> Result<(), FramingError> {
+        match self {
+            Self::Message(message) => {
+                // the peer frames the message by its header,
+                // so it must start with two zero bits and be exactly as long as its header says
+                let is_message = message.first().is_some_and(|b| b >> 6 == 0b00);
+
+                if !is_message || frame_size(message)? != Some(message.len()) {
+                    return Err(FramingError {
+                        ty: FramingErrorTy::BadLength,
+                        reason: format!(
+                            "Message of {} bytes doesn't match its header.",
+                            message.len()
+                        ),
+                    });
+                }
+
+                buf.put_slice(message);
+            }
+            Self::ChannelData { channel, data } => {
+                if !(0x4000..=0x7FFF).contains(channel) {
+                    return Err(FramingError {
+                        ty: FramingErrorTy::BadChannel,
+                        reason: format!("Channel number {channel:#06x} is out of range."),
+                    });
+                }
+
+                if data.len() > u16::MAX as usize {
+                    return Err(FramingError {
+                        ty: FramingErrorTy::BadLength,
+                        reason: format!("ChannelData of {} bytes is too long.", data.len()),
+                    });
+                }
+
+                buf.put_u16(*channel);
+                buf.put_u16(data.len() as u16);
+                buf.put_slice(data);
+                buf.put_bytes(0, ((data.len() + 3) & !3) - data.len());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Calculates the size of the frame at the start of `buf` from its header.
+///
+/// Returns `None` if `buf` doesn't contain the full header yet.
+pub fn frame_size(buf: &[u8]) -> Result<Option<usize>, FramingError> {
+    if buf.len() < 4 {
+        return Ok(None);
+    }
+
+    let len = u16::from_be_bytes([buf[2], buf[3]]) as usize;
+
+    match buf[0] >> 6 {
+        // STUN messages start with two zero bits
+        0b00 => {
+            if !len.is_multiple_of(4) {
+                return Err(FramingError {
+                    ty: FramingErrorTy::BadLength,
+                    reason: format!("Message length of {len} bytes isn't a multiple of 4."),
+                });
+            }
+
+            Ok(Some(20 + len))
+        }
+        // channel numbers are in the range 0x4000 to 0x7FFF
+        0b01 => Ok(Some(4 + ((len + 3) & !3))),
+        _ => Err(FramingError {
+            ty: FramingErrorTy::UnknownFrame,
+            reason: format!("Frame starts with unknown byte {:#04x}.", buf[0]),
+        }),
+    }
+}
+
+/// An incremental parser for frames on a byte stream.
+///
+/// Bytes are pushed as they are read from the stream,
+/// regardless of where the segments are split,
+/// and frames are taken as soon as they are complete.
+#[derive(Debug, Default)]
+pub struct FrameParser {
+    buf: BytesMut,
+}
+
+impl FrameParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends bytes read from the stream.
+    pub fn push(&mut self, data: &[u8]) {
+        self.buf.extend_from_slice(data);
+    }
+
+    /// Takes the next complete frame.
+    ///
+    /// Returns `None` if more bytes are needed.
+    /// After an error, the stream can't be resynchronized and should be closed.
+    pub fn next_frame(&mut self) -> Result<Option<Frame>, FramingError> {
+        parse_frame(&mut self.buf)
+    }
+
+    /// The number of buffered bytes that aren't part of a complete frame yet.
+    pub fn buffered(&self) -> usize {
+        self.buf.len()
+    }
+}
+
+fn parse_frame(buf: &mut BytesMut) -> Result<Option<Frame>, FramingError> {
+    let Some(size) = frame_size(buf)? else {
+        return Ok(None);
+    };
+
+    if buf.len() < size {
+        buf.reserve(size - buf.len());
+        return Ok(None);
+    }
+
+    let mut frame = buf.split_to(size).freeze();
+
+    Ok(Some(match frame[0] >> 6 {
+        0b00 => Frame::Message(frame),
+        _ => {
+            let channel = frame.get_u16();
+            let len = frame.get_u16() as usize;
+
+            Frame::ChannelData {
+                channel,
+                data: frame.slice(..len),
+            }
+        }
+    }))
+}
+
+#[derive(Debug)]
+pub struct FramingError {
+    pub ty: FramingErrorTy,
+    pub reason: String,
+}
+
+impl std::fmt::Display for FramingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.reason)
+    }
+}
+
+impl std::error::Error for FramingError {}
+
+impl From<FramingError> for std::io::Error {
+    fn from(err: FramingError) -> Self {
+        Self::new(std::io::ErrorKind::InvalidData, err)
+    }
+}
+
+#[derive(Debug)]
+pub enum FramingErrorTy {
+    /// The frame doesn't start with a STUN or ChannelData header.
+    UnknownFrame,
+    /// The length in the header is invalid.
+    BadLength,
+    /// The channel number is outside of 0x4000 to 0x7FFF.
+    BadChannel,
+}
+
+cfg_if::cfg_if! {
+    if #[cfg(feature = "async_tokio")] {
+        use tokio_util::codec::{Decoder, Encoder};
+
+        /// A codec for framing STUN and ChannelData messages on a byte stream.
+        ///
+        /// Use it with [tokio_util::codec::Framed] to turn a TCP or TLS stream
+        /// into a stream of [Frame]s.
+        #[derive(Debug, Default, Clone, Copy)]
+        pub struct FrameCodec;
+
+        impl Decoder for FrameCodec {
+            type Item = Frame;
+            type Error = std::io::Error;
+
+            fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+                Ok(parse_frame(src)?)
+            }
+        }
+
+        impl Encoder<Frame> for FrameCodec {
+            type Error = std::io::Error;
+
+            fn encode(&mut self, item: Frame, dst: &mut BytesMut) -> Result<(), Self::Error> {
+                dst.reserve(item.size());
+
+                Ok(item.encode(dst)?)
+            }
+        }
+    }
+}
@@ -1,6 +1,10 @@
 /// A 96-bit transaction ID.
-#[derive(Debug, PartialEq)]
-pub struct TransactionId(pub [u8; 12]);
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct TransactionId(
+    #[cfg_attr(feature = "serde", serde(with = "super::serde_util::hex"))] pub [u8; 12],
+);
 
 impl TransactionId {
     /// Creates a new transaction ID from a `u128`.
@@ -16,3 +20,28 @@ impl Default for TransactionId {
         Self(rand::random())
     }
 }
+
+/// A 128-bit transaction ID of classic STUN.
+///
+/// RFC 3489 didn't have a magic cookie, so the first 32 bits of the ID take its place.
+///
+/// See [RFC8489 Section 6](https://datatracker.ietf.org/doc/html/rfc8489#section-6) for more details.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct ClassicTransactionId(
+    #[cfg_attr(feature = "serde", serde(with = "super::serde_util::hex"))] pub [u8; 16],
+);
+
+impl ClassicTransactionId {
+    /// Creates a new transaction ID from a `u128`.
+    pub fn new(id: u128) -> Self {
+        Self(id.to_be_bytes())
+    }
+}
+
+impl Default for ClassicTransactionId {
+    fn default() -> Self {
+        Self(rand::random())
+    }
+}
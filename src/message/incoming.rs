@@ -1,36 +1,263 @@
 use super::{meta::MessageMeta, *};
 
 /// An incoming STUN message.
+///
+/// With the `serde` feature, custom attributes aren't serialized,
+/// since they can only be decoded with a [Registry].
 #[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IncomingMessage {
     pub transaction_id: TransactionId,
+    /// The 128-bit transaction ID, if this is a classic STUN message.
+    ///
+    /// Responses must be built with [MessageBuilder], which echoes the full ID.
+    pub classic: Option<ClassicTransactionId>,
     pub body: ClassTy,
     pub software: Option<Software>,
     pub fingerprint: Option<Fingerprint>,
+    /// Comprehension-required attributes (0x0000 to 0x7FFF) that weren't understood.
+    ///
+    /// A server should reject a request with any of these using [IncomingMessage::unknown_attributes].
+    #[cfg_attr(feature = "serde", serde(with = "super::serde_util::unknown"))]
+    pub unknown_required: Vec<(u16, Bytes)>,
+    /// Comprehension-optional attributes (0x8000 to 0xFFFF) that weren't understood.
+    #[cfg_attr(feature = "serde", serde(with = "super::serde_util::unknown"))]
+    pub unknown_optional: Vec<(u16, Bytes)>,
+    /// Attributes that were decoded with a [Registry].
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub custom: Vec<Box<dyn DynAttribute>>,
 }
 
 impl IncomingMessage {
     pub fn decode(buf: &[u8]) -> Result<Self, IncomingError> {
+        Self::decode_with(buf, &Registry::default())
+    }
+
+    /// Decodes the message, using `registry` to decode custom attributes and methods.
+    pub fn decode_with(buf: &[u8], registry: &Registry) -> Result<Self, IncomingError> {
         let meta = MessageMeta::decode(buf)?;
 
-        let body = ClassTy::decode(buf, &meta)?;
+        Self::decode_meta(buf, &meta, registry)
+    }
+
+    /// Decodes the message, also accepting classic STUN messages without a magic cookie.
+    ///
+    /// The deprecated CHANGE-REQUEST and SOURCE-ADDRESS attributes of classic messages are ignored.
+    ///
+    /// See [RFC3489](https://datatracker.ietf.org/doc/html/rfc3489) for more details.
+    pub fn decode_classic(buf: &[u8], registry: &Registry) -> Result<Self, IncomingError> {
+        let meta = MessageMeta::decode_classic(buf)?;
+
+        Self::decode_meta(buf, &meta, registry)
+    }
+
+    fn decode_meta(
+        buf: &[u8],
+        meta: &MessageMeta,
+        registry: &Registry,
+    ) -> Result<Self, IncomingError> {
+        let body = ClassTy::decode(buf, meta, registry)?;
 
         let mut software = None;
+        let mut fingerprint = None;
+        let mut unknown_required = vec![];
+        let mut unknown_optional = vec![];
+        let mut custom = vec![];
+        let mut integrity = false;
 
-        for attr in meta.attributes {
+        for (i, attr) in meta.attributes.iter().enumerate() {
             match attr.ty {
-                Software::TY => {
-                    software = Some(Software::decode(buf, &attr));
+                Software::TY => software = Some(Software::decode(buf, attr)?),
+                Fingerprint::TY => {
+                    if i != meta.attributes.len() - 1 {
+                        return Err(IncomingError {
+                            ty: IncomingErrorTy::BadFingerprint,
+                            reason: "FINGERPRINT must be the last attribute.".into(),
+                        });
+                    }
+
+                    if !Fingerprint::verify(buf, attr) {
+                        return Err(IncomingError {
+                            ty: IncomingErrorTy::BadFingerprint,
+                            reason: "FINGERPRINT doesn't match the message.".into(),
+                        });
+                    }
+
+                    fingerprint = Some(Fingerprint::decode(buf, attr)?);
                 }
-                _ => (),
+                MessageIntegrity::TY | MessageIntegritySha256::TY => integrity = true,
+                // attributes following the integrity attributes are ignored
+                _ if integrity => (),
+                CHANGE_REQUEST_TY | SOURCE_ADDRESS_TY if meta.classic.is_some() => (),
+                ty => match registry.decode_attribute(buf, attr) {
+                    Some(attribute) => custom.push(attribute?),
+                    None if !is_known(ty) => {
+                        let value =
+                            Bytes::copy_from_slice(&buf[attr.offset..(attr.offset + attr.len)]);
+
+                        match is_comprehension_required(ty) {
+                            true => unknown_required.push((ty, value)),
+                            false => unknown_optional.push((ty, value)),
+                        }
+                    }
+                    None => (),
+                },
             }
         }
 
         Ok(Self {
             transaction_id: meta.id,
+            classic: meta.classic,
             body,
-            software: todo!(),
-            fingerprint: todo!(),
+            software,
+            fingerprint,
+            unknown_required,
+            unknown_optional,
+            custom,
+        })
+    }
+
+    /// Creates a builder that encodes this message again, e.g. after it was deserialized.
+    ///
+    /// The original order of the attributes isn't recorded, so it isn't preserved:
+    /// the SOFTWARE attribute is encoded first, followed by the attributes of the class,
+    /// the custom attributes and the unknown attributes.
+    /// The integrity attributes can't be encoded without the key,
+    /// so they must be added again with [MessageBuilder::integrity].
+    /// FINGERPRINT is recomputed if the message had one.
+    pub fn to_builder(&self) -> MessageBuilder {
+        let (class, method) = match &self.body {
+            ClassTy::Request { method, .. } => (REQUEST_CLASS, method),
+            ClassTy::Indication { method } => (INDICATION_CLASS, method),
+            ClassTy::SuccessResponse { method } => (SUCCESS_RESPONSE_CLASS, method),
+            ClassTy::ErrorResponse { method, .. } => (ERROR_RESPONSE_CLASS, method),
+        };
+
+        let mut builder = MessageBuilder::new(class, method.method())
+            .transaction_id(self.transaction_id)
+            .classic(self.classic);
+
+        if let Some(software) = &self.software {
+            builder = builder.push(software.clone());
+        }
+
+        match &self.body {
+            ClassTy::Request {
+                authorization: Some(authorization),
+                ..
+            } => {
+                let IncomingAuthorization {
+                    user,
+                    realm,
+                    nonce,
+                    algorithms,
+                    algorithm,
+                    ..
+                } = authorization;
+
+                match user {
+                    Some(UserTy::Username(username)) => builder = builder.push(username.clone()),
+                    Some(UserTy::Userhash(userhash)) => builder = builder.push(userhash.clone()),
+                    None => (),
+                }
+
+                if let Some(nonce) = nonce {
+                    builder = builder.push(nonce.clone());
+                }
+
+                if let Some(realm) = realm {
+                    builder = builder.push(realm.clone());
+                }
+
+                if let Some(algorithms) = algorithms {
+                    builder = builder.push(algorithms.clone());
+                }
+
+                if let Some(algorithm) = algorithm {
+                    builder = builder.push(algorithm.clone());
+                }
+            }
+            ClassTy::ErrorResponse {
+                error_code,
+                unknown_attributes,
+                ..
+            } => {
+                builder = builder.push(error_code.clone());
+
+                if let Some(unknown_attributes) = unknown_attributes {
+                    builder = builder.push(unknown_attributes.clone());
+                }
+            }
+            _ => (),
+        }
+
+        for attribute in &self.custom {
+            builder = builder.push_raw(attribute.ty(), attribute.encode_body());
+        }
+
+        for (ty, value) in self.unknown_required.iter().chain(&self.unknown_optional) {
+            builder = builder.push_raw(*ty, value.clone());
+        }
+
+        if self.fingerprint.is_some() {
+            builder = builder.fingerprint();
+        }
+
+        builder
+    }
+
+    /// Lists the comprehension-required attributes that weren't understood.
+    ///
+    /// Returns `None` if every comprehension-required attribute was understood.
+    /// Otherwise, a server should answer a request with [ErrorCode::UnknownAttribute]
+    /// and the returned attribute.
+    ///
+    /// See [RFC8489 Section 7.3](https://datatracker.ietf.org/doc/html/rfc8489#section-7.3) for more details.
+    pub fn unknown_attributes(&self) -> Option<UnknownAttributes> {
+        if self.unknown_required.is_empty() {
+            return None;
+        }
+
+        let attributes = self.unknown_required.iter().map(|(ty, _)| *ty).collect();
+
+        Some(UnknownAttributes::new(attributes))
+    }
+
+    /// Creates a success response to this message.
+    ///
+    /// The response reuses the transaction ID and method of this message.
+    /// Returns `None` if the method of this message isn't `T`.
+    pub fn success_response<T: Method>(&self) -> Option<OutgoingMessage<SuccessResponse<T>>> {
+        let method = T::from_ty(self.body.method())?;
+
+        Some(OutgoingMessage {
+            transaction_id: self.transaction_id,
+            body: SuccessResponse { method },
+            software: false,
+            fingerprint: false,
+        })
+    }
+
+    /// Creates an error response to this message.
+    ///
+    /// The response reuses the transaction ID and method of this message.
+    /// Returns `None` if the method of this message isn't `T`.
+    pub fn error_response<T: Method>(
+        &self,
+        error_code: ErrorCode,
+        unknown_attributes: Option<UnknownAttributes>,
+    ) -> Option<OutgoingMessage<ErrorResponse<T>>> {
+        let method = T::from_ty(self.body.method())?;
+
+        Some(OutgoingMessage {
+            transaction_id: self.transaction_id,
+            body: ErrorResponse {
+                method,
+                error_code,
+                unknown_attributes,
+            },
+            software: false,
+            fingerprint: false,
         })
     }
 }
@@ -41,11 +268,53 @@ pub struct IncomingError {
     pub reason: String,
 }
 
+impl std::fmt::Display for IncomingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.reason)
+    }
+}
+
+impl std::error::Error for IncomingError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.ty {
+            IncomingErrorTy::BadAttribute(e) => Some(e),
+            IncomingErrorTy::BadString(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<PrecisError> for IncomingError {
+    fn from(err: PrecisError) -> Self {
+        Self {
+            reason: err.to_string(),
+            ty: IncomingErrorTy::BadString(err),
+        }
+    }
+}
+
+impl From<AttributeError> for IncomingError {
+    fn from(err: AttributeError) -> Self {
+        Self {
+            reason: err.to_string(),
+            ty: IncomingErrorTy::BadAttribute(err),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum IncomingErrorTy {
     BadFormat,
     BadLength,
     BadMagic,
+    BadFingerprint,
+    BadAttribute(AttributeError),
     UnknownClass,
     UnknownMethod,
+    /// The security features of the server were stripped or altered.
+    BidDown,
+    /// None of the password algorithms of the server are supported.
+    UnsupportedAlgorithm,
+    /// A string was rejected by the OpaqueString profile.
+    BadString(PrecisError),
 }
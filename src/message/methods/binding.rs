@@ -1,19 +1,27 @@
 use super::*;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Binding;
 
 pub const BINDING_METHOD: u16 = 0x01;
 
 impl Method for Binding {
-    const METHOD: u16 = 0x01;
+    const METHOD: u16 = BINDING_METHOD;
 
-    fn encode(&self, buf: &mut [u8], offset: &mut usize) {}
+    fn encode(&self, _buf: &mut [u8], _offset: &mut usize) {}
 
-    fn decode(meta: &MessageMeta) -> Self {
+    fn decode(_meta: &MessageMeta) -> Self {
         Self
     }
 
+    fn from_ty(ty: &MethodTy) -> Option<Self> {
+        match ty {
+            MethodTy::Binding(b) => Some(b.clone()),
+            _ => None,
+        }
+    }
+
     fn size(&self) -> usize {
         0
     }
@@ -0,0 +1,84 @@
+use std::{any::Any, fmt::Debug};
+
+use super::*;
+
+/// Trait for method types defined outside of this crate,
+/// such as private methods.
+///
+/// Every custom method is also a [Method],
+/// so it can be used with the message classes like the built-in methods.
+/// To decode it as part of an [IncomingMessage], register it with a [Registry].
+pub trait CustomMethod: Debug + Clone + PartialEq + Send + Sync + 'static {
+    /// The method number, in the range of 0x000 to 0xFFF.
+    const METHOD: u16;
+
+    fn encode(&self, _buf: &mut [u8], _offset: &mut usize) {}
+
+    fn decode(meta: &MessageMeta) -> Self;
+
+    fn size(&self) -> usize {
+        0
+    }
+}
+
+impl<T: CustomMethod> sealed::Sealed for T {}
+
+impl<T: CustomMethod> Method for T {
+    const METHOD: u16 = <T as CustomMethod>::METHOD;
+
+    fn encode(&self, buf: &mut [u8], offset: &mut usize) {
+        CustomMethod::encode(self, buf, offset)
+    }
+
+    fn decode(meta: &MessageMeta) -> Self {
+        <T as CustomMethod>::decode(meta)
+    }
+
+    fn from_ty(ty: &MethodTy) -> Option<Self> {
+        match ty {
+            MethodTy::Custom(m) => m.downcast_ref::<T>().cloned(),
+            _ => None,
+        }
+    }
+
+    fn size(&self) -> usize {
+        CustomMethod::size(self)
+    }
+}
+
+/// A type-erased [CustomMethod].
+pub trait DynMethod: Debug + Send + Sync {
+    /// The method number.
+    fn method(&self) -> u16;
+
+    fn as_any(&self) -> &dyn Any;
+
+    fn dyn_eq(&self, other: &dyn DynMethod) -> bool;
+}
+
+impl<T: CustomMethod> DynMethod for T {
+    fn method(&self) -> u16 {
+        <T as CustomMethod>::METHOD
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn dyn_eq(&self, other: &dyn DynMethod) -> bool {
+        other.as_any().downcast_ref::<T>() == Some(self)
+    }
+}
+
+impl dyn DynMethod {
+    /// Returns the method if it is of type `T`.
+    pub fn downcast_ref<T: CustomMethod>(&self) -> Option<&T> {
+        self.as_any().downcast_ref()
+    }
+}
+
+impl PartialEq for dyn DynMethod {
+    fn eq(&self, other: &Self) -> bool {
+        self.dyn_eq(other)
+    }
+}
@@ -1,6 +1,8 @@
 mod binding;
+mod custom;
 
 pub use binding::Binding;
+pub use custom::*;
 
 use super::*;
 
@@ -13,21 +15,71 @@ mod sealed {
 #[derive(Debug, PartialEq)]
 pub enum MethodTy {
     Binding(Binding),
+    /// A method registered with a [Registry].
+    Custom(Box<dyn DynMethod>),
 }
 
 impl MethodTy {
-    pub fn decode(meta: &MessageMeta) -> Result<Self, IncomingError> {
-        return match meta.method {
+    pub fn decode(meta: &MessageMeta, registry: &Registry) -> Result<Self, IncomingError> {
+        match meta.method {
             binding::BINDING_METHOD => Ok(MethodTy::Binding(Binding::decode(meta))),
-            m => Err(IncomingError {
-                ty: IncomingErrorTy::UnknownMethod,
-                reason: format!("Unknown method: {:#x?}.", m),
-            }),
-        };
+            m => match registry.decode_method(meta) {
+                Some(method) => Ok(MethodTy::Custom(method)),
+                None => Err(IncomingError {
+                    ty: IncomingErrorTy::UnknownMethod,
+                    reason: format!("Unknown method: {:#x?}.", m),
+                }),
+            },
+        }
+    }
+
+    /// The method number.
+    pub fn method(&self) -> u16 {
+        match self {
+            Self::Binding(_) => Binding::METHOD,
+            Self::Custom(m) => m.method(),
+        }
+    }
+}
+
+/// Represented by the name of the method, such as `"Binding"`,
+/// or the method number for custom methods.
+///
+/// Custom methods can only be decoded with a [Registry], so they can't be deserialized.
+#[cfg(feature = "serde")]
+impl serde::Serialize for MethodTy {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Binding(_) => s.serialize_str("Binding"),
+            Self::Custom(m) => s.serialize_u16(m.method()),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for MethodTy {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        #[derive(serde::Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Name(String),
+            Number(u16),
+        }
+
+        match Repr::deserialize(d)? {
+            Repr::Name(name) if name == "Binding" => Ok(Self::Binding(Binding)),
+            Repr::Number(binding::BINDING_METHOD) => Ok(Self::Binding(Binding)),
+            Repr::Name(name) => Err(serde::de::Error::custom(format!("unknown method: {name}"))),
+            Repr::Number(m) => Err(serde::de::Error::custom(format!(
+                "custom method {m:#x?} can't be deserialized"
+            ))),
+        }
     }
 }
 
 /// Sealed trait for method types.
+///
+/// Methods defined outside of this crate implement [CustomMethod] instead.
 pub trait Method: sealed::Sealed {
     const METHOD: u16;
 
@@ -35,5 +87,10 @@ pub trait Method: sealed::Sealed {
 
     fn decode(meta: &MessageMeta) -> Self;
 
+    /// Extracts the method from a decoded [MethodTy], if it matches.
+    fn from_ty(ty: &MethodTy) -> Option<Self>
+    where
+        Self: Sized;
+
     fn size(&self) -> usize;
 }
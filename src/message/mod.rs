@@ -2,43 +2,163 @@ pub use authorization::*;
 
 use bytes::{Bytes, BytesMut};
 
-use self::{attributes::*, meta::MessageMeta, methods::*};
+use self::{attributes::*, methods::*};
 
 pub mod attributes;
+pub mod demux;
 pub mod methods;
 
 mod authorization;
+mod builder;
+mod dissect;
+mod framing;
 mod id;
 mod incoming;
 mod meta;
 mod outgoing;
+mod precis;
+mod registry;
+#[cfg(feature = "serde")]
+mod serde_util;
+mod view;
 
+pub use builder::*;
+pub use dissect::*;
+pub use framing::*;
 pub use id::*;
 pub use incoming::*;
+pub use meta::*;
 pub use outgoing::*;
+pub use precis::*;
+pub use registry::*;
+pub use view::*;
 
-const MAGIC: u32 = 0x2112A442;
+pub(crate) const MAGIC: u32 = 0x2112A442;
+
+/// Checks whether `buf` contains a STUN message.
+///
+/// Only the header is checked: the top two bits must be zero,
+/// the magic cookie must be present and the message length must match `buf`.
+/// If the message ends with a FINGERPRINT attribute, it must match as well.
+///
+/// This is meant for quickly demultiplexing STUN from other protocols
+/// on the same transport address, so the attributes aren't decoded.
+pub fn is_stun(buf: &[u8]) -> bool {
+    if buf.len() < 20 || buf[0] & 0xC0 != 0 {
+        return false;
+    }
+
+    let len = u16::from_be_bytes([buf[2], buf[3]]) as usize;
+
+    if 20 + len != buf.len() || !len.is_multiple_of(4) {
+        return false;
+    }
+
+    if u32::from_be_bytes(buf[4..8].try_into().unwrap()) != MAGIC {
+        return false;
+    }
+
+    let fingerprint_size = attribute_size!(static Fingerprint);
+
+    if len >= fingerprint_size {
+        let offset = buf.len() - Fingerprint::SIZE;
+        let ty = u16::from_be_bytes([buf[offset - 4], buf[offset - 3]]);
+
+        if ty == Fingerprint::TY {
+            let meta = AttributeMeta {
+                ty,
+                offset,
+                len: u16::from_be_bytes([buf[offset - 2], buf[offset - 1]]) as usize,
+            };
+
+            return Fingerprint::verify(buf, &meta);
+        }
+    }
+
+    true
+}
 
 #[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ClassTy {
     Request {
         method: MethodTy,
-        authorization: Option<Authorization>,
+        authorization: Option<IncomingAuthorization>,
+    },
+    Indication {
+        method: MethodTy,
+    },
+    SuccessResponse {
+        method: MethodTy,
+    },
+    ErrorResponse {
+        method: MethodTy,
+        error_code: ErrorCode,
+        unknown_attributes: Option<UnknownAttributes>,
     },
 }
 
 impl ClassTy {
-    pub fn decode(buf: &[u8], meta: &MessageMeta) -> Result<Self, IncomingError> {
-        return match meta.class {
+    pub fn decode(
+        buf: &[u8],
+        meta: &MessageMeta,
+        registry: &Registry,
+    ) -> Result<Self, IncomingError> {
+        match meta.class {
             REQUEST_CLASS => Ok(ClassTy::Request {
-                method: MethodTy::decode(meta)?,
-                authorization: Authorization::decode(buf, meta),
+                method: MethodTy::decode(meta, registry)?,
+                authorization: IncomingAuthorization::decode(buf, meta)?,
+            }),
+            INDICATION_CLASS => Ok(ClassTy::Indication {
+                method: MethodTy::decode(meta, registry)?,
             }),
+            SUCCESS_RESPONSE_CLASS => Ok(ClassTy::SuccessResponse {
+                method: MethodTy::decode(meta, registry)?,
+            }),
+            ERROR_RESPONSE_CLASS => {
+                let method = MethodTy::decode(meta, registry)?;
+
+                let mut error_code = None;
+                let mut unknown_attributes = None;
+
+                for attr in &meta.attributes {
+                    match attr.ty {
+                        ErrorCode::TY => error_code = Some(ErrorCode::decode(buf, attr)?),
+                        UnknownAttributes::TY => {
+                            unknown_attributes = Some(UnknownAttributes::decode(buf, attr)?)
+                        }
+                        _ => (),
+                    }
+                }
+
+                let Some(error_code) = error_code else {
+                    return Err(IncomingError {
+                        ty: IncomingErrorTy::BadFormat,
+                        reason: "Error response is missing the ERROR-CODE attribute.".into(),
+                    });
+                };
+
+                Ok(ClassTy::ErrorResponse {
+                    method,
+                    error_code,
+                    unknown_attributes,
+                })
+            }
             c => Err(IncomingError {
                 ty: IncomingErrorTy::UnknownClass,
                 reason: format!("Unknown class: {:#x?}.", c),
             }),
-        };
+        }
+    }
+
+    /// The method of the message.
+    pub fn method(&self) -> &MethodTy {
+        match self {
+            Self::Request { method, .. }
+            | Self::Indication { method }
+            | Self::SuccessResponse { method }
+            | Self::ErrorResponse { method, .. } => method,
+        }
     }
 }
 
@@ -56,6 +176,9 @@ mod sealed {
     pub trait Sealed {}
 
     impl<T: super::Method> Sealed for super::Request<T> {}
+    impl<T: super::Method> Sealed for super::Indication<T> {}
+    impl<T: super::Method> Sealed for super::SuccessResponse<T> {}
+    impl<T: super::Method> Sealed for super::ErrorResponse<T> {}
 }
 
 /// Request Message Class.
@@ -64,7 +187,7 @@ pub struct Request<T: methods::Method> {
     pub authorization: Option<Authorization>,
 }
 
-const REQUEST_CLASS: u16 = 0b00;
+pub const REQUEST_CLASS: u16 = 0b00;
 
 impl<T: methods::Method> Class for Request<T> {
     const CLASS: u16 = REQUEST_CLASS;
@@ -89,6 +212,85 @@ impl<T: methods::Method> Class for Request<T> {
     }
 }
 
+/// Indication Message Class.
+///
+/// Indications don't receive a response.
+pub struct Indication<T: methods::Method> {
+    pub method: T,
+}
+
+pub const INDICATION_CLASS: u16 = 0b01;
+
+impl<T: methods::Method> Class for Indication<T> {
+    const CLASS: u16 = INDICATION_CLASS;
+    const METHOD: u16 = T::METHOD;
+
+    fn encode(&self, buf: &mut [u8], offset: &mut usize) {
+        self.method.encode(buf, offset);
+    }
+
+    fn size(&self) -> usize {
+        self.method.size()
+    }
+}
+
+/// Success Response Message Class.
+pub struct SuccessResponse<T: methods::Method> {
+    pub method: T,
+}
+
+pub const SUCCESS_RESPONSE_CLASS: u16 = 0b10;
+
+impl<T: methods::Method> Class for SuccessResponse<T> {
+    const CLASS: u16 = SUCCESS_RESPONSE_CLASS;
+    const METHOD: u16 = T::METHOD;
+
+    fn encode(&self, buf: &mut [u8], offset: &mut usize) {
+        self.method.encode(buf, offset);
+    }
+
+    fn size(&self) -> usize {
+        self.method.size()
+    }
+}
+
+/// Error Response Message Class.
+pub struct ErrorResponse<T: methods::Method> {
+    pub method: T,
+    pub error_code: ErrorCode,
+    /// Only used with [ErrorCode::UnknownAttribute].
+    pub unknown_attributes: Option<UnknownAttributes>,
+}
+
+pub const ERROR_RESPONSE_CLASS: u16 = 0b11;
+
+impl<T: methods::Method> Class for ErrorResponse<T> {
+    const CLASS: u16 = ERROR_RESPONSE_CLASS;
+    const METHOD: u16 = T::METHOD;
+
+    fn encode(&self, buf: &mut [u8], offset: &mut usize) {
+        self.method.encode(buf, offset);
+
+        encode_attribute(&self.error_code, buf, offset);
+
+        if let Some(ref u) = self.unknown_attributes {
+            encode_attribute(u, buf, offset);
+        }
+    }
+
+    fn size(&self) -> usize {
+        let error_code = &self.error_code;
+
+        let mut size = self.method.size() + attribute_size!(dyn error_code);
+
+        if let Some(ref u) = self.unknown_attributes {
+            size += attribute_size!(dyn u);
+        }
+
+        size
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::message::{methods::Binding, outgoing::OutgoingMessage};
@@ -130,7 +332,8 @@ mod tests {
             body: Request {
                 method: Binding,
                 authorization: Some(Authorization {
-                    credentials: Credentials::new_short_term(Username::new("Alice"), "Password"),
+                    credentials: Credentials::new_short_term(Username::new("Alice"), "Password")
+                        .unwrap(),
                     integrity: Integrity::Sha1,
                 }),
             },
@@ -141,5 +344,340 @@ mod tests {
         let buf = message.encode();
 
         assert_eq!(test_output, &*buf);
+
+        let verification =
+            IncomingAuthorization::verify(test_output, |_| Some(Bytes::from_static(b"Password")))
+                .expect("Failed to decode");
+
+        assert_eq!(Verification::Ok(Integrity::Sha1), verification);
+    }
+
+    #[test]
+    fn error_response_round_trip() {
+        let request = OutgoingMessage {
+            transaction_id: TransactionId::new(0x1234),
+            body: Request {
+                method: Binding,
+                authorization: None,
+            },
+            software: false,
+            fingerprint: false,
+        };
+
+        let request = IncomingMessage::decode(&request.encode()).expect("Failed to decode request");
+
+        let response = request
+            .error_response::<Binding>(
+                ErrorCode::UnknownAttribute,
+                Some(UnknownAttributes::new(vec![0x0003, 0x7FFF])),
+            )
+            .expect("Method should match");
+
+        let output =
+            IncomingMessage::decode(&response.encode()).expect("Failed to decode response");
+
+        let expected = IncomingMessage {
+            transaction_id: TransactionId::new(0x1234),
+            classic: None,
+            body: ClassTy::ErrorResponse {
+                method: MethodTy::Binding(Binding),
+                error_code: ErrorCode::UnknownAttribute,
+                unknown_attributes: Some(UnknownAttributes::new(vec![0x0003, 0x7FFF])),
+            },
+            software: None,
+            fingerprint: None,
+            unknown_required: vec![],
+            unknown_optional: vec![],
+            custom: vec![],
+        };
+
+        assert_eq!(expected, output);
+    }
+
+    #[test]
+    fn fingerprint() {
+        let message = OutgoingMessage {
+            transaction_id: TransactionId::new(0x5678),
+            body: Request {
+                method: Binding,
+                authorization: None,
+            },
+            software: false,
+            fingerprint: true,
+        };
+
+        let mut buf = message.encode().to_vec();
+
+        assert!(is_stun(&buf));
+
+        let output = IncomingMessage::decode(&buf).expect("Failed to decode");
+
+        assert!(matches!(
+            output.fingerprint,
+            Some(Fingerprint::Incoming { .. })
+        ));
+
+        // corrupt the transaction ID
+        buf[19] ^= 0xFF;
+
+        assert!(!is_stun(&buf));
+
+        let err = IncomingMessage::decode(&buf).expect_err("Fingerprint should mismatch");
+
+        assert!(matches!(err.ty, IncomingErrorTy::BadFingerprint));
+
+        // not a STUN message at all
+        assert!(!is_stun(&[0x80; 24]));
+    }
+
+    #[test]
+    fn malformed() {
+        let message = OutgoingMessage {
+            transaction_id: TransactionId::new(0x9abc),
+            body: Request {
+                method: Binding,
+                authorization: Some(Authorization {
+                    credentials: Credentials::new_short_term(Username::new("Alice"), "Password")
+                        .unwrap(),
+                    integrity: Integrity::Both,
+                }),
+            },
+            software: false,
+            fingerprint: true,
+        };
+
+        let buf = message.encode();
+
+        // decoding must fail gracefully, regardless of which byte is corrupted
+        for i in 0..buf.len() {
+            for bits in [0x01, 0x80, 0xFF] {
+                let mut corrupted = buf.to_vec();
+                corrupted[i] ^= bits;
+
+                let _ = IncomingMessage::decode(&corrupted);
+                let _ = IncomingAuthorization::verify(&corrupted, |_| Some("Password".into()));
+            }
+        }
+
+        // attribute length exceeding the message
+        let mut corrupted = buf.to_vec();
+        corrupted[22..24].copy_from_slice(&0xFFFFu16.to_be_bytes());
+
+        let err = IncomingMessage::decode(&corrupted).expect_err("Length should be invalid");
+
+        assert!(matches!(err.ty, IncomingErrorTy::BadLength));
+
+        // truncated message
+        assert!(IncomingMessage::decode(&buf[..buf.len() - 4]).is_err());
+    }
+
+    #[test]
+    fn unknown_attributes() {
+        let message = OutgoingMessage {
+            transaction_id: TransactionId::new(0xdef0),
+            body: Request {
+                method: Binding,
+                authorization: None,
+            },
+            software: false,
+            fingerprint: false,
+        };
+
+        let mut buf = message.encode().to_vec();
+
+        // append a comprehension-required and a comprehension-optional attribute
+        buf.extend_from_slice(&[0x7F, 0xF0, 0x00, 0x02, 0xAB, 0xCD, 0x00, 0x00]);
+        buf.extend_from_slice(&[0xC0, 0xFF, 0x00, 0x04, 0x01, 0x02, 0x03, 0x04]);
+        buf[2..4].copy_from_slice(&16u16.to_be_bytes());
+
+        let output = IncomingMessage::decode(&buf).expect("Failed to decode");
+
+        assert_eq!(
+            vec![(0x7FF0, Bytes::from_static(&[0xAB, 0xCD]))],
+            output.unknown_required
+        );
+        assert_eq!(
+            vec![(0xC0FF, Bytes::from_static(&[0x01, 0x02, 0x03, 0x04]))],
+            output.unknown_optional
+        );
+        assert_eq!(
+            Some(UnknownAttributes::new(vec![0x7FF0])),
+            output.unknown_attributes()
+        );
+    }
+
+    #[test]
+    fn limits() {
+        let message = OutgoingMessage {
+            transaction_id: TransactionId::new(0x1234),
+            body: ErrorResponse {
+                method: Binding,
+                error_code: ErrorCode::Other(499, "x".repeat(128)),
+                unknown_attributes: None,
+            },
+            software: false,
+            fingerprint: false,
+        };
+
+        let err = message.try_encode(None).unwrap_err();
+        assert!(matches!(
+            err.ty,
+            OutgoingErrorTy::AttributeTooLong(ErrorCode::TY)
+        ));
+
+        let message = MessageBuilder::request(Binding::METHOD)
+            .attribute(Realm::new("é".repeat(127)))
+            .expect("Failed to add attribute");
+
+        // a realm of 127 characters is allowed
+        let size = message.size();
+        let mut buf = vec![0; size + 4];
+        assert_eq!(
+            size,
+            message
+                .encode_into(&mut buf, None)
+                .expect("Failed to encode")
+        );
+        assert_eq!(&message.encode()[..], &buf[..size]);
+
+        let err = message.encode_into(&mut buf[..size - 1], None).unwrap_err();
+        assert!(matches!(err.ty, OutgoingErrorTy::BufferTooSmall));
+
+        let err = message.try_encode(Some(size - 1)).unwrap_err();
+        assert!(matches!(err.ty, OutgoingErrorTy::MessageTooLong));
+
+        let message = MessageBuilder::request(Binding::METHOD)
+            .attribute(Realm::new("é".repeat(128)))
+            .expect("Failed to add attribute");
+
+        let err = message.try_encode(None).unwrap_err();
+        assert!(matches!(
+            err.ty,
+            OutgoingErrorTy::AttributeTooLong(Realm::TY)
+        ));
+
+        // a malformed ERROR-CODE is rejected instead of panicking
+        let message = MessageBuilder::request(Binding::METHOD)
+            .push_raw(ErrorCode::TY, Bytes::from_static(&[0x04]));
+
+        let err = message.try_encode(None).unwrap_err();
+        assert!(matches!(
+            err.ty,
+            OutgoingErrorTy::AttributeTooLong(ErrorCode::TY)
+        ));
+    }
+
+    #[test]
+    #[should_panic(expected = "Failed to encode message")]
+    fn limits_panic() {
+        MessageBuilder::request(Binding::METHOD)
+            .attribute(Software::new("x".repeat(128)))
+            .expect("Failed to add attribute")
+            .encode();
+    }
+
+    #[test]
+    fn dissect_malformed() {
+        let message = MessageBuilder::request(Binding::METHOD)
+            .attribute(Software::default())
+            .unwrap()
+            .integrity(b"key", Integrity::Sha1)
+            .fingerprint()
+            .encode();
+
+        let dissection = dissect(&message, Some(b"key"));
+
+        assert!(dissection.issues.is_empty(), "{dissection}");
+        assert_eq!(Some(true), dissection.attributes[1].valid);
+        assert_eq!(Some(true), dissection.attributes[2].valid);
+
+        // move SOFTWARE after MESSAGE-INTEGRITY and truncate the packet
+        let software = Software::default();
+        let software = attribute_size!(dyn software);
+        let mut buf = message[..20].to_vec();
+        buf.extend_from_slice(&message[(20 + software)..(message.len() - 8)]);
+        buf.extend_from_slice(&message[20..(20 + software)]);
+        buf.truncate(buf.len() - 2);
+
+        let dissection = dissect(&buf, Some(b"key"));
+
+        assert_eq!(Some(false), dissection.attributes[0].valid);
+        assert_eq!(2, dissection.attributes.len());
+        assert_eq!(4, dissection.issues.len(), "{dissection}");
+    }
+
+    #[test]
+    fn builder() {
+        use std::net::SocketAddr;
+
+        use stun::message::Getter;
+
+        let request = IncomingMessage::decode(
+            &OutgoingMessage {
+                transaction_id: TransactionId::new(0x2468),
+                body: Request {
+                    method: Binding,
+                    authorization: None,
+                },
+                software: true,
+                fingerprint: false,
+            }
+            .encode(),
+        )
+        .expect("Failed to decode request");
+
+        assert_eq!(Some(Software::default()), request.software);
+
+        for addr in [
+            "192.0.2.1:32853",
+            "[2001:db8:1234:5678:11:2233:4455:6677]:32853",
+        ] {
+            let addr: SocketAddr = addr.parse().unwrap();
+
+            let buf = MessageBuilder::success_response(&request)
+                .attribute(XorMappedAddress::new(addr))
+                .expect("Attribute should be allowed")
+                .integrity(b"Password", Integrity::Sha1)
+                .fingerprint()
+                .encode();
+
+            // "stun" crate
+            let mut test_message = stun::message::Message::new();
+            test_message
+                .unmarshal_binary(&buf)
+                .expect("Failed to unmarshal");
+
+            let mut xor_addr = stun::xoraddr::XorMappedAddress::default();
+            xor_addr
+                .get_from(&test_message)
+                .expect("Missing XOR-MAPPED-ADDRESS");
+
+            assert_eq!(addr, SocketAddr::new(xor_addr.ip, xor_addr.port));
+
+            // "flashbang" crate
+            let view = MessageRef::new(&buf).expect("Failed to validate response");
+            let output = view
+                .get::<XorMappedAddress>()
+                .expect("Missing XOR-MAPPED-ADDRESS")
+                .expect("Failed to decode XOR-MAPPED-ADDRESS");
+
+            assert_eq!(SUCCESS_RESPONSE_CLASS, view.class());
+            assert_eq!(request.transaction_id, view.transaction_id());
+            assert_eq!(addr, output.addr());
+            assert!(is_stun(&buf));
+
+            let verification =
+                IncomingAuthorization::verify(&buf, |_| Some(Bytes::from_static(b"Password")))
+                    .expect("Failed to decode");
+
+            assert_eq!(Verification::Ok(Integrity::Sha1), verification);
+        }
+
+        let err = MessageBuilder::request(Binding::METHOD)
+            .attribute(Fingerprint::Outgoing)
+            .err()
+            .expect("FINGERPRINT should be rejected");
+
+        assert!(matches!(err.ty, OutgoingErrorTy::MisplacedAttribute));
     }
 }
@@ -3,51 +3,67 @@ use super::*;
 pub struct OutgoingMessage<T: Class> {
     pub transaction_id: TransactionId,
     pub body: T,
+    /// Whether to include the [Software::default] attribute.
     pub software: bool,
     pub fingerprint: bool,
 }
 
 impl<T: Class> OutgoingMessage<T> {
+    /// Encodes the message.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the message exceeds the limits of RFC 8489.
+    /// Use [OutgoingMessage::try_encode] to handle the error instead.
     pub fn encode(&self) -> Bytes {
-        let size = self.size();
-
-        let mut buf = BytesMut::zeroed(size);
+        self.try_encode(None).expect("Failed to encode message")
+    }
 
-        // encode message type (and conduct sanity check for top two bits)
-        let ty = ((T::METHOD & 0x1F80) << 2)
-            | ((T::METHOD & 0x0070) << 1)
-            | (T::METHOD & 0x000F)
-            | ((T::CLASS & 0x0002) << 7)
-            | ((T::CLASS & 0x0001) << 4);
-        buf[0..2].copy_from_slice(&(ty & 0x3FFF).to_be_bytes());
+    /// Encodes the message, failing if it exceeds the limits of RFC 8489
+    /// or is larger than `mtu` bytes.
+    pub fn try_encode(&self, mtu: Option<usize>) -> Result<Bytes, OutgoingError> {
+        let mut buf = BytesMut::zeroed(self.size());
 
-        // skip encoding the message size
-        // certain attributes require the size to only include up to the attribute
-        // instead, the size is incremented as the message is encoded
+        self.encode_into(&mut buf, mtu)?;
 
-        // encode the magic symbol
-        buf[4..8].copy_from_slice(&MAGIC.to_be_bytes());
+        Ok(buf.into())
+    }
 
-        // encode the transaction id
-        buf[8..20].copy_from_slice(&self.transaction_id.0);
+    /// Encodes the message into the start of `buf`, returning the size of the message.
+    ///
+    /// Fails if the message exceeds the limits of RFC 8489,
+    /// is larger than `mtu` bytes or doesn't fit into `buf`.
+    pub fn encode_into(&self, buf: &mut [u8], mtu: Option<usize>) -> Result<usize, OutgoingError> {
+        encode_checked(buf, self.size(), mtu, |buf| {
+            encode_header(buf, T::CLASS, T::METHOD, &self.transaction_id);
 
-        // set initial offset to be the size of the header
-        let mut offset = 20;
+            // set initial offset to be the size of the header
+            let mut offset = 20;
 
-        // encode the message body
-        self.body.encode(&mut buf, &mut offset);
+            // encode the SOFTWARE attribute, if desired
+            // (before the message body, so that it's covered by the integrity attributes)
+            if self.software {
+                encode_attribute(&Software::default(), buf, &mut offset);
+            }
 
-        // encode the FINGERPRINT attribute, if desired
-        if self.fingerprint {
-            encode_attribute(&Fingerprint::Outgoing, &mut buf, &mut offset);
-        }
+            // encode the message body
+            self.body.encode(buf, &mut offset);
 
-        buf.into()
+            // encode the FINGERPRINT attribute, if desired
+            if self.fingerprint {
+                encode_attribute(&Fingerprint::Outgoing, buf, &mut offset);
+            }
+        })
     }
 
     pub fn size(&self) -> usize {
         let mut size = 20 + self.body.size();
 
+        if self.software {
+            let software = Software::default();
+            size += attribute_size!(dyn software);
+        }
+
         if self.fingerprint {
             size += attribute_size!(static Fingerprint);
         }
@@ -55,3 +71,134 @@ impl<T: Class> OutgoingMessage<T> {
         size
     }
 }
+
+/// Encodes a message of `size` bytes into the start of `buf`.
+///
+/// The size of the message is checked before it is encoded,
+/// then the attributes are checked against the limits of RFC 8489.
+pub(crate) fn encode_checked<F>(
+    buf: &mut [u8],
+    size: usize,
+    mtu: Option<usize>,
+    encode: F,
+) -> Result<usize, OutgoingError>
+where
+    F: FnOnce(&mut [u8]),
+{
+    if size - 20 > u16::MAX as usize {
+        return Err(OutgoingError {
+            ty: OutgoingErrorTy::MessageTooLong,
+            reason: format!(
+                "Message length of {} bytes doesn't fit in the header.",
+                size - 20
+            ),
+        });
+    }
+
+    if let Some(mtu) = mtu {
+        if size > mtu {
+            return Err(OutgoingError {
+                ty: OutgoingErrorTy::MessageTooLong,
+                reason: format!("Message size of {size} bytes exceeds the MTU of {mtu} bytes."),
+            });
+        }
+    }
+
+    if buf.len() < size {
+        return Err(OutgoingError {
+            ty: OutgoingErrorTy::BufferTooSmall,
+            reason: format!("Buffer of {} bytes can't fit {size} bytes.", buf.len()),
+        });
+    }
+
+    // the message length and padding are expected to be zeroed
+    let buf = &mut buf[..size];
+    buf.fill(0);
+
+    encode(buf);
+
+    check_limits(buf)?;
+
+    Ok(size)
+}
+
+/// Checks the attributes of an encoded message against the limits of RFC 8489.
+fn check_limits(buf: &[u8]) -> Result<(), OutgoingError> {
+    for (ty, value) in MessageRef::new_unchecked(buf).iter_raw() {
+        let valid = match ty {
+            // fewer than 509 bytes
+            Username::TY => value.len() < 509,
+            // fewer than 128 characters
+            Realm::TY | Nonce::TY | Software::TY => char_count(value) < 128,
+            // reason phrase of fewer than 128 characters
+            // (a custom attribute may reuse the type with a shorter value)
+            ErrorCode::TY => value
+                .get(4..)
+                .is_some_and(|reason| char_count(reason) < 128),
+            _ => true,
+        };
+
+        if !valid {
+            return Err(OutgoingError {
+                ty: OutgoingErrorTy::AttributeTooLong(ty),
+                reason: format!("Attribute {ty:#06x} exceeds the limits of RFC 8489."),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+fn char_count(value: &[u8]) -> usize {
+    String::from_utf8_lossy(value).chars().count()
+}
+
+/// Encodes the message header, with a length of zero.
+pub(crate) fn encode_header(buf: &mut [u8], class: u16, method: u16, id: &TransactionId) {
+    // encode message type (and conduct sanity check for top two bits)
+    let ty = ((method & 0x1F80) << 2)
+        | ((method & 0x0070) << 1)
+        | (method & 0x000F)
+        | ((class & 0x0002) << 7)
+        | ((class & 0x0001) << 4);
+    buf[0..2].copy_from_slice(&(ty & 0x3FFF).to_be_bytes());
+
+    // skip encoding the message size
+    // certain attributes require the size to only include up to the attribute
+    // instead, the size is incremented as the message is encoded
+
+    // encode the magic symbol
+    buf[4..8].copy_from_slice(&MAGIC.to_be_bytes());
+
+    // encode the transaction id
+    buf[8..20].copy_from_slice(&id.0);
+}
+
+/// An error from encoding an outgoing message.
+#[derive(Debug)]
+pub struct OutgoingError {
+    pub ty: OutgoingErrorTy,
+    pub reason: String,
+}
+
+impl std::fmt::Display for OutgoingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.reason)
+    }
+}
+
+impl std::error::Error for OutgoingError {}
+
+#[derive(Debug)]
+pub enum OutgoingErrorTy {
+    /// The attribute must be placed at the end of the message.
+    MisplacedAttribute,
+    /// The attribute of the given type exceeds its maximum length.
+    AttributeTooLong(u16),
+    /// The message exceeds the maximum length or the MTU.
+    MessageTooLong,
+    /// The buffer is too small for the message.
+    BufferTooSmall,
+    /// The attribute of the given type can't be encoded in a classic RFC 3489 message.
+    NotClassic(u16),
+}
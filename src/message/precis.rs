@@ -0,0 +1,101 @@
+use icu_properties::{props::DefaultIgnorableCodePoint, CodePointSetData};
+use unicode_normalization::UnicodeNormalization;
+use unicode_properties::{GeneralCategory, UnicodeGeneralCategory};
+
+/// Prepares `input` with the PRECIS OpaqueString profile.
+///
+/// Usernames, realms and passwords must be prepared before they are hashed,
+/// so that clients and servers derive the same keys.
+///
+/// The profile is applied in order:
+/// - Width mapping: fullwidth and halfwidth code points are **not** mapped
+///   to their decompositions, as required by the profile.
+/// - Additional mapping: non-ASCII spaces are mapped to SPACE (U+0020).
+/// - Normalization: the string is normalized with Unicode NFC.
+///
+/// The string is rejected if it's empty or contains a code point that
+/// the FreeformClass disallows, i.e. control characters, default ignorable
+/// and unassigned code points, noncharacters and old Hangul jamo.
+///
+/// See [RFC8265 Section 4.2](https://datatracker.ietf.org/doc/html/rfc8265#section-4.2) for more details.
+pub fn opaque_string(input: &str) -> Result<String, PrecisError> {
+    let output: String = input
+        .chars()
+        .map(|c| match c.general_category() {
+            GeneralCategory::SpaceSeparator => ' ',
+            _ => c,
+        })
+        .nfc()
+        .collect();
+
+    if output.is_empty() {
+        return Err(PrecisError {
+            ty: PrecisErrorTy::Empty,
+            reason: "String is empty.".into(),
+        });
+    }
+
+    if let Some(c) = output.chars().find(|c| is_disallowed(*c)) {
+        return Err(PrecisError {
+            ty: PrecisErrorTy::Disallowed(c),
+            reason: format!("Code point U+{:04X} is disallowed.", c as u32),
+        });
+    }
+
+    Ok(output)
+}
+
+/// Checks whether the FreeformClass disallows `c`.
+///
+/// See [RFC8264 Section 9](https://datatracker.ietf.org/doc/html/rfc8264#section-9) for more details.
+fn is_disallowed(c: char) -> bool {
+    let cp = c as u32;
+
+    // Controls
+    if c.is_control() {
+        return true;
+    }
+
+    // Unassigned
+    if c.general_category() == GeneralCategory::Unassigned {
+        return true;
+    }
+
+    // Noncharacters
+    if (0xFDD0..=0xFDEF).contains(&cp) || cp & 0xFFFE == 0xFFFE {
+        return true;
+    }
+
+    // Default_Ignorable_Code_Point, from the tables of ICU4X
+    if CodePointSetData::new::<DefaultIgnorableCodePoint>().contains(c) {
+        return true;
+    }
+
+    // OldHangulJamo, i.e. the leading, vowel and trailing jamo
+    matches!(
+        cp,
+        0x1100..=0x11FF | 0xA960..=0xA97F | 0xD7B0..=0xD7FF
+    )
+}
+
+#[derive(Debug)]
+pub struct PrecisError {
+    pub ty: PrecisErrorTy,
+    pub reason: String,
+}
+
+impl std::fmt::Display for PrecisError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.reason)
+    }
+}
+
+impl std::error::Error for PrecisError {}
+
+#[derive(Debug)]
+pub enum PrecisErrorTy {
+    /// The string is empty after the mappings were applied.
+    Empty,
+    /// The string contains a code point that is disallowed.
+    Disallowed(char),
+}
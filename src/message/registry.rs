@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+
+use super::*;
+
+type AttributeDecoder = fn(&[u8], &AttributeMeta) -> Result<Box<dyn DynAttribute>, AttributeError>;
+
+type MethodDecoder = fn(&MessageMeta) -> Box<dyn DynMethod>;
+
+/// A registry of custom attributes and methods.
+///
+/// [IncomingMessage::decode_with] consults the registry to decode
+/// the attributes and methods that aren't built into this crate.
+#[derive(Default, Clone)]
+pub struct Registry {
+    attributes: HashMap<u16, AttributeDecoder>,
+    methods: HashMap<u16, MethodDecoder>,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a custom attribute type.
+    ///
+    /// Replaces any attribute that was registered with the same type.
+    pub fn register_attribute<T: CustomAttribute>(&mut self) -> &mut Self {
+        self.attributes
+            .insert(<T as CustomAttribute>::TY, decode_attribute::<T>);
+        self
+    }
+
+    /// Registers a custom method type.
+    ///
+    /// Replaces any method that was registered with the same number.
+    pub fn register_method<T: CustomMethod>(&mut self) -> &mut Self {
+        self.methods
+            .insert(<T as CustomMethod>::METHOD, decode_method::<T>);
+        self
+    }
+
+    /// Checks whether an attribute type is registered.
+    pub fn contains_attribute(&self, ty: u16) -> bool {
+        self.attributes.contains_key(&ty)
+    }
+
+    /// Decodes a registered attribute.
+    ///
+    /// Returns `None` if the attribute type isn't registered.
+    pub(crate) fn decode_attribute(
+        &self,
+        buf: &[u8],
+        meta: &AttributeMeta,
+    ) -> Option<Result<Box<dyn DynAttribute>, AttributeError>> {
+        self.attributes
+            .get(&meta.ty)
+            .map(|decode| decode(buf, meta))
+    }
+
+    /// Decodes a registered method.
+    ///
+    /// Returns `None` if the method isn't registered.
+    pub(crate) fn decode_method(&self, meta: &MessageMeta) -> Option<Box<dyn DynMethod>> {
+        self.methods.get(&meta.method).map(|decode| decode(meta))
+    }
+}
+
+fn decode_attribute<T: CustomAttribute>(
+    buf: &[u8],
+    meta: &AttributeMeta,
+) -> Result<Box<dyn DynAttribute>, AttributeError> {
+    Ok(Box::new(<T as CustomAttribute>::decode(buf, meta)?))
+}
+
+fn decode_method<T: CustomMethod>(meta: &MessageMeta) -> Box<dyn DynMethod> {
+    Box::new(<T as CustomMethod>::decode(meta))
+}
@@ -0,0 +1,95 @@
+//! Helpers for the human-readable serde representation of messages.
+
+use bytes::Bytes;
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+/// Bytes that are represented as a lowercase hex string.
+pub(crate) trait Hex: Sized {
+    fn as_hex_bytes(&self) -> &[u8];
+
+    fn from_hex_bytes(bytes: Vec<u8>) -> Option<Self>;
+}
+
+impl<const N: usize> Hex for [u8; N] {
+    fn as_hex_bytes(&self) -> &[u8] {
+        self
+    }
+
+    fn from_hex_bytes(bytes: Vec<u8>) -> Option<Self> {
+        bytes.try_into().ok()
+    }
+}
+
+impl Hex for Bytes {
+    fn as_hex_bytes(&self) -> &[u8] {
+        self
+    }
+
+    fn from_hex_bytes(bytes: Vec<u8>) -> Option<Self> {
+        Some(bytes.into())
+    }
+}
+
+/// Use with `#[serde(with = "hex")]` to represent bytes as a hex string.
+pub(crate) mod hex {
+    use super::*;
+
+    pub(crate) fn serialize<T: Hex, S: Serializer>(value: &T, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&encode(value.as_hex_bytes()))
+    }
+
+    pub(crate) fn deserialize<'de, T: Hex, D: Deserializer<'de>>(d: D) -> Result<T, D::Error> {
+        let s = String::deserialize(d)?;
+
+        let bytes = decode(&s).ok_or_else(|| D::Error::custom("invalid hex string"))?;
+
+        T::from_hex_bytes(bytes).ok_or_else(|| D::Error::custom("wrong number of hex digits"))
+    }
+
+    pub(crate) fn encode(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{b:02x}")).collect()
+    }
+
+    pub(crate) fn decode(s: &str) -> Option<Vec<u8>> {
+        if !s.len().is_multiple_of(2) || !s.is_ascii() {
+            return None;
+        }
+
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..(i + 2)], 16).ok())
+            .collect()
+    }
+}
+
+/// Use with `#[serde(with = "unknown")]` to represent unknown attributes
+/// as a list of types and hex values.
+pub(crate) mod unknown {
+    use super::*;
+
+    #[derive(Serialize, Deserialize)]
+    struct UnknownAttribute {
+        #[serde(rename = "type")]
+        ty: u16,
+        #[serde(with = "hex")]
+        value: Bytes,
+    }
+
+    pub(crate) fn serialize<S: Serializer>(
+        value: &[(u16, Bytes)],
+        s: S,
+    ) -> Result<S::Ok, S::Error> {
+        s.collect_seq(value.iter().map(|(ty, value)| UnknownAttribute {
+            ty: *ty,
+            value: value.clone(),
+        }))
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        d: D,
+    ) -> Result<Vec<(u16, Bytes)>, D::Error> {
+        let attributes = Vec::<UnknownAttribute>::deserialize(d)?;
+
+        Ok(attributes.into_iter().map(|a| (a.ty, a.value)).collect())
+    }
+}
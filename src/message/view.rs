@@ -0,0 +1,202 @@
+use super::*;
+
+/// A borrowed view of a STUN message.
+///
+/// Only the header and the bounds of the attributes are validated up front,
+/// which doesn't allocate.
+/// Attributes are decoded lazily, when they are accessed.
+///
+/// Use [IncomingMessage::decode] to decode the full message instead.
+#[derive(Debug, Clone, Copy)]
+pub struct MessageRef<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> MessageRef<'a> {
+    /// Validates the message in `buf`.
+    pub fn new(buf: &'a [u8]) -> Result<Self, IncomingError> {
+        Self::validate(buf, false)
+    }
+
+    /// Validates the message in `buf`, also accepting classic STUN messages without a magic cookie.
+    ///
+    /// See [RFC3489](https://datatracker.ietf.org/doc/html/rfc3489) for more details.
+    pub fn new_classic(buf: &'a [u8]) -> Result<Self, IncomingError> {
+        Self::validate(buf, true)
+    }
+
+    fn validate(buf: &'a [u8], classic: bool) -> Result<Self, IncomingError> {
+        let buf_len = buf.len();
+
+        if buf_len < 20 {
+            return Err(IncomingError {
+                ty: IncomingErrorTy::BadLength,
+                reason: "Message length was too small for 20-byte header.".into(),
+            });
+        }
+
+        if !buf_len.is_multiple_of(4) {
+            return Err(IncomingError {
+                ty: IncomingErrorTy::BadLength,
+                reason: "Message length must be aligned to a 32-bit boundary.".into(),
+            });
+        }
+
+        let ty = u16::from_be_bytes(buf[0..2].try_into().unwrap());
+
+        if ty & 0xC000 != 0 {
+            return Err(IncomingError {
+                ty: IncomingErrorTy::BadFormat,
+                reason: "The first two bits MUST be zero.".into(),
+            });
+        }
+
+        let len = u16::from_be_bytes(buf[2..4].try_into().unwrap());
+
+        let magic = u32::from_be_bytes(buf[4..8].try_into().unwrap());
+
+        if magic != MAGIC && !classic {
+            return Err(IncomingError {
+                ty: IncomingErrorTy::BadMagic,
+                reason: "Magic number was incorrect.".into(),
+            });
+        }
+
+        if 20 + len as usize != buf_len {
+            return Err(IncomingError {
+                ty: IncomingErrorTy::BadLength,
+                reason: "Message length doesn't match the size of the message.".into(),
+            });
+        }
+
+        let mut idx = 20;
+
+        while idx < buf_len {
+            if buf_len - idx < 4 {
+                return Err(IncomingError {
+                    ty: IncomingErrorTy::BadLength,
+                    reason: "Attribute header exceeds the message.".into(),
+                });
+            }
+
+            let len = u16::from_be_bytes(buf[(idx + 2)..(idx + 4)].try_into().unwrap()) as usize;
+            let offset = idx + 4;
+
+            if offset + len > buf_len {
+                return Err(IncomingError {
+                    ty: IncomingErrorTy::BadLength,
+                    reason: "Attribute length exceeds the message.".into(),
+                });
+            }
+
+            // the message is aligned, so the padding can't exceed it
+            idx = offset + ((len + 3) & !3);
+        }
+
+        Ok(Self { buf })
+    }
+
+    /// Creates a view without validating the message.
+    ///
+    /// Only used for messages that were encoded by this crate.
+    pub(crate) fn new_unchecked(buf: &'a [u8]) -> Self {
+        Self { buf }
+    }
+
+    /// The class of the message.
+    pub fn class(&self) -> u16 {
+        let ty = u16::from_be_bytes(self.buf[0..2].try_into().unwrap());
+
+        (ty & 0x0100) >> 7 | (ty & 0x0010) >> 4
+    }
+
+    /// The method of the message.
+    pub fn method(&self) -> u16 {
+        let ty = u16::from_be_bytes(self.buf[0..2].try_into().unwrap());
+
+        (ty & 0x3E00) >> 2 | (ty & 0x00E0) >> 1 | (ty & 0x000F)
+    }
+
+    /// The transaction ID of the message.
+    pub fn transaction_id(&self) -> TransactionId {
+        TransactionId(self.buf[8..20].try_into().unwrap())
+    }
+
+    /// The 128-bit transaction ID of a classic STUN message.
+    ///
+    /// Returns `None` if the message contains the magic cookie.
+    pub fn classic_transaction_id(&self) -> Option<ClassicTransactionId> {
+        let magic = u32::from_be_bytes(self.buf[4..8].try_into().unwrap());
+
+        match magic {
+            MAGIC => None,
+            _ => Some(ClassicTransactionId(self.buf[4..20].try_into().unwrap())),
+        }
+    }
+
+    /// The underlying buffer of the message.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.buf
+    }
+
+    /// Iterates over the type and value of each attribute, without decoding them.
+    ///
+    /// The values don't include the padding.
+    pub fn iter_raw(&self) -> impl Iterator<Item = (u16, &'a [u8])> + 'a {
+        let buf = self.buf;
+
+        self.attributes()
+            .map(move |attr| (attr.ty, &buf[attr.offset..(attr.offset + attr.len)]))
+    }
+
+    /// Decodes the first attribute of type `T`.
+    ///
+    /// Returns `None` if the message doesn't contain the attribute.
+    pub fn get<T: Attribute>(&self) -> Option<Result<T, AttributeError>> {
+        self.get_all::<T>().next()
+    }
+
+    /// Decodes every attribute of type `T`.
+    pub fn get_all<T: Attribute>(&self) -> impl Iterator<Item = Result<T, AttributeError>> + 'a {
+        let buf = self.buf;
+
+        self.attributes()
+            .filter(|attr| attr.ty == T::TY)
+            .map(move |attr| T::decode(buf, &attr))
+    }
+
+    /// Iterates over the attributes.
+    pub fn attributes(&self) -> Attributes<'a> {
+        Attributes {
+            buf: self.buf,
+            idx: 20,
+        }
+    }
+}
+
+/// An iterator over the attributes of a [MessageRef].
+#[derive(Debug, Clone)]
+pub struct Attributes<'a> {
+    buf: &'a [u8],
+    idx: usize,
+}
+
+impl Iterator for Attributes<'_> {
+    type Item = AttributeMeta;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.idx >= self.buf.len() {
+            return None;
+        }
+
+        // the bounds were validated by `MessageRef::new`
+        let idx = self.idx;
+        let ty = u16::from_be_bytes(self.buf[idx..(idx + 2)].try_into().unwrap());
+        let len = u16::from_be_bytes(self.buf[(idx + 2)..(idx + 4)].try_into().unwrap()) as usize;
+        let offset = idx + 4;
+
+        self.idx = offset + ((len + 3) & !3);
+
+        Some(AttributeMeta { ty, offset, len })
+    }
+}
@@ -0,0 +1,262 @@
+use std::{net::SocketAddr, sync::Arc};
+
+use zeroize::Zeroizing;
+
+use crate::{
+    message::{
+        attributes::{
+            ErrorCode, PasswordAlgorithms, Realm, SecurityFeatures, MD5_PASSWORD_ALGORITHM,
+            SHA256_PASSWORD_ALGORITHM,
+        },
+        opaque_string, ClassTy, IncomingAuthorization, IncomingMessage, Integrity, MessageBuilder,
+        OutgoingError, UserTy, Verification,
+    },
+    user::CredentialStore,
+};
+
+use super::{
+    challenge_response,
+    nonce::{NonceManager, NonceSecret, DEFAULT_NONCE_LIFETIME},
+};
+
+/// The key a request was authenticated with, which its response is signed with.
+#[derive(Debug)]
+pub struct Authenticated {
+    /// The key, which is wiped from memory on drop.
+    pub key: Zeroizing<Vec<u8>>,
+    pub integrity: Integrity,
+}
+
+/// Authenticates requests with the credentials of a [CredentialStore].
+///
+/// With a realm, requests are authenticated with long-term credentials,
+/// and clients are challenged with a realm and nonce.
+/// Without a realm, requests are authenticated with short-term credentials.
+///
+/// See [RFC8489 Section 9](https://datatracker.ietf.org/doc/html/rfc8489#section-9) for more details.
+pub struct Authenticator {
+    store: Arc<dyn CredentialStore>,
+    nonces: NonceManager,
+    algorithms: PasswordAlgorithms,
+}
+
+impl Authenticator {
+    /// Creates an authenticator that mints nonces with a random secret,
+    /// and advertises the SHA-256 and MD5 password algorithms.
+    ///
+    /// Only the algorithms that every user of the realm has a key for are advertised,
+    /// see [CredentialStore::algorithms].
+    ///
+    /// The nonces also advertise username anonymity, so clients may send USERHASH
+    /// instead of USERNAME, which is looked up with [CredentialStore::by_userhash].
+    pub fn new(store: Arc<dyn CredentialStore>) -> Self {
+        let features = SecurityFeatures {
+            password_algorithms: true,
+            username_anonymity: true,
+        };
+
+        Self {
+            store,
+            nonces: NonceManager::new(NonceSecret::random(0), features, DEFAULT_NONCE_LIFETIME),
+            algorithms: PasswordAlgorithms::new(vec![
+                SHA256_PASSWORD_ALGORITHM.clone(),
+                MD5_PASSWORD_ALGORITHM.clone(),
+            ]),
+        }
+    }
+
+    /// Sets the manager of the nonces, e.g. to share the secret with other servers.
+    pub fn nonces(mut self, nonces: NonceManager) -> Self {
+        self.nonces = nonces;
+        self
+    }
+
+    /// Sets the password algorithms that may be advertised, in order of preference.
+    pub fn algorithms(mut self, algorithms: PasswordAlgorithms) -> Self {
+        self.algorithms = algorithms;
+        self
+    }
+
+    pub fn store(&self) -> &Arc<dyn CredentialStore> {
+        &self.store
+    }
+
+    pub fn nonce_manager(&self) -> &NonceManager {
+        &self.nonces
+    }
+
+    /// Authenticates the request in `buf` from `source`.
+    ///
+    /// Fails with the error code the request should be rejected with,
+    /// see [Authenticator::reject].
+    pub async fn authenticate(
+        &self,
+        realm: Option<&str>,
+        request: &IncomingMessage,
+        buf: &[u8],
+        source: SocketAddr,
+    ) -> Result<Authenticated, ErrorCode> {
+        let ClassTy::Request { authorization, .. } = &request.body else {
+            return Err(ErrorCode::BadRequest);
+        };
+
+        let credentials = match realm {
+            Some(realm) => {
+                self.long_term(realm, authorization.as_ref(), source)
+                    .await?
+            }
+            None => self.short_term(authorization.as_ref()).await?,
+        };
+
+        let Some(key) = credentials else {
+            return Err(ErrorCode::Unauthenticated);
+        };
+
+        let verification = IncomingAuthorization::verify_key(buf, &key).map_err(|err| {
+            log::debug!("Failed to verify request from {source}: {err}");
+            ErrorCode::BadRequest
+        })?;
+
+        match verification {
+            Verification::Ok(integrity) => Ok(Authenticated { key, integrity }),
+            _ => Err(ErrorCode::Unauthenticated),
+        }
+    }
+
+    /// Looks up the long-term key of the request.
+    ///
+    /// The username and realms are prepared with [opaque_string] before they are compared,
+    /// in case the client didn't prepare them the same way as the store.
+    ///
+    /// See [RFC8489 Section 9.2.4](https://datatracker.ietf.org/doc/html/rfc8489#section-9.2.4) for more details.
+    async fn long_term(
+        &self,
+        realm: &str,
+        authorization: Option<&IncomingAuthorization>,
+        source: SocketAddr,
+    ) -> Result<Option<Zeroizing<Vec<u8>>>, ErrorCode> {
+        let Some(authorization) = authorization.filter(|a| a.integrity.is_some()) else {
+            return Err(ErrorCode::Unauthenticated);
+        };
+
+        let (Some(user), Some(request_realm), Some(nonce)) = (
+            &authorization.user,
+            &authorization.realm,
+            &authorization.nonce,
+        ) else {
+            return Err(ErrorCode::BadRequest);
+        };
+
+        self.nonces.validate(nonce, source.ip())?;
+
+        let algorithm = authorization.negotiate(&self.advertised(realm).await)?;
+
+        // the realm was already validated with the configuration
+        let realm = opaque_string(realm).map_err(|err| {
+            log::error!("Invalid realm `{realm}`: {err}");
+            ErrorCode::ServerError
+        })?;
+
+        // the client may still use the realm of a previous configuration
+        if prepare(&request_realm.to_string())? != realm {
+            return Ok(None);
+        }
+
+        let credentials = match user {
+            UserTy::Username(username) => {
+                self.store
+                    .by_username(&prepare(&username.to_string())?, Some(&realm))
+                    .await
+            }
+            UserTy::Userhash(userhash) => self.store.by_userhash(userhash, &realm).await,
+        };
+
+        Ok(credentials.and_then(|c| c.key(&algorithm).map(|key| Zeroizing::new(key.to_vec()))))
+    }
+
+    /// Looks up the short-term password of the request.
+    ///
+    /// The username is prepared with [opaque_string] before it is looked up.
+    ///
+    /// See [RFC8489 Section 9.1.3](https://datatracker.ietf.org/doc/html/rfc8489#section-9.1.3) for more details.
+    async fn short_term(
+        &self,
+        authorization: Option<&IncomingAuthorization>,
+    ) -> Result<Option<Zeroizing<Vec<u8>>>, ErrorCode> {
+        let Some(IncomingAuthorization {
+            user: Some(UserTy::Username(username)),
+            integrity: Some(_),
+            ..
+        }) = authorization
+        else {
+            return Err(ErrorCode::BadRequest);
+        };
+
+        let credentials = self
+            .store
+            .by_username(&prepare(&username.to_string())?, None)
+            .await;
+
+        Ok(credentials.and_then(|c| {
+            c.key(&MD5_PASSWORD_ALGORITHM)
+                .map(|key| Zeroizing::new(key.to_vec()))
+        }))
+    }
+
+    /// The password algorithms that are advertised in `realm`,
+    /// which are the preferred ones that every user of the realm has a key for.
+    ///
+    /// If there's none, e.g. because the realm has no users, all of them are advertised.
+    async fn advertised(&self, realm: &str) -> PasswordAlgorithms {
+        let realm = opaque_string(realm).unwrap_or_else(|_| realm.into());
+
+        let supported = self.store.algorithms(&realm).await;
+
+        let algorithms: Vec<_> = self
+            .algorithms
+            .algorithms()
+            .iter()
+            .filter(|algorithm| supported.contains(algorithm))
+            .cloned()
+            .collect();
+
+        match algorithms.is_empty() {
+            true => self.algorithms.clone(),
+            false => PasswordAlgorithms::new(algorithms),
+        }
+    }
+
+    /// Builds the error response that rejects a request which failed to authenticate.
+    ///
+    /// With long-term credentials, 401 (Unauthenticated) and 438 (Stale Nonce)
+    /// challenge the client with the realm and a fresh nonce.
+    pub async fn reject(
+        &self,
+        realm: Option<&str>,
+        request: &IncomingMessage,
+        error_code: ErrorCode,
+        source: SocketAddr,
+    ) -> Result<MessageBuilder, OutgoingError> {
+        match (realm, &error_code) {
+            (Some(realm), ErrorCode::Unauthenticated | ErrorCode::StaleNonce) => {
+                challenge_response(
+                    request,
+                    error_code,
+                    &Realm::new(realm),
+                    &self.nonces.mint(source.ip()),
+                    &self.advertised(realm).await,
+                )
+            }
+            _ => Ok(MessageBuilder::error_response(request, error_code)),
+        }
+    }
+}
+
+/// Prepares a string of the request with the OpaqueString profile,
+/// rejecting the request if the string isn't allowed.
+fn prepare(s: &str) -> Result<String, ErrorCode> {
+    opaque_string(s).map_err(|err| {
+        log::debug!("Rejected request with invalid string `{s}`: {err}");
+        ErrorCode::BadRequest
+    })
+}
@@ -1,11 +1,521 @@
+use std::{
+    fmt::Display,
+    io,
+    net::{IpAddr, Ipv6Addr, SocketAddr},
+    path::PathBuf,
+    str::FromStr,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
 
-#[derive(Clone, Copy)]
+use socket2::{Domain, Protocol, Socket, Type};
+
+use crate::message::opaque_string;
+
+use super::runtime::{INSECURE_PORT, SECURE_PORT};
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct ServerConfig {
-    
+    /// Accept classic STUN (RFC 3489) requests without a magic cookie.
+    ///
+    /// Classic requests are answered with MAPPED-ADDRESS instead of XOR-MAPPED-ADDRESS.
+    pub classic: bool,
+    /// The transport addresses the server listens on.
+    pub listeners: Vec<ListenerConfig>,
+    pub auth: AuthConfig,
+    /// The certificate of TLS and DTLS listeners.
+    pub tls: Option<TlsConfig>,
+    pub turn: TurnConfig,
+    pub log: LogConfig,
 }
 
+/// Listens on UDP and TCP port 3478 of all IPv4 and IPv6 addresses.
 impl Default for ServerConfig {
     fn default() -> Self {
-        Self { }
+        let addr = IpAddr::V6(Ipv6Addr::UNSPECIFIED);
+
+        Self {
+            classic: false,
+            listeners: vec![
+                ListenerConfig::new(Transport::Udp, addr),
+                ListenerConfig::new(Transport::Tcp, addr),
+            ],
+            auth: AuthConfig::default(),
+            tls: None,
+            turn: TurnConfig::default(),
+            log: LogConfig::default(),
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Checks that the configuration is consistent.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.listeners.is_empty() {
+            return Err(ConfigError {
+                ty: ConfigErrorTy::NoListeners,
+                reason: "At least one listener is required.".into(),
+            });
+        }
+
+        for (i, listener) in self.listeners.iter().enumerate() {
+            if self.listeners[..i].contains(listener) {
+                return Err(ConfigError {
+                    ty: ConfigErrorTy::DuplicateListener,
+                    reason: format!("Listener `{listener}` is configured more than once."),
+                });
+            }
+        }
+
+        let secure = self
+            .listeners
+            .iter()
+            .find(|l| matches!(l.transport, Transport::Tls | Transport::Dtls));
+
+        match (secure, &self.tls) {
+            (Some(listener), None) => {
+                return Err(ConfigError {
+                    ty: ConfigErrorTy::MissingCertificate,
+                    reason: format!("Listener `{listener}` requires a TLS certificate and key."),
+                })
+            }
+            (None, Some(_)) => {
+                return Err(ConfigError {
+                    ty: ConfigErrorTy::UnusedCertificate,
+                    reason: "A TLS certificate was given, but there are no TLS or DTLS listeners."
+                        .into(),
+                })
+            }
+            _ => (),
+        }
+
+        if let Some(listener) = secure {
+            return Err(ConfigError {
+                ty: ConfigErrorTy::UnsupportedTransport,
+                reason: format!("Listener `{listener}` uses a transport that isn't supported yet."),
+            });
+        }
+
+        self.turn.validate()?;
+
+        self.auth.validate()
+    }
+}
+
+/// A shared handle to the configuration of a running server, which can be reloaded.
+///
+/// Readers get a snapshot of the configuration with [ConfigHandle::get],
+/// so a reload never changes the configuration in the middle of handling a message.
+#[derive(Clone, Debug, Default)]
+pub struct ConfigHandle {
+    config: Arc<RwLock<Arc<ServerConfig>>>,
+}
+
+impl ConfigHandle {
+    pub fn new(config: ServerConfig) -> Self {
+        Self {
+            config: Arc::new(RwLock::new(Arc::new(config))),
+        }
+    }
+
+    /// A snapshot of the current configuration.
+    pub fn get(&self) -> Arc<ServerConfig> {
+        self.config.read().unwrap().clone()
+    }
+
+    /// Validates and replaces the configuration.
+    ///
+    /// The runtime starts listeners that were added and stops listeners that were removed,
+    /// while the sockets of unchanged listeners are kept.
+    /// Returns the changes that can't be applied to a running server,
+    /// which only take effect after a restart.
+    pub fn reload(&self, config: ServerConfig) -> Result<Vec<String>, ConfigError> {
+        config.validate()?;
+
+        let mut current = self.config.write().unwrap();
+
+        let mut pending = vec![];
+
+        if current.log.format != config.log.format {
+            pending.push("The log format only changes after a restart.".into());
+        }
+
+        if current.auth.credentials != config.auth.credentials {
+            pending.push("The credentials file only changes after a restart.".into());
+        }
+
+        if current.tls != config.tls {
+            pending.push("The TLS certificate only changes after a restart.".into());
+        }
+
+        *current = Arc::new(config);
+
+        Ok(pending)
+    }
+}
+
+impl From<ServerConfig> for ConfigHandle {
+    fn from(config: ServerConfig) -> Self {
+        Self::new(config)
+    }
+}
+
+/// The authentication of clients.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AuthConfig {
+    /// The realm of long-term credentials.
+    pub realm: Option<String>,
+    /// The file that contains the credentials of the users.
+    pub credentials: Option<PathBuf>,
+}
+
+impl AuthConfig {
+    fn validate(&self) -> Result<(), ConfigError> {
+        match (&self.realm, &self.credentials) {
+            (Some(realm), _) => {
+                if let Err(err) = opaque_string(realm) {
+                    return Err(ConfigError {
+                        ty: ConfigErrorTy::BadRealm,
+                        reason: format!("Invalid realm: {err}"),
+                    });
+                }
+
+                // REALM must be less than 128 characters
+                if realm.chars().count() >= 128 {
+                    return Err(ConfigError {
+                        ty: ConfigErrorTy::BadRealm,
+                        reason: "The realm must be less than 128 characters.".into(),
+                    });
+                }
+
+                Ok(())
+            }
+            (None, Some(_)) => Err(ConfigError {
+                ty: ConfigErrorTy::MissingRealm,
+                reason: "A realm is required for long-term credentials.".into(),
+            }),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+/// The limits of TURN allocations.
+///
+/// See [RFC8656 Section 7.2](https://datatracker.ietf.org/doc/html/rfc8656#section-7.2) for more details.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TurnConfig {
+    /// The maximum number of allocations, or `None` for no limit.
+    pub max_allocations: Option<usize>,
+    /// The maximum number of allocations of a single user, or `None` for no limit.
+    pub max_allocations_per_user: Option<usize>,
+    /// The maximum lifetime a client can request for an allocation.
+    pub max_lifetime: Duration,
+}
+
+impl Default for TurnConfig {
+    fn default() -> Self {
+        Self {
+            max_allocations: None,
+            max_allocations_per_user: None,
+            max_lifetime: Duration::from_secs(3600),
+        }
+    }
+}
+
+impl TurnConfig {
+    /// The default lifetime of an allocation, which the maximum can't be lower than.
+    pub const DEFAULT_LIFETIME: Duration = Duration::from_secs(600);
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        if self.max_lifetime < Self::DEFAULT_LIFETIME {
+            return Err(ConfigError {
+                ty: ConfigErrorTy::BadTurnLimit,
+                reason: format!(
+                    "The maximum lifetime of {} s is below the default lifetime of {} s.",
+                    self.max_lifetime.as_secs(),
+                    Self::DEFAULT_LIFETIME.as_secs()
+                ),
+            });
+        }
+
+        match (self.max_allocations, self.max_allocations_per_user) {
+            (Some(max), Some(per_user)) if per_user > max => Err(ConfigError {
+                ty: ConfigErrorTy::BadTurnLimit,
+                reason: format!(
+                    "The {per_user} allocations per user exceed the {max} allocations in total."
+                ),
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The certificate of TLS and DTLS listeners, as PEM files.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TlsConfig {
+    pub certificate: PathBuf,
+    pub key: PathBuf,
+}
+
+/// The logging of the server binary.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogConfig {
+    pub level: log::LevelFilter,
+    pub format: LogFormat,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            level: log::LevelFilter::Info,
+            format: LogFormat::Text,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human-readable lines.
+    Text,
+    /// One JSON object per line.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(format!(
+                "Unknown log format `{s}`, expected `text` or `json`."
+            )),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ConfigError {
+    pub ty: ConfigErrorTy,
+    pub reason: String,
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.reason)
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.ty {
+            ConfigErrorTy::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigErrorTy {
+    NoListeners,
+    DuplicateListener,
+    /// A TLS or DTLS listener has no certificate.
+    MissingCertificate,
+    /// A certificate was given without any TLS or DTLS listeners.
+    UnusedCertificate,
+    /// A listener uses TLS or DTLS, which aren't implemented yet.
+    UnsupportedTransport,
+    /// Credentials were given without a realm.
+    MissingRealm,
+    /// The realm is rejected by the OpaqueString profile or too long.
+    BadRealm,
+    BadTurnLimit,
+    /// The configuration file couldn't be read.
+    Io(io::Error),
+    /// The configuration file isn't valid TOML or doesn't match the schema.
+    Parse,
+}
+
+/// The transport protocol of a listener.
+///
+/// See [RFC8489 Section 6](https://datatracker.ietf.org/doc/html/rfc8489#section-6) for more details.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Transport {
+    Udp,
+    Tcp,
+    /// TCP secured with TLS ("stuns" scheme).
+    Tls,
+    /// UDP secured with DTLS ("stuns" scheme).
+    Dtls,
+}
+
+impl Transport {
+    /// The default port of the transport, 3478 for "stun" and 5349 for "stuns".
+    pub fn default_port(&self) -> u16 {
+        match self {
+            Self::Udp | Self::Tcp => INSECURE_PORT,
+            Self::Tls | Self::Dtls => SECURE_PORT,
+        }
+    }
+
+    /// Whether the transport is a byte stream, which needs framing.
+    pub fn is_stream(&self) -> bool {
+        matches!(self, Self::Tcp | Self::Tls)
+    }
+}
+
+impl FromStr for Transport {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "udp" => Ok(Self::Udp),
+            "tcp" => Ok(Self::Tcp),
+            "tls" => Ok(Self::Tls),
+            "dtls" => Ok(Self::Dtls),
+            _ => Err(format!(
+                "Unknown transport `{s}`, expected `udp`, `tcp`, `tls` or `dtls`."
+            )),
+        }
+    }
+}
+
+impl Display for Transport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Udp => "udp",
+            Self::Tcp => "tcp",
+            Self::Tls => "tls",
+            Self::Dtls => "dtls",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+/// A transport address the server listens on.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ListenerConfig {
+    pub transport: Transport,
+    pub address: IpAddr,
+    pub port: u16,
+    /// Accept IPv4 on an IPv6 address, by disabling `IPV6_V6ONLY`.
+    ///
+    /// IPv4 sources then appear as IPv4-mapped IPv6 addresses,
+    /// which are normalised before they are sent back to the client.
+    /// Ignored for IPv4 addresses.
+    pub dual_stack: bool,
+    pub options: SocketOptions,
+}
+
+impl ListenerConfig {
+    /// Creates a dual-stack listener on the default port of `transport`.
+    pub fn new(transport: Transport, address: IpAddr) -> Self {
+        Self {
+            transport,
+            address,
+            port: transport.default_port(),
+            dual_stack: true,
+            // allows restarting while connections are in TIME_WAIT
+            options: SocketOptions {
+                reuse_address: transport.is_stream(),
+                ..Default::default()
+            },
+        }
+    }
+
+    pub fn socket_addr(&self) -> SocketAddr {
+        SocketAddr::new(self.address, self.port)
+    }
+
+    /// Creates the socket of the listener with its options,
+    /// bound to its address and listening if the transport is a stream.
+    ///
+    /// The socket is non-blocking, so it can be handed to an async runtime.
+    pub fn bind(&self) -> io::Result<Socket> {
+        let addr = self.socket_addr();
+
+        let (ty, protocol) = match self.transport.is_stream() {
+            true => (Type::STREAM, Protocol::TCP),
+            false => (Type::DGRAM, Protocol::UDP),
+        };
+
+        let socket = Socket::new(Domain::for_address(addr), ty, Some(protocol))?;
+
+        if addr.is_ipv6() {
+            socket.set_only_v6(!self.dual_stack)?;
+        }
+
+        self.options.apply(&socket, addr)?;
+
+        socket.set_nonblocking(true)?;
+        socket.bind(&addr.into())?;
+
+        if self.transport.is_stream() {
+            socket.listen(self.options.backlog.unwrap_or(1024))?;
+        }
+
+        Ok(socket)
+    }
+}
+
+impl Display for ListenerConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.transport, self.socket_addr())
+    }
+}
+
+/// Options that are set on the socket of a listener.
+///
+/// Options that are `None` keep the defaults of the operating system.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SocketOptions {
+    /// Sets `SO_REUSEADDR`.
+    pub reuse_address: bool,
+    /// Sets `SO_REUSEPORT`, so multiple processes can share the port.
+    ///
+    /// Only supported on Unix.
+    pub reuse_port: bool,
+    /// Sets `SO_RCVBUF`.
+    pub recv_buffer_size: Option<usize>,
+    /// Sets `SO_SNDBUF`.
+    pub send_buffer_size: Option<usize>,
+    /// Sets `IP_TTL` or `IPV6_UNICAST_HOPS`.
+    pub ttl: Option<u32>,
+    /// The maximum number of pending connections of stream transports.
+    pub backlog: Option<i32>,
+}
+
+impl SocketOptions {
+    fn apply(&self, socket: &Socket, addr: SocketAddr) -> io::Result<()> {
+        socket.set_reuse_address(self.reuse_address)?;
+
+        if self.reuse_port {
+            cfg_if::cfg_if! {
+                if #[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos"))))] {
+                    socket.set_reuse_port(true)?;
+                } else {
+                    return Err(io::Error::new(io::ErrorKind::Unsupported, "SO_REUSEPORT isn't supported"));
+                }
+            }
+        }
+
+        if let Some(size) = self.recv_buffer_size {
+            socket.set_recv_buffer_size(size)?;
+        }
+
+        if let Some(size) = self.send_buffer_size {
+            socket.set_send_buffer_size(size)?;
+        }
+
+        if let Some(ttl) = self.ttl {
+            match addr {
+                SocketAddr::V4(_) => socket.set_ttl(ttl)?,
+                SocketAddr::V6(_) => socket.set_unicast_hops_v6(ttl)?,
+            }
+        }
+
+        Ok(())
     }
 }
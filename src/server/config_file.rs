@@ -0,0 +1,254 @@
+use std::{
+    fs,
+    net::{IpAddr, Ipv6Addr},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use serde::Deserialize;
+
+use super::config::{
+    AuthConfig, ConfigError, ConfigErrorTy, ListenerConfig, LogConfig, LogFormat, ServerConfig,
+    SocketOptions, TlsConfig, Transport, TurnConfig,
+};
+
+// The schema of the file, which is kept apart from `ServerConfig`
+// so that every field can be omitted and unknown keys are rejected.
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    #[serde(default)]
+    classic: bool,
+    listeners: Option<Vec<ListenerFile>>,
+    #[serde(default)]
+    auth: AuthFile,
+    tls: Option<TlsFile>,
+    #[serde(default)]
+    turn: TurnFile,
+    #[serde(default)]
+    log: LogFile,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ListenerFile {
+    transport: String,
+    address: Option<IpAddr>,
+    port: Option<u16>,
+    dual_stack: Option<bool>,
+    #[serde(default)]
+    options: OptionsFile,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OptionsFile {
+    reuse_address: Option<bool>,
+    reuse_port: Option<bool>,
+    recv_buffer_size: Option<usize>,
+    send_buffer_size: Option<usize>,
+    ttl: Option<u32>,
+    backlog: Option<i32>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AuthFile {
+    realm: Option<String>,
+    credentials: Option<PathBuf>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TlsFile {
+    certificate: PathBuf,
+    key: PathBuf,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TurnFile {
+    max_allocations: Option<usize>,
+    max_allocations_per_user: Option<usize>,
+    /// In seconds.
+    max_lifetime: Option<u64>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LogFile {
+    level: Option<String>,
+    format: Option<String>,
+}
+
+impl ServerConfig {
+    /// Parses and validates a configuration in TOML.
+    ///
+    /// Omitted values keep their defaults, e.g. the server listens on UDP and TCP port 3478
+    /// if there are no `[[listeners]]`.
+    ///
+    /// ```toml
+    /// classic = false
+    ///
+    /// [[listeners]]
+    /// transport = "udp"
+    /// address = "::"
+    /// port = 3478
+    /// dual_stack = true
+    ///
+    /// [listeners.options]
+    /// recv_buffer_size = 1048576
+    ///
+    /// [auth]
+    /// realm = "example.org"
+    /// credentials = "users.txt"
+    ///
+    /// [turn]
+    /// max_allocations = 1000
+    /// max_allocations_per_user = 10
+    /// max_lifetime = 3600
+    ///
+    /// [log]
+    /// level = "info"
+    /// format = "json"
+    /// ```
+    pub fn from_toml(s: &str) -> Result<Self, ConfigError> {
+        let file: ConfigFile = toml::from_str(s).map_err(|err| ConfigError {
+            ty: ConfigErrorTy::Parse,
+            reason: format!("Invalid configuration: {err}"),
+        })?;
+
+        let config = file.into_config()?;
+
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// Loads and validates a TOML configuration file, see [ServerConfig::from_toml].
+    ///
+    /// Relative paths in the file are resolved against the directory of the file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+
+        let s = fs::read_to_string(path).map_err(|err| ConfigError {
+            reason: format!("Failed to read `{}`: {err}", path.display()),
+            ty: ConfigErrorTy::Io(err),
+        })?;
+
+        let mut config = Self::from_toml(&s).map_err(|err| ConfigError {
+            reason: format!("{}: {}", path.display(), err.reason),
+            ty: err.ty,
+        })?;
+
+        if let Some(dir) = path.parent() {
+            let resolve = |p: &mut PathBuf| *p = dir.join(&*p);
+
+            if let Some(credentials) = &mut config.auth.credentials {
+                resolve(credentials);
+            }
+
+            if let Some(tls) = &mut config.tls {
+                resolve(&mut tls.certificate);
+                resolve(&mut tls.key);
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+impl ConfigFile {
+    fn into_config(self) -> Result<ServerConfig, ConfigError> {
+        let defaults = ServerConfig::default();
+
+        let listeners = match self.listeners {
+            Some(listeners) => listeners
+                .into_iter()
+                .map(ListenerFile::into_config)
+                .collect::<Result<_, _>>()?,
+            None => defaults.listeners,
+        };
+
+        let turn = TurnConfig {
+            max_allocations: self.turn.max_allocations,
+            max_allocations_per_user: self.turn.max_allocations_per_user,
+            max_lifetime: self
+                .turn
+                .max_lifetime
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.turn.max_lifetime),
+        };
+
+        let log = LogConfig {
+            level: parse_field("log.level", self.log.level)?.unwrap_or(defaults.log.level),
+            format: parse_field::<LogFormat>("log.format", self.log.format)?
+                .unwrap_or(defaults.log.format),
+        };
+
+        Ok(ServerConfig {
+            classic: self.classic,
+            listeners,
+            auth: AuthConfig {
+                realm: self.auth.realm,
+                credentials: self.auth.credentials,
+            },
+            tls: self.tls.map(|tls| TlsConfig {
+                certificate: tls.certificate,
+                key: tls.key,
+            }),
+            turn,
+            log,
+        })
+    }
+}
+
+impl ListenerFile {
+    fn into_config(self) -> Result<ListenerConfig, ConfigError> {
+        let transport: Transport = parse_field("listeners.transport", Some(self.transport))?
+            .expect("the transport is required");
+
+        let mut listener = ListenerConfig::new(
+            transport,
+            self.address.unwrap_or(IpAddr::V6(Ipv6Addr::UNSPECIFIED)),
+        );
+
+        if let Some(port) = self.port {
+            listener.port = port;
+        }
+
+        if let Some(dual_stack) = self.dual_stack {
+            listener.dual_stack = dual_stack;
+        }
+
+        let options = self.options;
+        let defaults = listener.options;
+
+        listener.options = SocketOptions {
+            reuse_address: options.reuse_address.unwrap_or(defaults.reuse_address),
+            reuse_port: options.reuse_port.unwrap_or(defaults.reuse_port),
+            recv_buffer_size: options.recv_buffer_size,
+            send_buffer_size: options.send_buffer_size,
+            ttl: options.ttl,
+            backlog: options.backlog,
+        };
+
+        Ok(listener)
+    }
+}
+
+fn parse_field<T>(key: &str, value: Option<String>) -> Result<Option<T>, ConfigError>
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    value
+        .map(|value| value.parse())
+        .transpose()
+        .map_err(|err| ConfigError {
+            ty: ConfigErrorTy::Parse,
+            reason: format!("Invalid configuration: `{key}`: {err}"),
+        })
+}
@@ -1,19 +1,31 @@
 use std::marker::PhantomData;
+use std::net::SocketAddr;
 use std::sync::{Arc, atomic::AtomicBool};
 
-use self::config::ServerConfig;
+use bytes::Bytes;
+
+use crate::message::{MessageBuilder, MessageRef, IncomingMessage, IncomingErrorTy, ClassTy, OutgoingError, Registry, MAGIC, REQUEST_CLASS, ERROR_RESPONSE_CLASS, attributes::{ErrorCode, MappedAddress, Nonce, PasswordAlgorithms, Realm, XorMappedAddress}, methods::MethodTy};
+
+use self::auth::Authenticator;
+use self::config::{ConfigHandle, ServerConfig};
 use self::runtime::{ServerRuntime, ServerRunner};
 
+pub mod auth;
 pub mod config;
+#[cfg(feature = "toml_config")]
+mod config_file;
+pub mod nonce;
 pub mod runtime;
 
 /// The STUN server.
 /// 
-/// Runs on ports 3478 (stun) and 5349 (stuns).
+/// Listens on the transport addresses of its [ServerConfig],
+/// which are UDP and TCP port 3478 (stun) by default.
 #[derive(Default)]
 pub struct Server<R: ServerRuntime> {
     running: Arc<AtomicBool>,
-    config: ServerConfig,
+    config: ConfigHandle,
+    auth: Option<Arc<Authenticator>>,
     _marker: PhantomData<R>,
 }
 
@@ -21,17 +33,174 @@ impl<R: ServerRuntime> Server<R> {
     pub fn new(running: Arc<AtomicBool>, config: ServerConfig) -> Self {
         Self {
             running,
-            config,
+            config: config.into(),
+            auth: None,
             _marker: PhantomData,
         }
     }
 
+    /// Authenticates requests with the credentials of `auth`.
+    ///
+    /// Requests are authenticated with long-term credentials if the configuration has a realm,
+    /// and with short-term credentials otherwise.
+    pub fn authenticator(mut self, auth: Authenticator) -> Self {
+        self.auth = Some(Arc::new(auth));
+        self
+    }
+
+    /// The handle to reload the configuration while the server is running.
+    pub fn config(&self) -> ConfigHandle {
+        self.config.clone()
+    }
+
     pub async fn run(&mut self) {
         let runner = ServerRunner {
             running: self.running.clone(),
-            config: self.config,
+            config: self.config.clone(),
+            auth: self.auth.clone(),
         };
 
         R::run(runner).await;
     }
 }
+
+/// Builds the success response to a Binding request from `source`.
+///
+/// Classic STUN (RFC 3489) clients don't understand XOR-MAPPED-ADDRESS,
+/// so they are answered with MAPPED-ADDRESS instead.
+/// IPv4-mapped IPv6 sources of dual-stack sockets are normalised to IPv4,
+/// since that is the address the client actually has.
+pub fn binding_response(request: &IncomingMessage, source: SocketAddr) -> Result<MessageBuilder, OutgoingError> {
+    let response = MessageBuilder::success_response(request);
+
+    let source = SocketAddr::new(source.ip().to_canonical(), source.port());
+
+    match request.classic {
+        Some(_) => response.attribute(MappedAddress::new(source)),
+        None => response.attribute(XorMappedAddress::new(source)),
+    }
+}
+
+/// Builds an error response that challenges the client to authenticate with long-term credentials.
+///
+/// `error_code` is usually 401 (Unauthenticated) or 438 (Stale Nonce).
+/// PASSWORD-ALGORITHMS is only advertised if the nonce has the password algorithms security feature,
+/// so that the client can detect if it was stripped.
+///
+/// See [RFC8489 Section 9.2.4](https://datatracker.ietf.org/doc/html/rfc8489#section-9.2.4) for more details.
+pub fn challenge_response(request: &IncomingMessage, error_code: ErrorCode, realm: &Realm, nonce: &Nonce, algorithms: &PasswordAlgorithms) -> Result<MessageBuilder, OutgoingError> {
+    let response = MessageBuilder::error_response(request, error_code)
+        .attribute(realm.clone())?
+        .attribute(nonce.clone())?;
+
+    match nonce.features() {
+        Some(features) if features.password_algorithms => response.attribute(algorithms.clone()),
+        _ => Ok(response),
+    }
+}
+
+/// Handles a message received from `source`, returning the response to send back.
+///
+/// Binding requests are answered with the transport address of `source`.
+/// With an [Authenticator], requests must be authenticated first,
+/// and their responses are signed with the same key.
+/// Requests that can't be decoded are rejected with 400 (Bad Request),
+/// and requests with unknown comprehension-required attributes with 420 (Unknown Attribute)
+/// once they are authenticated.
+/// Indications, responses and anything that isn't a STUN message are dropped silently.
+///
+/// See [RFC8489 Section 6.3](https://datatracker.ietf.org/doc/html/rfc8489#section-6.3) for more details.
+pub async fn handle_message(config: &ServerConfig, auth: Option<&Authenticator>, buf: &[u8], source: SocketAddr) -> Option<Bytes> {
+    let result = match config.classic {
+        true => IncomingMessage::decode_classic(buf, &Registry::default()),
+        false => IncomingMessage::decode(buf),
+    };
+
+    let response = match result {
+        Ok(message) => respond(config, auth, &message, buf, source).await,
+        Err(err) => {
+            // messages that fail the FINGERPRINT check may belong to another protocol
+            if matches!(err.ty, IncomingErrorTy::BadFingerprint) {
+                return None;
+            }
+
+            let header = request_header(buf, config.classic)?;
+
+            log::debug!("Rejected malformed request from {source}: {err}");
+
+            MessageBuilder::new(ERROR_RESPONSE_CLASS, header.method())
+                .transaction_id(header.transaction_id())
+                .classic(header.classic_transaction_id())
+                .attribute(ErrorCode::BadRequest)
+                .map(Some)
+        }
+    };
+
+    match response.and_then(|response| response.map(|response| response.try_encode(None)).transpose()) {
+        Ok(response) => response,
+        Err(err) => {
+            log::warn!("Failed to build response for {source}: {err}");
+            None
+        }
+    }
+}
+
+/// Builds the response to a decoded message, if it needs one.
+async fn respond(config: &ServerConfig, auth: Option<&Authenticator>, message: &IncomingMessage, buf: &[u8], source: SocketAddr) -> Result<Option<MessageBuilder>, OutgoingError> {
+    let ClassTy::Request { method, .. } = &message.body else {
+        return Ok(None);
+    };
+
+    let realm = config.auth.realm.as_deref();
+
+    let authenticated = match auth {
+        Some(auth) => match auth.authenticate(realm, message, buf, source).await {
+            Ok(authenticated) => Some(authenticated),
+            Err(error_code) => {
+                log::debug!("Rejected request from {source}: {error_code:?}");
+
+                return auth.reject(realm, message, error_code, source).await.map(Some);
+            }
+        },
+        None => None,
+    };
+
+    // unknown attributes are only checked after authentication, so the 420 can be signed
+    let mut response = match (message.unknown_attributes(), method) {
+        (Some(unknown), _) => MessageBuilder::error_response(message, ErrorCode::UnknownAttribute).attribute(unknown)?,
+        (None, MethodTy::Binding(_)) => binding_response(message, source)?,
+        (None, _) => MessageBuilder::error_response(message, ErrorCode::BadRequest),
+    };
+
+    if let Some(authenticated) = authenticated {
+        response = response.integrity(&authenticated.key, authenticated.integrity);
+    }
+
+    // FINGERPRINT is only added if the client uses it, since classic clients don't understand it
+    match (&message.fingerprint, &message.classic) {
+        (Some(_), None) => Ok(Some(response.fingerprint())),
+        _ => Ok(Some(response)),
+    }
+}
+
+/// Checks the header of a message that couldn't be decoded,
+/// so that it can still be rejected if it's a request.
+fn request_header(buf: &[u8], classic: bool) -> Option<MessageRef<'_>> {
+    if buf.len() < 20 || buf[0] & 0xC0 != 0 {
+        return None;
+    }
+
+    let len = u16::from_be_bytes([buf[2], buf[3]]) as usize;
+    let magic = u32::from_be_bytes(buf[4..8].try_into().unwrap());
+
+    if 20 + len != buf.len() || (magic != MAGIC && !classic) {
+        return None;
+    }
+
+    let header = MessageRef::new_unchecked(buf);
+
+    match header.class() {
+        REQUEST_CLASS => Some(header),
+        _ => None,
+    }
+}
@@ -0,0 +1,238 @@
+use std::{
+    fmt::Debug,
+    net::IpAddr,
+    sync::RwLock,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use zeroize::Zeroize;
+
+use crate::message::attributes::{ErrorCode, Nonce, SecurityFeatures, NONCE_COOKIE};
+
+/// The default lifetime of a nonce, as recommended for TURN servers.
+///
+/// See [RFC8656 Section 7.2](https://datatracker.ietf.org/doc/html/rfc8656#section-7.2) for more details.
+pub const DEFAULT_NONCE_LIFETIME: Duration = Duration::from_secs(3600);
+
+// bytes of the HMAC-SHA256 that are kept in the nonce
+const TAG_SIZE: usize = 16;
+
+// key ID (4 bytes), timestamp (8 bytes) and tag
+const PAYLOAD_SIZE: usize = 4 + 8 + TAG_SIZE;
+
+/// A secret for minting nonces.
+///
+/// Servers behind the same load balancer should share their secrets,
+/// so that a nonce minted by one instance is accepted by the others.
+/// The ID is embedded in every nonce to pick the secret it was minted with.
+#[derive(Clone, PartialEq)]
+pub struct NonceSecret {
+    id: u32,
+    key: [u8; 32],
+}
+
+impl NonceSecret {
+    pub fn new(id: u32, key: [u8; 32]) -> Self {
+        Self { id, key }
+    }
+
+    /// Generates a secret with a random key.
+    pub fn random(id: u32) -> Self {
+        Self::new(id, rand::random())
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn key(&self) -> &[u8; 32] {
+        &self.key
+    }
+
+    fn tag(&self, timestamp: u64, features: SecurityFeatures, ip: IpAddr) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.key).unwrap();
+
+        mac.update(&self.id.to_be_bytes());
+        mac.update(&timestamp.to_be_bytes());
+        mac.update(&features.bits().to_be_bytes()[1..]);
+
+        match ip.to_canonical() {
+            IpAddr::V4(ip) => mac.update(&ip.octets()),
+            IpAddr::V6(ip) => mac.update(&ip.octets()),
+        }
+
+        mac
+    }
+}
+
+impl Drop for NonceSecret {
+    fn drop(&mut self) {
+        self.key.zeroize();
+    }
+}
+
+// Implement Debug manually to prevent the key from being leaked to logs.
+impl Debug for NonceSecret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NonceSecret").field("id", &self.id).finish()
+    }
+}
+
+struct Secrets {
+    current: NonceSecret,
+    /// The previous secret and when it was replaced.
+    previous: Option<(NonceSecret, SystemTime)>,
+}
+
+/// Mints and validates stateless nonces.
+///
+/// A nonce consists of the nonce cookie and security features,
+/// followed by the ID of the secret, a timestamp and an HMAC-SHA256 (in hex).
+/// The HMAC covers all of these and the IP address of the client,
+/// so nonces can be validated without storing anything per client.
+///
+/// See [RFC8489 Section 9.2](https://datatracker.ietf.org/doc/html/rfc8489#section-9.2) for more details.
+pub struct NonceManager {
+    secrets: RwLock<Secrets>,
+    features: SecurityFeatures,
+    lifetime: Duration,
+    grace: Duration,
+}
+
+impl NonceManager {
+    /// Creates a manager that mints nonces with `features`,
+    /// which expire after `lifetime`.
+    pub fn new(secret: NonceSecret, features: SecurityFeatures, lifetime: Duration) -> Self {
+        Self {
+            secrets: RwLock::new(Secrets {
+                current: secret,
+                previous: None,
+            }),
+            features,
+            lifetime,
+            grace: Duration::ZERO,
+        }
+    }
+
+    /// Sets how long nonces of the previous secret are accepted after a rotation.
+    ///
+    /// `Duration::MAX` accepts them until the next rotation.
+    pub fn grace(mut self, grace: Duration) -> Self {
+        self.grace = grace;
+        self
+    }
+
+    pub fn features(&self) -> SecurityFeatures {
+        self.features
+    }
+
+    /// Replaces the current secret with `secret`.
+    ///
+    /// Nonces of the replaced secret are still accepted during the grace period,
+    /// while nonces of any older secret become stale.
+    pub fn rotate(&self, secret: NonceSecret) {
+        let mut secrets = self.secrets.write().unwrap();
+
+        let previous = std::mem::replace(&mut secrets.current, secret);
+        secrets.previous = Some((previous, SystemTime::now()));
+    }
+
+    /// Mints a nonce for the client at `ip`.
+    pub fn mint(&self, ip: IpAddr) -> Nonce {
+        self.mint_at(ip, SystemTime::now())
+    }
+
+    /// Mints a nonce for the client at `ip`, as if it was minted at `time`.
+    pub fn mint_at(&self, ip: IpAddr, time: SystemTime) -> Nonce {
+        let secrets = self.secrets.read().unwrap();
+        let secret = &secrets.current;
+
+        let timestamp = time
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        let tag = secret
+            .tag(timestamp, self.features, ip)
+            .finalize()
+            .into_bytes();
+
+        let mut payload = [0u8; PAYLOAD_SIZE];
+        payload[..4].copy_from_slice(&secret.id.to_be_bytes());
+        payload[4..12].copy_from_slice(&timestamp.to_be_bytes());
+        payload[12..].copy_from_slice(&tag[..TAG_SIZE]);
+
+        let payload: String = payload.iter().map(|b| format!("{b:02x}")).collect();
+
+        Nonce::with_features(self.features, payload)
+    }
+
+    /// Validates a nonce that the client at `ip` sent.
+    ///
+    /// Fails with [ErrorCode::StaleNonce] if the nonce expired, wasn't minted by this server
+    /// or for this client, or its secret was rotated out.
+    /// The client should then retry with the fresh nonce of the error response.
+    pub fn validate(&self, nonce: &Nonce, ip: IpAddr) -> Result<(), ErrorCode> {
+        self.validate_at(nonce, ip, SystemTime::now())
+    }
+
+    /// Validates a nonce that the client at `ip` sent, as if it was received at `now`.
+    pub fn validate_at(&self, nonce: &Nonce, ip: IpAddr, now: SystemTime) -> Result<(), ErrorCode> {
+        let features = nonce.features().ok_or(ErrorCode::StaleNonce)?;
+
+        let nonce = nonce.to_string();
+        let payload = nonce
+            .get((NONCE_COOKIE.len() + 4)..)
+            .and_then(decode_hex)
+            .ok_or(ErrorCode::StaleNonce)?;
+
+        let id = u32::from_be_bytes(payload[..4].try_into().unwrap());
+        let timestamp = u64::from_be_bytes(payload[4..12].try_into().unwrap());
+        let tag = &payload[12..];
+
+        let secrets = self.secrets.read().unwrap();
+
+        let secret = match &secrets.previous {
+            _ if secrets.current.id == id => &secrets.current,
+            // a grace period that overflows the clock, e.g. `Duration::MAX`, never ends
+            Some((previous, rotated))
+                if previous.id == id
+                    && rotated.checked_add(self.grace).is_none_or(|end| now < end) =>
+            {
+                previous
+            }
+            _ => return Err(ErrorCode::StaleNonce),
+        };
+
+        secret
+            .tag(timestamp, features, ip)
+            .verify_truncated_left(tag)
+            .map_err(|_| ErrorCode::StaleNonce)?;
+
+        let minted = UNIX_EPOCH
+            .checked_add(Duration::from_secs(timestamp))
+            .ok_or(ErrorCode::StaleNonce)?;
+
+        // nonces from the future were minted by an instance whose clock is ahead
+        match now.duration_since(minted).unwrap_or_default() <= self.lifetime {
+            true => Ok(()),
+            false => Err(ErrorCode::StaleNonce),
+        }
+    }
+}
+
+fn decode_hex(s: &str) -> Option<[u8; PAYLOAD_SIZE]> {
+    if s.len() != PAYLOAD_SIZE * 2 || !s.is_ascii() {
+        return None;
+    }
+
+    let mut bytes = [0u8; PAYLOAD_SIZE];
+
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&s[(i * 2)..(i * 2 + 2)], 16).ok()?;
+    }
+
+    Some(bytes)
+}
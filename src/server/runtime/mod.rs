@@ -1,10 +1,8 @@
-use std::{io, sync::{Arc, Mutex, atomic::AtomicBool}, task::{Poll, Waker}, time::{Instant, Duration}, net::SocketAddr};
-
-use std::future::Future;
+use std::{io, sync::{Arc, atomic::AtomicBool}, net::SocketAddr};
 
 use bytes::Bytes;
 
-use super::config::ServerConfig;
+use super::{auth::Authenticator, config::ConfigHandle, handle_message};
 
 pub mod tokio_server;
 
@@ -17,7 +15,8 @@ pub const SECURE_PORT: u16 = 5349;
 #[derive(Clone)]
 pub struct ServerRunner {
     pub running: Arc<AtomicBool>,
-    pub config: ServerConfig,
+    pub config: ConfigHandle,
+    pub auth: Option<Arc<Authenticator>>,
 }
 
 #[async_trait::async_trait]
@@ -34,16 +33,40 @@ pub trait ServerConn {
 
 pub struct ServerProcessor<T: ServerConn> {
     conn: T,
+    config: ConfigHandle,
+    auth: Option<Arc<Authenticator>>,
 }
 
 impl<T: ServerConn> ServerProcessor<T> {
-    pub fn new(conn: T) -> Self {
+    pub fn new(conn: T, config: ConfigHandle) -> Self {
         Self {
-            conn
+            conn,
+            config,
+            auth: None,
         }
     }
 
-    pub fn process(&self) {
-        
+    /// Authenticates requests with `auth`, see [Server::authenticator](crate::server::Server::authenticator).
+    pub fn authenticator(mut self, auth: Option<Arc<Authenticator>>) -> Self {
+        self.auth = auth;
+        self
     }
-}
\ No newline at end of file
+
+    /// Answers the messages on the connection until it fails or is closed.
+    ///
+    /// Every message is handled with the configuration at the time it was received.
+    pub async fn process(&mut self) -> io::Result<()> {
+        loop {
+            let (buf, source) = self.conn.recv().await?;
+
+            let Some(response) = handle_message(&self.config.get(), self.auth.as_deref(), &buf, source).await else {
+                continue;
+            };
+
+            // a peer that went away shouldn't stop the others from being answered
+            if let Err(err) = self.conn.send(&response, source).await {
+                log::warn!("Failed to send response to {source}: {err}");
+            }
+        }
+    }
+}
@@ -1,128 +1,201 @@
-use std::{io, net::{SocketAddr, IpAddr, Ipv6Addr}, sync::{Arc, atomic::{AtomicBool, Ordering}}, pin::Pin};
+use std::{io, net::SocketAddr, sync::atomic::Ordering};
 
-use bytes::{Bytes, BytesMut};
-use futures::{FutureExt, future::poll_fn};
-use tokio::{net::{TcpListener, TcpStream, TcpSocket, UdpSocket}, time::{timeout, Duration}, io::{AsyncReadExt, AsyncWriteExt}};
+use bytes::Bytes;
+use futures::StreamExt;
+use tokio::{net::{TcpListener, TcpStream, UdpSocket}, task::{JoinHandle, JoinSet}, time::{interval, sleep, timeout, Duration}, io::AsyncWriteExt};
+use tokio_util::codec::Framed;
 
+use crate::message::{Frame, FrameCodec};
+use crate::server::config::{ListenerConfig, Transport};
 
 use super::*;
 
 const MAX_PACKET_SIZE: usize = 65535;
 
+/// How long to wait before restarting a listener that failed.
+const RESTART_DELAY: Duration = Duration::from_secs(1);
+
 pub struct TokioServerRuntime;
 
 impl TokioServerRuntime {
-    async fn serve_tcp(runner: ServerRunner) -> io::Result<()> {
-        // TODO: Make addr const when Rust 1.69 releases
-        let addr = SocketAddr::new(IpAddr::V6(Ipv6Addr::LOCALHOST), INSECURE_PORT);
-    
-        let listener = TcpListener::bind(addr).await?;
+    async fn serve_tcp(runner: ServerRunner, listener: ListenerConfig) -> io::Result<()> {
+        let socket = TcpListener::from_std(listener.bind()?.into())?;
     
-        log::debug!("Started `tcp/{INSECURE_PORT}`");
+        log::debug!("Started `{listener}`");
+
+        // the connections are owned by the listener, so they are aborted when it stops
+        let mut connections = JoinSet::new();
     
         while runner.running.load(Ordering::Relaxed) {
-            let Ok(result) = timeout(Duration::from_millis(200), listener.accept()).await else {
+            // reap the connections that were closed
+            while connections.try_join_next().is_some() {}
+
+            let Ok(result) = timeout(Duration::from_millis(200), socket.accept()).await else {
                 continue;
             };
     
             let (stream, remote) = match result {
                 Ok(r) => r,
                 Err(err) => {
-                    log::warn!("`tcp/{INSECURE_PORT}` failed to connect to remote: {err}");
+                    log::warn!("`{listener}` failed to connect to remote: {err}");
                     continue;
                 }
             };
 
             let conn = TcpConn {
-                stream,
+                stream: Framed::new(stream, FrameCodec),
                 remote,
             };
-    
-            tokio::spawn(async move {
-                let processor = ServerProcessor::new(conn);
 
-                processor.process();
+            let config = runner.config.clone();
+            let auth = runner.auth.clone();
+            let listener = listener.clone();
+    
+            connections.spawn(async move {
+                let mut processor = ServerProcessor::new(conn, config).authenticator(auth);
+
+                match processor.process().await {
+                    Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                        log::debug!("`{listener}` closed connection to {remote}");
+                    }
+                    Err(err) => {
+                        log::warn!("`{listener}` closed connection to {remote}: {err}");
+                    }
+                    Ok(()) => (),
+                }
             });
         }
     
         Ok(())
     }
     
-    async fn serve_udp(runner: ServerRunner) -> io::Result<()> {
-        // TODO: make const
-        let addr = SocketAddr::new(IpAddr::V6(Ipv6Addr::LOCALHOST), INSECURE_PORT);
-    
-        let socket = UdpSocket::bind(addr).await?;
+    async fn serve_udp(runner: ServerRunner, listener: ListenerConfig) -> io::Result<()> {
+        let socket = UdpSocket::from_std(listener.bind()?.into())?;
     
-        log::debug!("Started `udp/{INSECURE_PORT}`");
+        log::debug!("Started `{listener}`");
 
         let conn = UdpConn {
             socket,
         };
         
-        let processor = ServerProcessor::new(conn);
+        let mut processor = ServerProcessor::new(conn, runner.config).authenticator(runner.auth);
 
-        processor.process();
-    
-        Ok(())
+        processor.process().await
+    }
+
+    fn spawn(runner: &ServerRunner, listener: &ListenerConfig, delay: Duration) -> JoinHandle<io::Result<()>> {
+        let runner = runner.clone();
+        let listener = listener.clone();
+
+        tokio::spawn(async move {
+            sleep(delay).await;
+
+            match listener.transport {
+                Transport::Udp => Self::serve_udp(runner, listener).await,
+                Transport::Tcp => Self::serve_tcp(runner, listener).await,
+                Transport::Tls | Transport::Dtls => unreachable!("Unsupported transports aren't spawned"),
+            }
+        })
     }
 }
 
 #[async_trait::async_trait]
 impl ServerRuntime for TokioServerRuntime {
+    /// Runs the listeners of the configuration until the server stops.
+    ///
+    /// Listeners that fail are restarted, and the listeners are reconciled with the configuration
+    /// whenever it's reloaded: added listeners are started and removed listeners are stopped
+    /// along with their connections, while unchanged listeners keep their sockets.
     async fn run(runner: ServerRunner) {
-        let mut tcp = tokio::spawn(Self::serve_tcp(runner.clone()));
-        let mut udp = tokio::spawn(Self::serve_udp(runner.clone()));
+        // unsupported listeners have no task, so they are only reported once
+        let mut tasks: Vec<(ListenerConfig, Option<JoinHandle<io::Result<()>>>)> = vec![];
 
-        poll_fn(|cx| {
-            if runner.running.load(Ordering::Relaxed) {
-                let tcp_status = tcp.poll_unpin(cx);
+        let mut interval = interval(Duration::from_millis(200));
 
-                if let Poll::Ready(Ok(Err(e))) = tcp_status {
-                    log::error!("{e}");
+        while runner.running.load(Ordering::Relaxed) {
+            let config = runner.config.get();
 
-                    tcp = tokio::spawn(Self::serve_tcp(runner.clone()));
+            tasks.retain(|(listener, task)| {
+                if config.listeners.contains(listener) {
+                    return true;
                 }
 
-                let udp_status = udp.poll_unpin(cx);
+                if let Some(task) = task {
+                    task.abort();
+                    log::info!("Stopped `{listener}`");
+                }
 
-                if let Poll::Ready(Ok(Err(e))) = udp_status {
-                    log::error!("{e}");
+                false
+            });
 
-                    udp = tokio::spawn(Self::serve_udp(runner.clone()));
+            for listener in &config.listeners {
+                if tasks.iter().any(|(l, _)| l == listener) {
+                    continue;
                 }
 
-                Poll::Pending
-            } else {
-                tcp.abort();
-                udp.abort();
+                let task = match listener.transport {
+                    Transport::Udp | Transport::Tcp => Some(Self::spawn(&runner, listener, Duration::ZERO)),
+                    Transport::Tls | Transport::Dtls => {
+                        log::error!("`{listener}` isn't supported yet");
+                        None
+                    }
+                };
 
-                Poll::Ready(())
+                tasks.push((listener.clone(), task));
             }
-        }).await;
+
+            for (listener, task) in &mut tasks {
+                let Some(task) = task else {
+                    continue;
+                };
+
+                if !task.is_finished() {
+                    continue;
+                }
+
+                match (&mut *task).await {
+                    Ok(Ok(())) => log::debug!("Stopped `{listener}`"),
+                    Ok(Err(e)) => log::error!("`{listener}` failed: {e}"),
+                    Err(e) => log::error!("`{listener}` panicked: {e}"),
+                }
+
+                // a finished task can't be polled again, so it's replaced even when stopping
+                *task = Self::spawn(&runner, listener, RESTART_DELAY);
+            }
+
+            interval.tick().await;
+        }
+
+        for task in tasks.iter().filter_map(|(_, task)| task.as_ref()) {
+            task.abort();
+        }
     }
 }
 
 struct TcpConn {
-    stream: TcpStream,
+    stream: Framed<TcpStream, FrameCodec>,
     remote: SocketAddr,
 }
 
 #[async_trait::async_trait]
 impl ServerConn for TcpConn {
-    async fn send(&mut self, buf: &[u8], addr: SocketAddr) -> io::Result<()> {
-        self.stream.write_all(&buf).await?;
+    async fn send(&mut self, buf: &[u8], _addr: SocketAddr) -> io::Result<()> {
+        self.stream.get_mut().write_all(buf).await?;
         Ok(())
     }
 
     async fn recv(&mut self) -> io::Result<(Bytes, SocketAddr)> {
-        let mut buf = [0u8; MAX_PACKET_SIZE];
-
-        let size = self.stream.read(&mut buf).await?;
-
-        let bytes = Bytes::from(buf[0..size].to_vec());
-
-        Ok((bytes, self.remote))
+        loop {
+            match self.stream.next().await {
+                Some(Ok(Frame::Message(message))) => return Ok((message, self.remote)),
+                // TURN isn't supported, so channel data is dropped
+                Some(Ok(Frame::ChannelData { channel, .. })) => {
+                    log::debug!("Dropped ChannelData on channel {channel:#06x} from {}", self.remote);
+                }
+                Some(Err(err)) => return Err(err),
+                None => return Err(io::ErrorKind::UnexpectedEof.into()),
+            }
+        }
     }
 }
 
@@ -134,11 +207,11 @@ struct UdpConn {
 impl ServerConn for UdpConn {
     async fn send(&mut self, buf: &[u8], addr: SocketAddr) -> io::Result<()> {
         let size = self.socket.send_to(buf, addr).await?;
-        return if size != buf.len() {
-            Err(io::Error::new(io::ErrorKind::Other, "Failed to write full message"))
+        if size != buf.len() {
+            Err(io::Error::other("Failed to write full message"))
         } else {
             Ok(())
-        };
+        }
     }
 
     async fn recv(&mut self) -> io::Result<(Bytes, SocketAddr)> {
@@ -0,0 +1,667 @@
+//! Credentials of users, as looked up by a server.
+
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Mutex, RwLock},
+    time::SystemTime,
+};
+
+use zeroize::Zeroizing;
+
+use crate::message::{
+    attributes::{PasswordAlgorithm, Userhash, MD5_PASSWORD_ALGORITHM, SHA256_PASSWORD_ALGORITHM},
+    opaque_string, LongTermKey, PrecisError,
+};
+
+/// The credentials of a user, as kept by a [CredentialStore].
+///
+/// Long-term credentials only keep the keys derived from the password,
+/// so the password itself doesn't need to be stored.
+/// Keys and passwords are wiped from memory on drop.
+#[derive(Clone, PartialEq)]
+pub enum StoredCredentials {
+    LongTerm {
+        username: String,
+        realm: String,
+        /// The keys of the user, one per password algorithm.
+        keys: Vec<LongTermKey>,
+    },
+    ShortTerm {
+        username: String,
+        password: Zeroizing<String>,
+    },
+}
+
+// Implement Debug manually to prevent the keys from being leaked to logs.
+impl Debug for StoredCredentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::LongTerm {
+                username, realm, ..
+            } => f
+                .debug_struct("LongTerm")
+                .field("username", username)
+                .field("realm", realm)
+                .finish(),
+            Self::ShortTerm { username, .. } => f
+                .debug_struct("ShortTerm")
+                .field("username", username)
+                .finish(),
+        }
+    }
+}
+
+impl StoredCredentials {
+    /// Creates long-term credentials with the keys of the MD5 and SHA-256 password algorithms.
+    ///
+    /// The username, realm and password are prepared with [opaque_string].
+    pub fn long_term(username: &str, realm: &str, password: &str) -> Result<Self, PrecisError> {
+        let keys = [&*SHA256_PASSWORD_ALGORITHM, &*MD5_PASSWORD_ALGORITHM]
+            .into_iter()
+            .map(|algorithm| LongTermKey::derive(username, realm, password, algorithm))
+            .collect::<Result<_, PrecisError>>()?;
+
+        Ok(Self::LongTerm {
+            username: opaque_string(username)?,
+            realm: opaque_string(realm)?,
+            keys,
+        })
+    }
+
+    /// Creates short-term credentials.
+    ///
+    /// The username and password are prepared with [opaque_string].
+    pub fn short_term(username: &str, password: &str) -> Result<Self, PrecisError> {
+        Ok(Self::ShortTerm {
+            username: opaque_string(username)?,
+            password: Zeroizing::new(opaque_string(password)?),
+        })
+    }
+
+    pub fn username(&self) -> &str {
+        match self {
+            Self::LongTerm { username, .. } | Self::ShortTerm { username, .. } => username,
+        }
+    }
+
+    /// The realm of long-term credentials.
+    pub fn realm(&self) -> Option<&str> {
+        match self {
+            Self::LongTerm { realm, .. } => Some(realm),
+            Self::ShortTerm { .. } => None,
+        }
+    }
+
+    /// The key of the message integrity attributes.
+    ///
+    /// For long-term credentials, that is the key of `algorithm`, if there is one.
+    /// For short-term credentials, that is the password and `algorithm` is ignored.
+    pub fn key(&self, algorithm: &PasswordAlgorithm) -> Option<&[u8]> {
+        match self {
+            Self::LongTerm { keys, .. } => keys
+                .iter()
+                .find(|key| key.algorithm() == algorithm)
+                .map(LongTermKey::as_bytes),
+            Self::ShortTerm { password, .. } => Some(password.as_bytes()),
+        }
+    }
+
+    /// The password algorithms of the keys of long-term credentials.
+    pub fn algorithms(&self) -> Vec<PasswordAlgorithm> {
+        match self {
+            Self::LongTerm { keys, .. } => keys.iter().map(|key| key.algorithm().clone()).collect(),
+            Self::ShortTerm { .. } => vec![],
+        }
+    }
+
+    /// Identifies the credentials in a store.
+    fn id(&self) -> (String, Option<String>) {
+        (self.username().into(), self.realm().map(Into::into))
+    }
+
+    /// The USERHASH of long-term credentials.
+    pub fn userhash(&self) -> Option<Userhash> {
+        match self {
+            Self::LongTerm {
+                username, realm, ..
+            } => Some(Userhash::hash(username, realm)),
+            Self::ShortTerm { .. } => None,
+        }
+    }
+}
+
+/// Looks up the credentials of users.
+///
+/// Usernames and realms are compared after they were prepared with [opaque_string],
+/// as clients send them.
+#[async_trait::async_trait]
+pub trait CredentialStore: Send + Sync {
+    /// Looks up the long-term credentials of `username` in `realm`,
+    /// or the short-term credentials of `username` if `realm` is `None`.
+    async fn by_username(&self, username: &str, realm: Option<&str>) -> Option<StoredCredentials>;
+
+    /// Lists the long-term credentials of `realm`.
+    async fn by_realm(&self, realm: &str) -> Vec<StoredCredentials>;
+
+    /// Looks up the long-term credentials in `realm` whose USERHASH is `userhash`.
+    ///
+    /// By default, this hashes every username of the realm,
+    /// stores with many users should keep a [UserhashIndex] instead.
+    ///
+    /// See [RFC8489 Section 14.4](https://datatracker.ietf.org/doc/html/rfc8489#section-14.4) for more details.
+    async fn by_userhash(&self, userhash: &Userhash, realm: &str) -> Option<StoredCredentials> {
+        self.by_realm(realm)
+            .await
+            .into_iter()
+            .find(|credentials| credentials.userhash().as_ref() == Some(userhash))
+    }
+
+    /// Lists the password algorithms that every user of `realm` has a key for,
+    /// which are the only ones a server can advertise without locking out some users.
+    /// Returns an empty list if the realm has no users.
+    ///
+    /// By default, this looks at the keys of every user of the realm.
+    async fn algorithms(&self, realm: &str) -> Vec<PasswordAlgorithm> {
+        common_algorithms(&self.by_realm(realm).await)
+    }
+}
+
+/// Lists the password algorithms that all the long-term credentials have a key for.
+fn common_algorithms<'a>(
+    credentials: impl IntoIterator<Item = &'a StoredCredentials>,
+) -> Vec<PasswordAlgorithm> {
+    let mut common: Option<Vec<PasswordAlgorithm>> = None;
+
+    for credentials in credentials {
+        let algorithms = credentials.algorithms();
+
+        common = Some(match common {
+            Some(common) => common
+                .into_iter()
+                .filter(|a| algorithms.contains(a))
+                .collect(),
+            None => algorithms,
+        });
+    }
+
+    common.unwrap_or_default()
+}
+
+/// Maps the USERHASH of long-term credentials back to the username, per realm.
+///
+/// The hash covers the realm, so the same user has a different USERHASH in every realm,
+/// and an entry must be updated whenever a user or their realm changes.
+/// Stores that can't hash their usernames when looking them up, like [MemoryStore],
+/// keep an index next to the credentials.
+///
+/// See [RFC8489 Section 14.4](https://datatracker.ietf.org/doc/html/rfc8489#section-14.4) for more details.
+#[derive(Debug, Default, Clone)]
+pub struct UserhashIndex {
+    usernames: HashMap<(Userhash, String), String>,
+}
+
+impl UserhashIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the USERHASH of `username` in `realm`, which must already be prepared with [opaque_string].
+    pub fn insert(&mut self, username: &str, realm: &str) {
+        self.usernames.insert(
+            (Userhash::hash(username, realm), realm.into()),
+            username.into(),
+        );
+    }
+
+    /// Removes the USERHASH of `username` in `realm`.
+    pub fn remove(&mut self, username: &str, realm: &str) {
+        self.usernames
+            .remove(&(Userhash::hash(username, realm), realm.into()));
+    }
+
+    /// Looks up the username whose USERHASH in `realm` is `userhash`.
+    pub fn get(&self, userhash: &Userhash, realm: &str) -> Option<&str> {
+        self.usernames
+            .get(&(userhash.clone(), realm.into()))
+            .map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.usernames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.usernames.is_empty()
+    }
+}
+
+impl<'a> FromIterator<&'a StoredCredentials> for UserhashIndex {
+    /// Indexes the long-term credentials, skipping short-term credentials.
+    fn from_iter<T: IntoIterator<Item = &'a StoredCredentials>>(iter: T) -> Self {
+        let mut index = Self::new();
+
+        for credentials in iter {
+            if let Some(realm) = credentials.realm() {
+                index.insert(credentials.username(), realm);
+            }
+        }
+
+        index
+    }
+}
+
+/// Keeps credentials in memory, with an index of their USERHASH.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    users: RwLock<Users>,
+}
+
+// The credentials and their index are behind the same lock,
+// so lookups never see one without the other.
+#[derive(Debug, Default)]
+struct Users {
+    credentials: HashMap<(String, Option<String>), StoredCredentials>,
+    userhashes: UserhashIndex,
+    /// The password algorithms every user of a realm has a key for.
+    algorithms: HashMap<String, Vec<PasswordAlgorithm>>,
+}
+
+impl Users {
+    fn update_algorithms(&mut self, realm: &str) {
+        let algorithms = common_algorithms(
+            self.credentials
+                .values()
+                .filter(|credentials| credentials.realm() == Some(realm)),
+        );
+
+        match algorithms.is_empty() {
+            true => self.algorithms.remove(realm),
+            false => self.algorithms.insert(realm.into(), algorithms),
+        };
+    }
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds credentials, replacing the credentials of the same user and realm.
+    pub fn insert(&self, credentials: StoredCredentials) {
+        let mut users = self.users.write().unwrap();
+
+        let realm = credentials.realm().map(String::from);
+
+        if let Some(realm) = &realm {
+            users.userhashes.insert(credentials.username(), realm);
+        }
+
+        users.credentials.insert(credentials.id(), credentials);
+
+        if let Some(realm) = &realm {
+            users.update_algorithms(realm);
+        }
+    }
+
+    /// Removes the credentials of `username` in `realm`, or the short-term credentials if `realm` is `None`.
+    pub fn remove(&self, username: &str, realm: Option<&str>) -> Option<StoredCredentials> {
+        let mut users = self.users.write().unwrap();
+
+        let removed = users
+            .credentials
+            .remove(&(username.into(), realm.map(Into::into)))?;
+
+        if let Some(realm) = realm {
+            users.userhashes.remove(username, realm);
+            users.update_algorithms(realm);
+        }
+
+        Some(removed)
+    }
+
+    /// Replaces all credentials at once, rebuilding the index,
+    /// so lookups never see a partial update.
+    pub fn replace(&self, credentials: impl IntoIterator<Item = StoredCredentials>) {
+        let credentials: HashMap<_, _> = credentials.into_iter().map(|c| (c.id(), c)).collect();
+        let userhashes = credentials.values().collect();
+
+        let mut users = Users {
+            credentials,
+            userhashes,
+            algorithms: HashMap::new(),
+        };
+
+        let realms: Vec<String> = users
+            .credentials
+            .values()
+            .filter_map(|credentials| credentials.realm().map(String::from))
+            .collect();
+
+        for realm in realms {
+            if !users.algorithms.contains_key(&realm) {
+                users.update_algorithms(&realm);
+            }
+        }
+
+        *self.users.write().unwrap() = users;
+    }
+
+    pub fn len(&self) -> usize {
+        self.users.read().unwrap().credentials.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[async_trait::async_trait]
+impl CredentialStore for MemoryStore {
+    async fn by_username(&self, username: &str, realm: Option<&str>) -> Option<StoredCredentials> {
+        self.users
+            .read()
+            .unwrap()
+            .credentials
+            .get(&(username.into(), realm.map(Into::into)))
+            .cloned()
+    }
+
+    async fn by_realm(&self, realm: &str) -> Vec<StoredCredentials> {
+        self.users
+            .read()
+            .unwrap()
+            .credentials
+            .values()
+            .filter(|credentials| credentials.realm() == Some(realm))
+            .cloned()
+            .collect()
+    }
+
+    async fn by_userhash(&self, userhash: &Userhash, realm: &str) -> Option<StoredCredentials> {
+        let users = self.users.read().unwrap();
+
+        let username = users.userhashes.get(userhash, realm)?;
+
+        users
+            .credentials
+            .get(&(username.into(), Some(realm.into())))
+            .cloned()
+    }
+
+    async fn algorithms(&self, realm: &str) -> Vec<PasswordAlgorithm> {
+        self.users
+            .read()
+            .unwrap()
+            .algorithms
+            .get(realm)
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+/// Loads long-term credentials from a file, which can be reloaded while it's in use.
+///
+/// Every line of the file is `username:realm:key`, where `key` is the hash of
+/// `username:realm:password` in hex, see [LongTermKey::to_hex].
+/// The password algorithm is picked by the length of the key,
+/// 32 digits for MD5 and 64 digits for SHA-256,
+/// so a user can have a line for each algorithm.
+/// Empty lines and lines starting with `#` are ignored.
+///
+/// Usernames can't contain `:`, while realms can.
+///
+/// ```text
+/// # username:realm:key
+/// alice:example.org:df60a444f2935c6996a3af5ed4a76bd1
+/// ```
+#[derive(Debug)]
+pub struct FileStore {
+    path: PathBuf,
+    store: MemoryStore,
+    /// When the file was modified before it was last loaded.
+    modified: Mutex<Option<SystemTime>>,
+}
+
+impl FileStore {
+    pub fn load(path: impl Into<PathBuf>) -> Result<Self, CredentialError> {
+        let store = Self {
+            path: path.into(),
+            store: MemoryStore::new(),
+            modified: Mutex::new(None),
+        };
+
+        store.reload()?;
+
+        Ok(store)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Reads the file again, replacing all credentials.
+    ///
+    /// If the file can't be read or parsed, the current credentials are kept.
+    pub fn reload(&self) -> Result<(), CredentialError> {
+        let modified = self.modified_at();
+
+        let s = fs::read_to_string(&self.path)
+            .map(Zeroizing::new)
+            .map_err(|err| CredentialError {
+                reason: format!("Failed to read `{}`: {err}", self.path.display()),
+                ty: CredentialErrorTy::Io(err),
+            })?;
+
+        let users = Self::parse(&s)?;
+
+        // a single user without a SHA-256 key downgrades the whole realm to MD5
+        let md5_only = users
+            .iter()
+            .filter(|credentials| credentials.key(&SHA256_PASSWORD_ALGORITHM).is_none())
+            .count();
+
+        if md5_only > 0 {
+            log::warn!(
+                "{md5_only} user(s) in `{}` only have an MD5 key, so only MD5 is advertised in their realms",
+                self.path.display()
+            );
+        }
+
+        self.store.replace(users);
+
+        *self.modified.lock().unwrap() = modified;
+
+        Ok(())
+    }
+
+    /// Reloads the file if it was modified since it was last loaded.
+    ///
+    /// Returns whether the file was reloaded.
+    pub fn reload_if_modified(&self) -> Result<bool, CredentialError> {
+        let modified = self.modified_at();
+
+        if modified.is_some() && modified == *self.modified.lock().unwrap() {
+            return Ok(false);
+        }
+
+        self.reload()?;
+
+        Ok(true)
+    }
+
+    fn modified_at(&self) -> Option<SystemTime> {
+        fs::metadata(&self.path).and_then(|m| m.modified()).ok()
+    }
+
+    /// Parses the lines of a credentials file.
+    pub fn parse(s: &str) -> Result<Vec<StoredCredentials>, CredentialError> {
+        let mut users: Vec<StoredCredentials> = vec![];
+
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let bad_line = |reason: &str| CredentialError {
+                ty: CredentialErrorTy::Parse(i + 1),
+                reason: format!("Line {}: {reason}", i + 1),
+            };
+
+            let (user, key) = line
+                .rsplit_once(':')
+                .ok_or_else(|| bad_line("Expected `username:realm:key`."))?;
+            let (username, realm) = user
+                .split_once(':')
+                .ok_or_else(|| bad_line("Expected `username:realm:key`."))?;
+
+            let key = decode_hex(key).ok_or_else(|| bad_line("The key isn't in hex."))?;
+
+            let algorithm = match key.len() {
+                16 => MD5_PASSWORD_ALGORITHM.clone(),
+                32 => SHA256_PASSWORD_ALGORITHM.clone(),
+                _ => return Err(bad_line("The key isn't an MD5 or SHA-256 hash.")),
+            };
+
+            let key = LongTermKey::from_bytes(algorithm, key);
+
+            let prepare = |s: &str| {
+                opaque_string(s).map_err(|err| CredentialError {
+                    reason: format!("Line {}: {err}", i + 1),
+                    ty: CredentialErrorTy::BadString(err),
+                })
+            };
+
+            let username = prepare(username)?;
+            let realm = prepare(realm)?;
+
+            let existing = users
+                .iter_mut()
+                .find(|c| c.username() == username && c.realm() == Some(&realm));
+
+            match existing {
+                Some(StoredCredentials::LongTerm { keys, .. }) => {
+                    if keys.iter().any(|k| k.algorithm() == key.algorithm()) {
+                        return Err(bad_line("The user already has a key of this algorithm."));
+                    }
+
+                    keys.push(key);
+                }
+                _ => users.push(StoredCredentials::LongTerm {
+                    username,
+                    realm,
+                    keys: vec![key],
+                }),
+            }
+        }
+
+        Ok(users)
+    }
+}
+
+#[async_trait::async_trait]
+impl CredentialStore for FileStore {
+    async fn by_username(&self, username: &str, realm: Option<&str>) -> Option<StoredCredentials> {
+        self.store.by_username(username, realm).await
+    }
+
+    async fn by_realm(&self, realm: &str) -> Vec<StoredCredentials> {
+        self.store.by_realm(realm).await
+    }
+
+    async fn by_userhash(&self, userhash: &Userhash, realm: &str) -> Option<StoredCredentials> {
+        self.store.by_userhash(userhash, realm).await
+    }
+
+    async fn algorithms(&self, realm: &str) -> Vec<PasswordAlgorithm> {
+        self.store.algorithms(realm).await
+    }
+}
+
+cfg_if::cfg_if! {
+    if #[cfg(feature = "async_tokio")] {
+        use std::{sync::Arc, time::Duration};
+
+        use tokio::task::JoinHandle;
+
+        impl FileStore {
+            /// Checks the file for changes every `period`, reloading it when it was modified.
+            ///
+            /// Errors are logged and the current credentials are kept until the file is fixed.
+            /// The task stops when every other reference to the store was dropped.
+            pub fn watch(self: Arc<Self>, period: Duration) -> JoinHandle<()> {
+                let store = Arc::downgrade(&self);
+
+                tokio::spawn(async move {
+                    let mut interval = tokio::time::interval(period);
+
+                    loop {
+                        interval.tick().await;
+
+                        let Some(store) = store.upgrade() else {
+                            break;
+                        };
+
+                        match store.reload_if_modified() {
+                            Ok(true) => log::info!("Reloaded credentials from `{}`", store.path.display()),
+                            Ok(false) => (),
+                            Err(err) => log::error!("Failed to reload credentials: {err}"),
+                        }
+                    }
+                })
+            }
+        }
+    }
+}
+
+/// Decodes a key in hex into a buffer that is wiped on drop.
+fn decode_hex(s: &str) -> Option<Zeroizing<Vec<u8>>> {
+    if !s.len().is_multiple_of(2) || !s.is_ascii() {
+        return None;
+    }
+
+    // reserved up front, so the key isn't left behind by reallocations
+    let mut key = Zeroizing::new(Vec::with_capacity(s.len() / 2));
+
+    for i in (0..s.len()).step_by(2) {
+        key.push(u8::from_str_radix(&s[i..(i + 2)], 16).ok()?);
+    }
+
+    Some(key)
+}
+
+#[derive(Debug)]
+pub struct CredentialError {
+    pub ty: CredentialErrorTy,
+    pub reason: String,
+}
+
+impl std::fmt::Display for CredentialError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.reason)
+    }
+}
+
+impl std::error::Error for CredentialError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.ty {
+            CredentialErrorTy::Io(e) => Some(e),
+            CredentialErrorTy::BadString(e) => Some(e),
+            CredentialErrorTy::Parse(_) => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum CredentialErrorTy {
+    /// The credentials file couldn't be read.
+    Io(io::Error),
+    /// The line with this number is malformed.
+    Parse(usize),
+    /// A username or realm is rejected by the OpaqueString profile.
+    BadString(PrecisError),
+}
@@ -0,0 +1,137 @@
+//! Classic STUN compatibility
+//!
+//! https://datatracker.ietf.org/doc/html/rfc3489
+
+use std::net::SocketAddr;
+
+use flashbang::{
+    message::{
+        attributes::*,
+        methods::{Binding, MethodTy},
+        *,
+    },
+    server::binding_response,
+};
+
+const REQUEST: &[u8] = &[
+    0x00, 0x01, 0x00, 0x10, //    Request type and message length
+    0x01, 0x23, 0x45, 0x67, // }
+    0x89, 0xab, 0xcd, 0xef, // }  Transaction ID (128 bits)
+    0x01, 0x23, 0x45, 0x67, // }
+    0x89, 0xab, 0xcd, 0xef, // }
+    0x00, 0x03, 0x00, 0x04, //    CHANGE-REQUEST attribute header
+    0x00, 0x00, 0x00, 0x06, //    Change IP and port
+    0x00, 0x04, 0x00, 0x04, //    SOURCE-ADDRESS attribute header (truncated)
+    0x00, 0x01, 0x0d, 0x96, //    Family and port
+];
+
+const ID: u128 = 0x0123456789abcdef0123456789abcdef;
+
+#[test]
+fn request() {
+    let err = IncomingMessage::decode(REQUEST).unwrap_err();
+    assert!(matches!(err.ty, IncomingErrorTy::BadMagic));
+
+    let message =
+        IncomingMessage::decode_classic(REQUEST, &Registry::default()).expect("Failed to decode");
+
+    assert_eq!(Some(ClassicTransactionId::new(ID)), message.classic);
+    assert!(matches!(message.body.method(), MethodTy::Binding(Binding)));
+
+    // the deprecated attributes are ignored
+    assert!(message.unknown_required.is_empty());
+    assert_eq!(None, message.unknown_attributes());
+}
+
+#[test]
+fn response() {
+    let source: SocketAddr = "192.0.2.1:32853".parse().unwrap();
+
+    let request =
+        IncomingMessage::decode_classic(REQUEST, &Registry::default()).expect("Failed to decode");
+
+    let response = binding_response(&request, source)
+        .expect("Failed to build response")
+        .encode();
+
+    // the full transaction ID is echoed in place of the magic cookie
+    assert_eq!(&REQUEST[4..20], &response[4..20]);
+
+    let view = MessageRef::new_classic(&response).expect("Failed to decode");
+
+    assert_eq!(SUCCESS_RESPONSE_CLASS, view.class());
+    assert_eq!(
+        Some(ClassicTransactionId::new(ID)),
+        view.classic_transaction_id()
+    );
+
+    let mapped = view.get::<MappedAddress>().unwrap().unwrap();
+    assert_eq!(source, mapped.addr());
+    assert!(view.get::<XorMappedAddress>().is_none());
+
+    // modern requests are still answered with XOR-MAPPED-ADDRESS in classic mode
+    let modern = OutgoingMessage {
+        transaction_id: TransactionId::new(0x1234),
+        body: Request {
+            method: Binding,
+            authorization: None,
+        },
+        software: false,
+        fingerprint: false,
+    }
+    .encode();
+
+    let request =
+        IncomingMessage::decode_classic(&modern, &Registry::default()).expect("Failed to decode");
+    assert_eq!(None, request.classic);
+
+    let response = binding_response(&request, source)
+        .expect("Failed to build response")
+        .encode();
+
+    let view = MessageRef::new(&response).expect("Failed to decode");
+
+    let mapped = view.get::<XorMappedAddress>().unwrap().unwrap();
+    assert_eq!(source, mapped.addr());
+}
+
+#[test]
+fn modern_attributes() {
+    let source: SocketAddr = "192.0.2.1:32853".parse().unwrap();
+
+    let request =
+        IncomingMessage::decode_classic(REQUEST, &Registry::default()).expect("Failed to decode");
+
+    let response = || binding_response(&request, source).expect("Failed to build response");
+
+    let err = response().fingerprint().try_encode(None).unwrap_err();
+    assert!(matches!(
+        err.ty,
+        OutgoingErrorTy::NotClassic(Fingerprint::TY)
+    ));
+
+    let err = response()
+        .integrity(b"password", Integrity::Both)
+        .try_encode(None)
+        .unwrap_err();
+    assert!(matches!(
+        err.ty,
+        OutgoingErrorTy::NotClassic(MessageIntegritySha256::TY)
+    ));
+
+    let err = response()
+        .attribute(XorMappedAddress::new(source))
+        .unwrap()
+        .try_encode(None)
+        .unwrap_err();
+    assert!(matches!(
+        err.ty,
+        OutgoingErrorTy::NotClassic(XorMappedAddress::TY)
+    ));
+
+    // MESSAGE-INTEGRITY is part of RFC 3489
+    assert!(response()
+        .integrity(b"password", Integrity::Sha1)
+        .try_encode(None)
+        .is_ok());
+}
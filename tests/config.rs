@@ -0,0 +1,270 @@
+//! Validation of the server configuration
+
+use std::{
+    net::{IpAddr, Ipv4Addr},
+    time::Duration,
+};
+
+use flashbang::server::config::*;
+
+fn listener(transport: Transport) -> ListenerConfig {
+    ListenerConfig::new(transport, IpAddr::V4(Ipv4Addr::UNSPECIFIED))
+}
+
+fn tls() -> Option<TlsConfig> {
+    Some(TlsConfig {
+        certificate: "cert.pem".into(),
+        key: "key.pem".into(),
+    })
+}
+
+#[test]
+fn validate() {
+    ServerConfig::default().validate().unwrap();
+
+    let config = ServerConfig {
+        listeners: vec![listener(Transport::Udp), listener(Transport::Tcp)],
+        auth: AuthConfig {
+            realm: Some("example.org".into()),
+            credentials: Some("users.txt".into()),
+        },
+        ..Default::default()
+    };
+    config.validate().unwrap();
+
+    let cases = [
+        (
+            ServerConfig {
+                listeners: vec![],
+                ..Default::default()
+            },
+            "NoListeners",
+        ),
+        (
+            ServerConfig {
+                listeners: vec![listener(Transport::Udp), listener(Transport::Udp)],
+                ..Default::default()
+            },
+            "DuplicateListener",
+        ),
+        (
+            ServerConfig {
+                listeners: vec![listener(Transport::Dtls)],
+                ..Default::default()
+            },
+            "MissingCertificate",
+        ),
+        (
+            ServerConfig {
+                tls: tls(),
+                ..Default::default()
+            },
+            "UnusedCertificate",
+        ),
+        (
+            ServerConfig {
+                listeners: vec![listener(Transport::Udp), listener(Transport::Tls)],
+                tls: tls(),
+                ..Default::default()
+            },
+            "UnsupportedTransport",
+        ),
+        (
+            ServerConfig {
+                auth: AuthConfig {
+                    realm: None,
+                    credentials: Some("users.txt".into()),
+                },
+                ..Default::default()
+            },
+            "MissingRealm",
+        ),
+        (
+            ServerConfig {
+                auth: AuthConfig {
+                    realm: Some("bad\u{0007}realm".into()),
+                    credentials: None,
+                },
+                ..Default::default()
+            },
+            "BadRealm",
+        ),
+        (
+            ServerConfig {
+                turn: TurnConfig {
+                    max_lifetime: Duration::from_secs(60),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            "BadTurnLimit",
+        ),
+    ];
+
+    for (config, expected) in cases {
+        let err = config.validate().unwrap_err();
+        assert_eq!(expected, format!("{:?}", err.ty), "{err}");
+    }
+}
+
+#[test]
+fn parse() {
+    assert_eq!(Ok(Transport::Dtls), "dtls".parse());
+    assert!("quic".parse::<Transport>().is_err());
+
+    assert_eq!(Ok(LogFormat::Json), "json".parse());
+    assert!("yaml".parse::<LogFormat>().is_err());
+}
+
+#[cfg(feature = "toml_config")]
+#[test]
+fn from_toml() {
+    assert_eq!(
+        ServerConfig::default(),
+        ServerConfig::from_toml("").unwrap()
+    );
+
+    let config = ServerConfig::from_toml(
+        r#"
+        classic = true
+
+        [[listeners]]
+        transport = "udp"
+        address = "127.0.0.1"
+        port = 3479
+
+        [[listeners]]
+        transport = "tcp"
+
+        [listeners.options]
+        reuse_address = false
+        backlog = 64
+
+        [auth]
+        realm = "example.org"
+        credentials = "users.txt"
+
+        [turn]
+        max_allocations = 100
+        max_lifetime = 1800
+
+        [log]
+        level = "debug"
+        format = "json"
+        "#,
+    )
+    .unwrap();
+
+    let udp = ListenerConfig {
+        port: 3479,
+        ..listener(Transport::Udp)
+    };
+    let mut tcp = ListenerConfig::new(Transport::Tcp, "::".parse().unwrap());
+    tcp.options.reuse_address = false;
+    tcp.options.backlog = Some(64);
+
+    let expected = ServerConfig {
+        classic: true,
+        listeners: vec![
+            ListenerConfig {
+                address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+                ..udp
+            },
+            tcp,
+        ],
+        auth: AuthConfig {
+            realm: Some("example.org".into()),
+            credentials: Some("users.txt".into()),
+        },
+        tls: None,
+        turn: TurnConfig {
+            max_allocations: Some(100),
+            max_allocations_per_user: None,
+            max_lifetime: Duration::from_secs(1800),
+        },
+        log: LogConfig {
+            level: log::LevelFilter::Debug,
+            format: LogFormat::Json,
+        },
+    };
+    assert_eq!(expected, config);
+
+    let cases = [
+        ("classic = 1", "Parse"),
+        ("unknown = true", "Parse"),
+        ("[[listeners]]\ntransport = \"quic\"", "Parse"),
+        ("[[listeners]]\naddress = \"::\"", "Parse"),
+        ("[log]\nlevel = \"loud\"", "Parse"),
+        ("listeners = []", "NoListeners"),
+        ("[auth]\ncredentials = \"users.txt\"", "MissingRealm"),
+        (
+            "[[listeners]]\ntransport = \"dtls\"\n[tls]\ncertificate = \"cert.pem\"\nkey = \"key.pem\"",
+            "UnsupportedTransport",
+        ),
+    ];
+
+    for (s, expected) in cases {
+        let err = ServerConfig::from_toml(s).unwrap_err();
+        assert_eq!(expected, format!("{:?}", err.ty), "{err}");
+    }
+}
+
+#[cfg(feature = "toml_config")]
+#[test]
+fn load() {
+    let dir = std::env::temp_dir().join(format!("flashbang-config-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let path = dir.join("server.toml");
+    std::fs::write(
+        &path,
+        "[auth]\nrealm = \"example.org\"\ncredentials = \"users.txt\"\n",
+    )
+    .unwrap();
+
+    let config = ServerConfig::load(&path).unwrap();
+
+    // relative paths are resolved against the directory of the file
+    assert_eq!(Some(dir.join("users.txt")), config.auth.credentials);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let err = ServerConfig::load(&path).unwrap_err();
+    assert!(matches!(err.ty, ConfigErrorTy::Io(_)), "{err}");
+}
+
+#[test]
+fn reload() {
+    let handle = ConfigHandle::new(ServerConfig::default());
+
+    let pending = handle
+        .reload(ServerConfig {
+            classic: true,
+            ..Default::default()
+        })
+        .unwrap();
+    assert!(pending.is_empty());
+    assert!(handle.get().classic);
+
+    // changes that need a restart are reported, but still stored
+    let pending = handle
+        .reload(ServerConfig {
+            log: LogConfig {
+                format: LogFormat::Json,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .unwrap();
+    assert_eq!(1, pending.len());
+    assert_eq!(LogFormat::Json, handle.get().log.format);
+
+    // invalid configurations keep the current one
+    assert!(handle
+        .reload(ServerConfig {
+            listeners: vec![],
+            ..Default::default()
+        })
+        .is_err());
+    assert_eq!(LogFormat::Json, handle.get().log.format);
+}
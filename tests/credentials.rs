@@ -0,0 +1,265 @@
+//! Looking up the credentials of users
+
+use std::{sync::Arc, time::Duration};
+
+use flashbang::{
+    message::{attributes::*, *},
+    user::*,
+};
+
+fn hex(key: &[u8]) -> String {
+    key.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[tokio::test]
+async fn memory() {
+    let store = MemoryStore::new();
+
+    store.insert(StoredCredentials::long_term("alice", "example.org", "password").unwrap());
+    store.insert(StoredCredentials::long_term("bob", "example.org", "hunter2").unwrap());
+    store.insert(StoredCredentials::long_term("alice", "example.com", "secret").unwrap());
+    store.insert(StoredCredentials::short_term("alice", "short").unwrap());
+    assert_eq!(4, store.len());
+
+    let alice = store
+        .by_username("alice", Some("example.org"))
+        .await
+        .unwrap();
+    assert_eq!("alice", alice.username());
+    assert_eq!(Some("example.org"), alice.realm());
+
+    for algorithm in [&*MD5_PASSWORD_ALGORITHM, &*SHA256_PASSWORD_ALGORITHM] {
+        let key = LongTermKey::derive("alice", "example.org", "password", algorithm).unwrap();
+        assert_eq!(Some(key.as_bytes()), alice.key(algorithm));
+    }
+
+    let short = store.by_username("alice", None).await.unwrap();
+    assert_eq!(None, short.realm());
+    assert_eq!(
+        Some(b"short".as_slice()),
+        short.key(&MD5_PASSWORD_ALGORITHM)
+    );
+
+    assert_eq!(None, store.by_username("carol", Some("example.org")).await);
+    assert_eq!(None, store.by_username("bob", Some("example.com")).await);
+
+    let mut realm: Vec<_> = store
+        .by_realm("example.org")
+        .await
+        .iter()
+        .map(|c| c.username().to_string())
+        .collect();
+    realm.sort();
+    assert_eq!(vec!["alice", "bob"], realm);
+
+    let userhash = Userhash::new(Username::new("bob"), Realm::new("example.org")).unwrap();
+    let bob = store.by_userhash(&userhash, "example.org").await.unwrap();
+    assert_eq!("bob", bob.username());
+    assert_eq!(None, store.by_userhash(&userhash, "example.com").await);
+
+    assert!(store.remove("bob", Some("example.org")).is_some());
+    assert_eq!(None, store.by_userhash(&userhash, "example.org").await);
+
+    // every user of the realm has keys for both algorithms
+    let algorithms = store.algorithms("example.org").await;
+    assert!(algorithms.contains(&SHA256_PASSWORD_ALGORITHM));
+    assert!(algorithms.contains(&MD5_PASSWORD_ALGORITHM));
+    assert!(store.algorithms("example.net").await.is_empty());
+
+    // a user with only an MD5 key restricts the realm to MD5
+    let md5 =
+        LongTermKey::derive("carol", "example.org", "password", &MD5_PASSWORD_ALGORITHM).unwrap();
+    store.insert(StoredCredentials::LongTerm {
+        username: "carol".into(),
+        realm: "example.org".into(),
+        keys: vec![md5],
+    });
+    assert_eq!(
+        vec![MD5_PASSWORD_ALGORITHM.clone()],
+        store.algorithms("example.org").await
+    );
+
+    store.remove("carol", Some("example.org"));
+    assert_eq!(2, store.algorithms("example.org").await.len());
+
+    // the keys aren't leaked to logs
+    assert_eq!(
+        r#"LongTerm { username: "alice", realm: "example.org" }"#,
+        format!("{alice:?}")
+    );
+}
+
+#[tokio::test]
+async fn userhash_index() {
+    let alice = StoredCredentials::long_term("alice", "example.org", "password").unwrap();
+    let userhash = Userhash::new(Username::new("alice"), Realm::new("example.org")).unwrap();
+    assert_eq!(Some(userhash.clone()), alice.userhash());
+
+    let index: UserhashIndex = [
+        &alice,
+        &StoredCredentials::short_term("bob", "short").unwrap(),
+    ]
+    .into_iter()
+    .collect();
+    assert_eq!(1, index.len());
+    assert_eq!(Some("alice"), index.get(&userhash, "example.org"));
+    assert_eq!(None, index.get(&userhash, "example.com"));
+
+    // the index is rebuilt when the users and their realms change
+    let store = MemoryStore::new();
+    store.insert(alice);
+    assert!(store.by_userhash(&userhash, "example.org").await.is_some());
+
+    store.replace([StoredCredentials::long_term("alice", "example.com", "password").unwrap()]);
+    assert_eq!(None, store.by_userhash(&userhash, "example.org").await);
+
+    let moved = Userhash::new(Username::new("alice"), Realm::new("example.com")).unwrap();
+    let credentials = store.by_userhash(&moved, "example.com").await.unwrap();
+    assert_eq!(Some("example.com"), credentials.realm());
+
+    // the index matches the default lookup of other stores
+    let file = FileStore::parse(&format!(
+        "alice:example.com:{}",
+        *LongTermKey::derive("alice", "example.com", "password", &MD5_PASSWORD_ALGORITHM)
+            .unwrap()
+            .to_hex()
+    ))
+    .unwrap();
+    assert_eq!(Some(moved), file[0].userhash());
+}
+
+#[test]
+fn parse() {
+    let md5 =
+        LongTermKey::derive("alice", "example.org", "password", &MD5_PASSWORD_ALGORITHM).unwrap();
+    let sha256 = LongTermKey::derive(
+        "alice",
+        "example.org",
+        "password",
+        &SHA256_PASSWORD_ALGORITHM,
+    )
+    .unwrap();
+
+    let file = format!(
+        "# username:realm:key\n\nalice:example.org:{}\nalice:example.org:{}\nbob:realm:with:colons:{}\n",
+        *md5.to_hex(),
+        *sha256.to_hex(),
+        *md5.to_hex(),
+    );
+
+    let users = FileStore::parse(&file).unwrap();
+    assert_eq!(2, users.len());
+
+    assert_eq!(Some(md5.as_bytes()), users[0].key(&MD5_PASSWORD_ALGORITHM));
+    assert_eq!(
+        Some(sha256.as_bytes()),
+        users[0].key(&SHA256_PASSWORD_ALGORITHM)
+    );
+    assert_eq!(Some("realm:with:colons"), users[1].realm());
+    assert_eq!(None, users[1].key(&SHA256_PASSWORD_ALGORITHM));
+
+    let cases = [
+        "alice".to_string(),
+        "alice:example.org".to_string(),
+        "alice:example.org:xyz".to_string(),
+        "alice:example.org:abcd".to_string(),
+        format!("alice:bad\u{0007}realm:{}", *md5.to_hex()),
+        format!(
+            "alice:example.org:{0}\nalice:example.org:{0}",
+            *md5.to_hex()
+        ),
+    ];
+
+    for case in cases {
+        assert!(FileStore::parse(&case).is_err(), "{case}");
+    }
+
+    let err = FileStore::parse("# comment\nalice").unwrap_err();
+    assert!(matches!(err.ty, CredentialErrorTy::Parse(2)), "{err}");
+}
+
+#[test]
+fn long_term_key() {
+    let key = LongTermKey::derive(
+        "alice",
+        "example.org",
+        "password",
+        &SHA256_PASSWORD_ALGORITHM,
+    )
+    .unwrap();
+
+    let expected = StoredCredentials::long_term("alice", "example.org", "password").unwrap();
+    let expected = expected.key(&SHA256_PASSWORD_ALGORITHM).unwrap();
+
+    assert_eq!(expected, key.as_bytes());
+    assert_eq!(hex(expected), *key.to_hex());
+
+    // the key round-trips through a credentials file
+    let users = FileStore::parse(&format!("alice:example.org:{}", *key.to_hex())).unwrap();
+    assert_eq!(
+        Some(key.as_bytes()),
+        users[0].key(&SHA256_PASSWORD_ALGORITHM)
+    );
+
+    // the key isn't leaked to logs
+    assert!(!format!("{key:?}").contains(key.to_hex().as_str()));
+}
+
+#[tokio::test]
+async fn file() {
+    let line = |user: &str, password: &str| {
+        let key =
+            LongTermKey::derive(user, "example.org", password, &MD5_PASSWORD_ALGORITHM).unwrap();
+
+        format!("{user}:example.org:{}\n", *key.to_hex())
+    };
+
+    let path = std::env::temp_dir().join(format!("flashbang-users-{}.txt", std::process::id()));
+    std::fs::write(&path, line("alice", "password")).unwrap();
+
+    let store = Arc::new(FileStore::load(&path).unwrap());
+    assert!(store
+        .by_username("alice", Some("example.org"))
+        .await
+        .is_some());
+
+    let watcher = store.clone().watch(Duration::from_millis(50));
+
+    // the file is reloaded once it was modified
+    std::fs::write(&path, line("bob", "password")).unwrap();
+
+    let mut reloaded = false;
+
+    for _ in 0..40 {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        if store
+            .by_username("bob", Some("example.org"))
+            .await
+            .is_some()
+        {
+            reloaded = true;
+            break;
+        }
+    }
+
+    assert!(reloaded, "The file wasn't reloaded");
+    assert_eq!(None, store.by_username("alice", Some("example.org")).await);
+
+    // a broken file keeps the current credentials
+    std::fs::write(&path, "bob").unwrap();
+    assert!(store.reload().is_err());
+    assert!(store
+        .by_username("bob", Some("example.org"))
+        .await
+        .is_some());
+
+    std::fs::remove_file(&path).unwrap();
+
+    // the watcher stops with the store
+    drop(store);
+    tokio::time::timeout(Duration::from_secs(1), watcher)
+        .await
+        .expect("The watcher didn't stop")
+        .unwrap();
+}
@@ -0,0 +1,129 @@
+//! Vendor-specific attributes and private methods
+//!
+//! Based off the GOOG-NETWORK-INFO attribute used by WebRTC,
+//! and a made-up probe method.
+
+use flashbang::message::{
+    attributes::{encode_attribute, AttributeError, AttributeErrorTy, CustomAttribute},
+    methods::CustomMethod,
+    AttributeMeta, IncomingErrorTy, IncomingMessage, MessageMeta, MessageRef, OutgoingMessage,
+    Registry, Request, TransactionId,
+};
+
+#[derive(Debug, PartialEq)]
+struct NetworkInfo {
+    network_id: u16,
+    network_cost: u16,
+}
+
+impl CustomAttribute for NetworkInfo {
+    const TY: u16 = 0xC057;
+    const SIZE: usize = 4;
+
+    fn encode(&self, buf: &mut [u8], offset: usize) {
+        buf[offset..(offset + 2)].copy_from_slice(&self.network_id.to_be_bytes());
+        buf[(offset + 2)..(offset + 4)].copy_from_slice(&self.network_cost.to_be_bytes());
+    }
+
+    fn decode(buf: &[u8], meta: &AttributeMeta) -> Result<Self, AttributeError> {
+        if meta.len != Self::SIZE {
+            return Err(AttributeError {
+                attribute: Self::TY,
+                ty: AttributeErrorTy::BadLength(meta.len),
+            });
+        }
+
+        Ok(Self {
+            network_id: u16::from_be_bytes([buf[meta.offset], buf[meta.offset + 1]]),
+            network_cost: u16::from_be_bytes([buf[meta.offset + 2], buf[meta.offset + 3]]),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Probe;
+
+impl CustomMethod for Probe {
+    const METHOD: u16 = 0x0FF;
+
+    fn encode(&self, buf: &mut [u8], offset: &mut usize) {
+        let info = NetworkInfo {
+            network_id: 7,
+            network_cost: 10,
+        };
+
+        encode_attribute(&info, buf, offset);
+    }
+
+    fn decode(_meta: &MessageMeta) -> Self {
+        Self
+    }
+
+    fn size(&self) -> usize {
+        4 + NetworkInfo::SIZE
+    }
+}
+
+fn probe() -> OutgoingMessage<Request<Probe>> {
+    OutgoingMessage {
+        transaction_id: TransactionId::new(0xC057),
+        body: Request {
+            method: Probe,
+            authorization: None,
+        },
+        software: false,
+        fingerprint: true,
+    }
+}
+
+#[test]
+fn registered() {
+    let buf = probe().encode();
+
+    let mut registry = Registry::new();
+    registry
+        .register_attribute::<NetworkInfo>()
+        .register_method::<Probe>();
+
+    let output = IncomingMessage::decode_with(&buf, &registry).expect("Failed to decode message");
+
+    assert_eq!(Probe::METHOD, output.body.method().method());
+
+    let response = output
+        .success_response::<Probe>()
+        .expect("Method should be a probe");
+
+    assert_eq!(output.transaction_id, response.transaction_id);
+
+    let info = output.custom[0]
+        .downcast_ref::<NetworkInfo>()
+        .expect("Attribute should be decoded");
+
+    assert_eq!(
+        &NetworkInfo {
+            network_id: 7,
+            network_cost: 10
+        },
+        info
+    );
+    assert!(output.unknown_optional.is_empty());
+
+    // custom attributes are encoded again
+    assert_eq!(buf, output.to_builder().encode());
+
+    let view = MessageRef::new(&buf).expect("Failed to validate message");
+
+    assert_eq!(
+        Some(Ok(info)),
+        view.get::<NetworkInfo>().as_ref().map(|r| r.as_ref())
+    );
+}
+
+#[test]
+fn unregistered() {
+    let buf = probe().encode();
+
+    let err = IncomingMessage::decode(&buf).expect_err("Method should be unknown");
+
+    assert!(matches!(err.ty, IncomingErrorTy::UnknownMethod));
+}
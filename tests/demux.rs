@@ -0,0 +1,107 @@
+//! Demultiplexing of STUN, DTLS, RTP/RTCP and ChannelData on one socket
+//!
+//! https://datatracker.ietf.org/doc/html/rfc7983#section-7
+
+use std::time::Duration;
+
+use flashbang::message::{
+    demux::{classify, DemuxSocket, PacketClass},
+    methods::Binding,
+    *,
+};
+use tokio::{net::UdpSocket, time::timeout};
+
+fn stun() -> Vec<u8> {
+    let message = OutgoingMessage {
+        transaction_id: TransactionId::new(0x9abc),
+        body: Request {
+            method: Binding,
+            authorization: None,
+        },
+        software: false,
+        fingerprint: true,
+    };
+
+    message.encode().to_vec()
+}
+
+// a DTLS 1.2 ClientHello record header
+const DTLS: &[u8] = &[0x16, 0xfe, 0xfd, 0x00, 0x00, 0x00, 0x00];
+
+// an RTP header with payload type 111
+const RTP: &[u8] = &[
+    0x80, 0x6f, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x12, 0x34, 0x56, 0x78,
+];
+
+// ChannelData on channel 0x4000 with 3 bytes of data, unpadded
+const CHANNEL_DATA: &[u8] = &[0x40, 0x00, 0x00, 0x03, 0x01, 0x02, 0x03];
+
+#[test]
+fn classes() {
+    let mut stun = stun();
+
+    assert_eq!(PacketClass::Stun, classify(&stun));
+    assert_eq!(PacketClass::Dtls, classify(DTLS));
+    assert_eq!(PacketClass::Rtp, classify(RTP));
+    assert_eq!(PacketClass::ChannelData, classify(CHANNEL_DATA));
+    assert_eq!(PacketClass::Zrtp, classify(&[0x10, 0x00]));
+    assert_eq!(PacketClass::Unknown, classify(&[]));
+    assert_eq!(PacketClass::Unknown, classify(&[0xff]));
+
+    // ChannelData that's cut off
+    assert_eq!(PacketClass::Unknown, classify(&CHANNEL_DATA[..6]));
+
+    // a corrupt fingerprint disqualifies STUN
+    let last = stun.len() - 1;
+    stun[last] ^= 0xff;
+    assert_eq!(PacketClass::Unknown, classify(&stun));
+
+    // as does a missing magic cookie
+    let mut stun = self::stun();
+    stun[4] = 0;
+    assert_eq!(PacketClass::Unknown, classify(&stun));
+}
+
+#[tokio::test]
+async fn socket() {
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let addr = socket.local_addr().unwrap();
+
+    let (demux, mut receivers) = DemuxSocket::new(socket, 8);
+
+    let peer = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let peer_addr = peer.local_addr().unwrap();
+
+    let stun = stun();
+
+    for packet in [&stun[..], DTLS, RTP, CHANNEL_DATA] {
+        peer.send_to(packet, addr).await.unwrap();
+    }
+
+    let wait = Duration::from_secs(5);
+
+    let (packet, from) = timeout(wait, receivers.stun.recv()).await.unwrap().unwrap();
+    assert_eq!((&stun[..], peer_addr), (&packet[..], from));
+
+    let (packet, _) = timeout(wait, receivers.dtls.recv()).await.unwrap().unwrap();
+    assert_eq!(DTLS, &packet[..]);
+
+    let (packet, _) = timeout(wait, receivers.rtp.recv()).await.unwrap().unwrap();
+    assert_eq!(RTP, &packet[..]);
+
+    let (packet, _) = timeout(wait, receivers.channel_data.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(CHANNEL_DATA, &packet[..]);
+
+    // replies go out through the wrapped socket
+    demux.send_to(RTP, peer_addr).await.unwrap();
+
+    let mut buf = [0u8; 64];
+    let (size, from) = timeout(wait, peer.recv_from(&mut buf))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!((RTP, addr), (&buf[..size], from));
+}
@@ -0,0 +1,142 @@
+//! Framing of STUN and ChannelData messages on byte streams
+//!
+//! Frames may be split across or coalesced into TCP segments.
+
+use bytes::{Bytes, BytesMut};
+use flashbang::message::{
+    methods::{Binding, Method},
+    *,
+};
+use tokio_util::codec::{Decoder, Encoder};
+
+fn frames() -> Vec<Frame> {
+    let message = OutgoingMessage {
+        transaction_id: TransactionId::new(0x5678),
+        body: Request {
+            method: Binding,
+            authorization: None,
+        },
+        software: true,
+        fingerprint: true,
+    };
+
+    vec![
+        Frame::Message(message.encode()),
+        Frame::ChannelData {
+            channel: 0x4001,
+            data: Bytes::from_static(b"hello"),
+        },
+        Frame::Message(message.encode()),
+    ]
+}
+
+fn stream(frames: &[Frame]) -> Vec<u8> {
+    let mut buf = vec![];
+
+    for frame in frames {
+        frame.encode(&mut buf).expect("Failed to encode");
+    }
+
+    buf
+}
+
+#[test]
+fn parser() {
+    let frames = frames();
+    let stream = stream(&frames);
+
+    // ChannelData is padded to a multiple of 4 bytes
+    assert_eq!(12, frames[1].size());
+    assert_eq!(frames.iter().map(Frame::size).sum::<usize>(), stream.len());
+
+    // every possible segment size, from byte-by-byte to the whole stream at once
+    for segment in 1..=stream.len() {
+        let mut parser = FrameParser::new();
+        let mut output = vec![];
+
+        for chunk in stream.chunks(segment) {
+            parser.push(chunk);
+
+            while let Some(frame) = parser.next_frame().expect("Failed to parse") {
+                output.push(frame);
+            }
+        }
+
+        assert_eq!(frames, output);
+        assert_eq!(0, parser.buffered());
+    }
+}
+
+#[test]
+fn invalid() {
+    let mut parser = FrameParser::new();
+
+    parser.push(&[0x80, 0x00, 0x00, 0x00]);
+    assert!(matches!(
+        parser.next_frame().unwrap_err().ty,
+        FramingErrorTy::UnknownFrame
+    ));
+
+    let mut parser = FrameParser::new();
+
+    parser.push(&[0x00, 0x01, 0x00, 0x03]);
+    assert!(matches!(
+        parser.next_frame().unwrap_err().ty,
+        FramingErrorTy::BadLength
+    ));
+
+    let frame = Frame::ChannelData {
+        channel: 0x8000,
+        data: Bytes::new(),
+    };
+    assert!(matches!(
+        frame.encode(&mut vec![]).unwrap_err().ty,
+        FramingErrorTy::BadChannel
+    ));
+
+    // messages that the peer would frame differently aren't written
+    let message = MessageBuilder::request(Binding::METHOD).encode();
+    let mut channel = message.to_vec();
+    channel[0] = 0x40;
+
+    let cases = [
+        Bytes::from_static(&[0x00, 0x01]),
+        message.slice(..(message.len() - 4)),
+        Bytes::from(channel),
+    ];
+
+    for case in cases {
+        let mut buf = vec![];
+        assert!(matches!(
+            Frame::Message(case).encode(&mut buf).unwrap_err().ty,
+            FramingErrorTy::BadLength
+        ));
+        assert!(buf.is_empty());
+    }
+}
+
+#[test]
+fn codec() {
+    let frames = frames();
+    let mut codec = FrameCodec;
+    let mut buf = BytesMut::new();
+
+    for frame in frames.clone() {
+        codec.encode(frame, &mut buf).expect("Failed to encode");
+    }
+
+    assert_eq!(stream(&frames), buf);
+
+    // feed a partial frame first
+    let mut rest = buf.split_off(7);
+    assert_eq!(None, codec.decode(&mut buf).expect("Failed to decode"));
+
+    buf.unsplit(rest.split());
+
+    let mut output = vec![];
+    while let Some(frame) = codec.decode(&mut buf).expect("Failed to decode") {
+        output.push(frame);
+    }
+
+    assert_eq!(frames, output);
+}
@@ -0,0 +1,410 @@
+//! Listeners of the server
+
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream, UdpSocket},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use flashbang::{
+    message::{attributes::*, methods::Binding, *},
+    server::{
+        config::{ConfigHandle, ListenerConfig, ServerConfig, SocketOptions, Transport},
+        handle_message,
+        runtime::{tokio_server::TokioServerRuntime, ServerRunner, ServerRuntime},
+    },
+};
+
+fn binding_request() -> Vec<u8> {
+    let message = OutgoingMessage {
+        transaction_id: TransactionId::new(0x1234),
+        body: Request {
+            method: Binding,
+            authorization: None,
+        },
+        software: false,
+        fingerprint: false,
+    };
+
+    message.encode().to_vec()
+}
+
+fn mapped_address(response: &[u8]) -> SocketAddr {
+    let view = MessageRef::new(response).expect("Failed to decode");
+
+    view.get::<XorMappedAddress>().unwrap().unwrap().addr()
+}
+
+#[test]
+fn config() {
+    let listener = ListenerConfig::new(Transport::Tls, IpAddr::V6(Ipv6Addr::UNSPECIFIED));
+    assert_eq!(5349, listener.port);
+    assert_eq!("tls/[::]:5349", listener.to_string());
+
+    let config = ServerConfig::default();
+    assert_eq!(
+        vec![Transport::Udp, Transport::Tcp],
+        config
+            .listeners
+            .iter()
+            .map(|l| l.transport)
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn bind() {
+    let listener = ListenerConfig {
+        port: 0,
+        dual_stack: false,
+        options: SocketOptions {
+            recv_buffer_size: Some(1 << 16),
+            ttl: Some(32),
+            ..Default::default()
+        },
+        ..ListenerConfig::new(Transport::Udp, IpAddr::V6(Ipv6Addr::LOCALHOST))
+    };
+
+    let socket = listener.bind().expect("Failed to bind");
+
+    assert!(socket.only_v6().unwrap());
+    assert!(socket.recv_buffer_size().unwrap() >= 1 << 16);
+    assert_eq!(32, socket.unicast_hops_v6().unwrap());
+
+    let listener = ListenerConfig {
+        port: 0,
+        ..ListenerConfig::new(Transport::Tcp, IpAddr::V4(Ipv4Addr::LOCALHOST))
+    };
+
+    let socket = listener.bind().expect("Failed to bind");
+    let addr = socket.local_addr().unwrap().as_socket().unwrap();
+
+    TcpStream::connect(addr).expect("Failed to connect");
+}
+
+#[test]
+fn dual_stack() {
+    let listener = ListenerConfig {
+        port: 0,
+        ..ListenerConfig::new(Transport::Udp, IpAddr::V6(Ipv6Addr::UNSPECIFIED))
+    };
+
+    let socket: UdpSocket = listener.bind().expect("Failed to bind").into();
+    socket.set_nonblocking(false).unwrap();
+    socket
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+
+    let port = socket.local_addr().unwrap().port();
+
+    let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+    client
+        .send_to(&binding_request(), ("127.0.0.1", port))
+        .unwrap();
+
+    let mut buf = [0u8; 1500];
+    let (size, source) = socket.recv_from(&mut buf).unwrap();
+
+    // IPv4 clients appear as IPv4-mapped IPv6 addresses
+    assert!(matches!(source.ip(), IpAddr::V6(ip) if ip.to_ipv4_mapped().is_some()));
+
+    let response = futures::executor::block_on(handle_message(
+        &ServerConfig::default(),
+        None,
+        &buf[..size],
+        source,
+    ))
+    .unwrap();
+
+    assert_eq!(client.local_addr().unwrap(), mapped_address(&response));
+}
+
+#[tokio::test]
+async fn runtime() {
+    // find free ports for the listeners
+    let udp_port = UdpSocket::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let tcp_port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+
+    let addr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+    let running = Arc::new(AtomicBool::new(true));
+
+    let runner = ServerRunner {
+        running: running.clone(),
+        config: ServerConfig {
+            listeners: vec![
+                ListenerConfig {
+                    port: udp_port,
+                    ..ListenerConfig::new(Transport::Udp, addr)
+                },
+                ListenerConfig {
+                    port: tcp_port,
+                    ..ListenerConfig::new(Transport::Tcp, addr)
+                },
+                // unsupported transports are skipped
+                ListenerConfig::new(Transport::Dtls, addr),
+            ],
+            ..Default::default()
+        }
+        .into(),
+        auth: None,
+    };
+
+    let server = tokio::spawn(TokioServerRuntime::run(runner));
+
+    let response = tokio::task::spawn_blocking(move || {
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_millis(200)))
+            .unwrap();
+
+        let mut buf = [0u8; 1500];
+
+        // the listener may not have started yet
+        for _ in 0..25 {
+            client
+                .send_to(&binding_request(), ("127.0.0.1", udp_port))
+                .unwrap();
+
+            if let Ok(size) = client.recv(&mut buf) {
+                assert_eq!(client.local_addr().unwrap(), mapped_address(&buf[..size]));
+                return true;
+            }
+        }
+
+        false
+    })
+    .await
+    .unwrap();
+
+    assert!(response, "No response over UDP");
+
+    let response = tokio::task::spawn_blocking(move || {
+        use std::io::{Read, Write};
+
+        // the listener may not have started yet
+        let mut stream = (0..25)
+            .find_map(|_| {
+                TcpStream::connect(("127.0.0.1", tcp_port))
+                    .map_err(|_| std::thread::sleep(Duration::from_millis(200)))
+                    .ok()
+            })
+            .unwrap();
+        stream.write_all(&binding_request()).unwrap();
+
+        let mut buf = [0u8; 32];
+        stream.read_exact(&mut buf).unwrap();
+
+        assert_eq!(stream.local_addr().unwrap(), mapped_address(&buf));
+    })
+    .await;
+
+    assert!(response.is_ok(), "No response over TCP");
+
+    running.store(false, Ordering::Relaxed);
+
+    tokio::time::timeout(Duration::from_secs(5), server)
+        .await
+        .expect("Server didn't stop")
+        .unwrap();
+}
+
+fn free_udp_port() -> u16 {
+    UdpSocket::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+/// Sends Binding requests to `port` until one is answered or `attempts` run out.
+async fn answers(port: u16, attempts: usize) -> bool {
+    tokio::task::spawn_blocking(move || {
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_millis(200)))
+            .unwrap();
+
+        let mut buf = [0u8; 1500];
+
+        (0..attempts).any(|_| {
+            client
+                .send_to(&binding_request(), ("127.0.0.1", port))
+                .unwrap();
+
+            client.recv(&mut buf).is_ok()
+        })
+    })
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn reload() {
+    let first_port = free_udp_port();
+    let second_port = free_udp_port();
+
+    let listener = |port| ListenerConfig {
+        port,
+        ..ListenerConfig::new(Transport::Udp, IpAddr::V4(Ipv4Addr::LOCALHOST))
+    };
+
+    let handle = ConfigHandle::new(ServerConfig {
+        listeners: vec![listener(first_port)],
+        ..Default::default()
+    });
+
+    let running = Arc::new(AtomicBool::new(true));
+
+    let runner = ServerRunner {
+        running: running.clone(),
+        config: handle.clone(),
+        auth: None,
+    };
+
+    let server = tokio::spawn(TokioServerRuntime::run(runner));
+
+    assert!(answers(first_port, 25).await, "First listener didn't start");
+
+    // added listeners are started
+    let pending = handle
+        .reload(ServerConfig {
+            listeners: vec![listener(first_port), listener(second_port)],
+            ..Default::default()
+        })
+        .unwrap();
+    assert!(pending.is_empty());
+
+    assert!(
+        answers(second_port, 25).await,
+        "Second listener didn't start"
+    );
+
+    // removed listeners are stopped
+    handle
+        .reload(ServerConfig {
+            listeners: vec![listener(second_port)],
+            ..Default::default()
+        })
+        .unwrap();
+
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    assert!(!answers(first_port, 2).await, "First listener didn't stop");
+    assert!(answers(second_port, 2).await, "Second listener stopped");
+
+    // invalid configurations are rejected and the current one is kept
+    assert!(handle
+        .reload(ServerConfig {
+            listeners: vec![],
+            ..Default::default()
+        })
+        .is_err());
+    assert_eq!(handle.get().listeners, vec![listener(second_port)]);
+
+    running.store(false, Ordering::Relaxed);
+
+    tokio::time::timeout(Duration::from_secs(5), server)
+        .await
+        .expect("Server didn't stop")
+        .unwrap();
+}
+
+#[tokio::test]
+async fn reload_tcp() {
+    let udp_port = free_udp_port();
+    let tcp_port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+
+    let addr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+    let udp = ListenerConfig {
+        port: udp_port,
+        ..ListenerConfig::new(Transport::Udp, addr)
+    };
+    let tcp = ListenerConfig {
+        port: tcp_port,
+        ..ListenerConfig::new(Transport::Tcp, addr)
+    };
+
+    let handle = ConfigHandle::new(ServerConfig {
+        listeners: vec![udp.clone(), tcp],
+        ..Default::default()
+    });
+
+    let running = Arc::new(AtomicBool::new(true));
+
+    let runner = ServerRunner {
+        running: running.clone(),
+        config: handle.clone(),
+        auth: None,
+    };
+
+    let server = tokio::spawn(TokioServerRuntime::run(runner));
+
+    let mut stream = tokio::task::spawn_blocking(move || {
+        use std::io::{Read, Write};
+
+        // the listener may not have started yet
+        let mut stream = (0..25)
+            .find_map(|_| {
+                TcpStream::connect(("127.0.0.1", tcp_port))
+                    .map_err(|_| std::thread::sleep(Duration::from_millis(200)))
+                    .ok()
+            })
+            .unwrap();
+        stream.write_all(&binding_request()).unwrap();
+
+        let mut buf = [0u8; 32];
+        stream.read_exact(&mut buf).unwrap();
+
+        stream
+    })
+    .await
+    .unwrap();
+
+    // the connections of a removed listener are closed with it
+    handle
+        .reload(ServerConfig {
+            listeners: vec![udp],
+            ..Default::default()
+        })
+        .unwrap();
+
+    let closed = tokio::task::spawn_blocking(move || {
+        use std::io::Read;
+
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+
+        match stream.read(&mut [0u8; 32]) {
+            Ok(size) => size == 0,
+            Err(err) => err.kind() == std::io::ErrorKind::ConnectionReset,
+        }
+    })
+    .await
+    .unwrap();
+
+    assert!(closed, "Connection wasn't closed");
+
+    running.store(false, Ordering::Relaxed);
+
+    tokio::time::timeout(Duration::from_secs(5), server)
+        .await
+        .expect("Server didn't stop")
+        .unwrap();
+}
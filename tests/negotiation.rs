@@ -0,0 +1,286 @@
+//! Password algorithm negotiation and bid-down protection
+//!
+//! https://datatracker.ietf.org/doc/html/rfc8489#section-9.2.4
+
+use flashbang::{
+    message::{attributes::*, methods::Binding, *},
+    server::challenge_response,
+};
+
+fn request(authorization: Option<Authorization>) -> Vec<u8> {
+    OutgoingMessage {
+        transaction_id: TransactionId::new(0x4242),
+        body: Request {
+            method: Binding,
+            authorization,
+        },
+        software: false,
+        fingerprint: false,
+    }
+    .encode()
+    .to_vec()
+}
+
+fn authorization(buf: &[u8]) -> IncomingAuthorization {
+    let message = IncomingMessage::decode(buf).unwrap();
+
+    let ClassTy::Request {
+        authorization: Some(authorization),
+        ..
+    } = message.body
+    else {
+        panic!("Request is missing the authorization attributes");
+    };
+
+    authorization
+}
+
+fn advertised() -> PasswordAlgorithms {
+    PasswordAlgorithms::new(vec![
+        SHA256_PASSWORD_ALGORITHM.clone(),
+        MD5_PASSWORD_ALGORITHM.clone(),
+    ])
+}
+
+fn challenge(nonce: &Nonce) -> Challenge {
+    let request = IncomingMessage::decode(&request(None)).unwrap();
+
+    let response = challenge_response(
+        &request,
+        ErrorCode::Unauthenticated,
+        &Realm::new("example.org"),
+        nonce,
+        &advertised(),
+    )
+    .unwrap()
+    .encode();
+
+    Challenge::decode(&response).unwrap().unwrap()
+}
+
+#[test]
+fn nonce_features() {
+    let features = SecurityFeatures {
+        password_algorithms: true,
+        username_anonymity: false,
+    };
+
+    let nonce = Nonce::with_features(features, "f//499k954d6OL34");
+
+    assert_eq!("obMatJos2gAAAf//499k954d6OL34", nonce.to_string());
+    assert_eq!(Some(features), nonce.features());
+
+    assert_eq!(None, Nonce::new("f//499k954d6OL34").features());
+}
+
+#[test]
+fn negotiate() {
+    let features = SecurityFeatures {
+        password_algorithms: true,
+        username_anonymity: true,
+    };
+
+    let challenge = challenge(&Nonce::with_features(features, "abcd"));
+
+    assert_eq!(Some(advertised()), challenge.algorithms);
+    assert_eq!(
+        Some(SHA256_PASSWORD_ALGORITHM.clone()),
+        challenge.select_algorithm().unwrap()
+    );
+
+    let credentials = challenge
+        .credentials(Username::new("user"), "password")
+        .unwrap();
+
+    let buf = request(Some(Authorization {
+        credentials,
+        integrity: Integrity::Sha256,
+    }));
+
+    let authorization = authorization(&buf);
+
+    assert!(matches!(authorization.user, Some(UserTy::Userhash(_))));
+
+    let algorithm = authorization.negotiate(&advertised()).unwrap();
+    assert_eq!(*SHA256_PASSWORD_ALGORITHM, algorithm);
+
+    let verification =
+        IncomingAuthorization::verify(&buf, |_| Some(algorithm.hash(b"user:example.org:password")))
+            .unwrap();
+
+    assert_eq!(Verification::Ok(Integrity::Sha256), verification);
+
+    // a server that advertises a different list rejects the request
+    let other = PasswordAlgorithms::new(vec![SHA256_PASSWORD_ALGORITHM.clone()]);
+    assert_eq!(Err(ErrorCode::BadRequest), authorization.negotiate(&other));
+}
+
+#[test]
+fn bid_down() {
+    let features = SecurityFeatures {
+        password_algorithms: true,
+        username_anonymity: false,
+    };
+
+    // an attacker strips PASSWORD-ALGORITHMS from the challenge
+    let mut stripped = challenge(&Nonce::with_features(features, "abcd"));
+    stripped.algorithms = None;
+
+    let err = stripped.select_algorithm().unwrap_err();
+    assert!(matches!(err.ty, IncomingErrorTy::BidDown));
+
+    // an attacker strips PASSWORD-ALGORITHMS from the request
+    let nonce = Nonce::with_features(features, "abcd");
+    let buf = request(Some(Authorization {
+        credentials: Credentials::new_long_term(
+            Username::new("user"),
+            nonce,
+            Realm::new("example.org"),
+            "password",
+            false,
+            Some(SHA256_PASSWORD_ALGORITHM.clone()),
+        )
+        .unwrap(),
+        integrity: Integrity::Sha256,
+    }));
+
+    let authorization = authorization(&buf);
+
+    assert_eq!(
+        Err(ErrorCode::BadRequest),
+        authorization.negotiate(&advertised())
+    );
+
+    // without the security feature, PASSWORD-ALGORITHMS isn't advertised
+    let challenge = challenge(&Nonce::new("abcd"));
+
+    assert_eq!(None, challenge.algorithms);
+    assert_eq!(None, challenge.select_algorithm().unwrap());
+}
+
+#[test]
+fn precomputed_key() {
+    let features = SecurityFeatures {
+        password_algorithms: true,
+        username_anonymity: false,
+    };
+
+    let challenge = challenge(&Nonce::with_features(features, "abcd"));
+
+    let key = LongTermKey::derive(
+        "user",
+        "example.org",
+        "password",
+        &SHA256_PASSWORD_ALGORITHM,
+    )
+    .unwrap();
+
+    let credentials = challenge
+        .credentials_with_key(Username::new("user"), key.clone())
+        .unwrap();
+
+    let buf = request(Some(Authorization {
+        credentials,
+        integrity: Integrity::Sha256,
+    }));
+
+    let authorization = authorization(&buf);
+
+    assert_eq!(
+        *SHA256_PASSWORD_ALGORITHM,
+        authorization.negotiate(&advertised()).unwrap()
+    );
+
+    let verification = IncomingAuthorization::verify(&buf, |_| {
+        Some(SHA256_PASSWORD_ALGORITHM.hash(b"user:example.org:password"))
+    })
+    .unwrap();
+
+    assert_eq!(Verification::Ok(Integrity::Sha256), verification);
+
+    // a key for an algorithm the server doesn't advertise can't be used
+    let sha256_only = PasswordAlgorithms::new(vec![SHA256_PASSWORD_ALGORITHM.clone()]);
+    let mut restricted = challenge.clone();
+    restricted.algorithms = Some(sha256_only);
+
+    let md5 =
+        LongTermKey::derive("user", "example.org", "password", &MD5_PASSWORD_ALGORITHM).unwrap();
+
+    let err = restricted
+        .credentials_with_key(Username::new("user"), md5)
+        .unwrap_err();
+    assert!(matches!(err.ty, IncomingErrorTy::UnsupportedAlgorithm));
+}
+
+#[test]
+fn unknown_algorithm() {
+    let features = SecurityFeatures {
+        password_algorithms: true,
+        username_anonymity: false,
+    };
+
+    let nonce = Nonce::with_features(features, "abcd");
+    let request_buf = request(None);
+    let request_message = IncomingMessage::decode(&request_buf).unwrap();
+
+    let mut response = challenge_response(
+        &request_message,
+        ErrorCode::Unauthenticated,
+        &Realm::new("example.org"),
+        &nonce,
+        &advertised(),
+    )
+    .unwrap()
+    .encode()
+    .to_vec();
+
+    let algorithms = |buf: &[u8]| {
+        MessageRef::new(buf)
+            .unwrap()
+            .iter_raw()
+            .find(|(ty, _)| *ty == PasswordAlgorithms::TY)
+            .map(|(_, value)| value.to_vec())
+            .unwrap()
+    };
+
+    // the server advertises an algorithm that isn't supported (0x0003) before MD5
+    let position = response
+        .windows(6)
+        .position(|window| window == [0x80, 0x02, 0x00, 0x08, 0x00, 0x02])
+        .unwrap();
+    response[position + 5] = 0x03;
+
+    let challenge = Challenge::decode(&response).unwrap().unwrap();
+    let advertised = challenge.algorithms.clone().unwrap();
+
+    assert_eq!(vec![0x0003], advertised.unknown().collect::<Vec<_>>());
+    assert_eq!(
+        Some(MD5_PASSWORD_ALGORITHM.clone()),
+        challenge.select_algorithm().unwrap()
+    );
+
+    // the list is echoed exactly as it was advertised
+    let credentials = challenge
+        .credentials(Username::new("user"), "password")
+        .unwrap();
+
+    let buf = request(Some(Authorization {
+        credentials,
+        integrity: Integrity::Sha256,
+    }));
+
+    assert_eq!(algorithms(&response), algorithms(&buf));
+
+    let authorization = authorization(&buf);
+    assert_eq!(
+        *MD5_PASSWORD_ALGORITHM,
+        authorization.negotiate(&advertised).unwrap()
+    );
+
+    // the echo doesn't match a list without the unknown algorithm
+    let md5_only = PasswordAlgorithms::new(vec![MD5_PASSWORD_ALGORITHM.clone()]);
+    assert_eq!(
+        Err(ErrorCode::BadRequest),
+        authorization.negotiate(&md5_only)
+    );
+}
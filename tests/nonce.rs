@@ -0,0 +1,115 @@
+//! Stateless nonces with expiry and secret rotation
+//!
+//! https://datatracker.ietf.org/doc/html/rfc8489#section-9.2
+
+use std::{
+    net::IpAddr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use flashbang::{
+    message::attributes::*,
+    server::nonce::{NonceManager, NonceSecret},
+};
+
+const LIFETIME: Duration = Duration::from_secs(600);
+
+fn features() -> SecurityFeatures {
+    SecurityFeatures {
+        password_algorithms: true,
+        username_anonymity: true,
+    }
+}
+
+fn ip() -> IpAddr {
+    "192.0.2.1".parse().unwrap()
+}
+
+#[test]
+fn validate() {
+    let manager = NonceManager::new(NonceSecret::new(1, [7; 32]), features(), LIFETIME);
+
+    let nonce = manager.mint(ip());
+
+    assert_eq!(Some(features()), nonce.features());
+    assert!(nonce.to_string().len() < 128);
+    assert_eq!(Ok(()), manager.validate(&nonce, ip()));
+
+    // the same client connecting over IPv6
+    let mapped: IpAddr = "::ffff:192.0.2.1".parse().unwrap();
+    assert_eq!(Ok(()), manager.validate(&nonce, mapped));
+
+    // another client
+    let other: IpAddr = "192.0.2.2".parse().unwrap();
+    assert_eq!(Err(ErrorCode::StaleNonce), manager.validate(&nonce, other));
+
+    // an attacker strips the security features
+    let stripped = Nonce::with_features(
+        SecurityFeatures::default(),
+        &nonce.to_string()[(NONCE_COOKIE.len() + 4)..],
+    );
+    assert_eq!(
+        Err(ErrorCode::StaleNonce),
+        manager.validate(&stripped, ip())
+    );
+
+    for garbage in ["", "abcd", NONCE_COOKIE] {
+        assert_eq!(
+            Err(ErrorCode::StaleNonce),
+            manager.validate(&Nonce::new(garbage), ip())
+        );
+    }
+}
+
+#[test]
+fn expiry() {
+    let manager = NonceManager::new(NonceSecret::new(1, [7; 32]), features(), LIFETIME);
+
+    // timestamps have a resolution of seconds
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    let minted =
+        UNIX_EPOCH + Duration::from_secs(now.as_secs()) - LIFETIME - Duration::from_secs(1);
+    let nonce = manager.mint_at(ip(), minted);
+
+    assert_eq!(Ok(()), manager.validate_at(&nonce, ip(), minted + LIFETIME));
+    assert_eq!(Err(ErrorCode::StaleNonce), manager.validate(&nonce, ip()));
+}
+
+#[test]
+fn rotation() {
+    let secret = NonceSecret::new(1, [7; 32]);
+
+    let manager =
+        NonceManager::new(secret.clone(), features(), LIFETIME).grace(Duration::from_secs(60));
+
+    // another instance that shares the secret
+    let other = NonceManager::new(secret, features(), LIFETIME);
+
+    let nonce = manager.mint(ip());
+    assert_eq!(Ok(()), other.validate(&nonce, ip()));
+
+    // nonces of the previous secret are accepted during the grace period
+    manager.rotate(NonceSecret::random(2));
+    assert_eq!(Ok(()), manager.validate(&nonce, ip()));
+    assert_eq!(Ok(()), manager.validate(&manager.mint(ip()), ip()));
+    assert_eq!(
+        Err(ErrorCode::StaleNonce),
+        other.validate(&manager.mint(ip()), ip())
+    );
+
+    // but not after another rotation
+    manager.rotate(NonceSecret::random(3));
+    assert_eq!(Err(ErrorCode::StaleNonce), manager.validate(&nonce, ip()));
+
+    // or without a grace period
+    other.rotate(NonceSecret::random(2));
+    assert_eq!(Err(ErrorCode::StaleNonce), other.validate(&nonce, ip()));
+
+    // a grace period that never ends
+    let forever =
+        NonceManager::new(NonceSecret::new(1, [7; 32]), features(), LIFETIME).grace(Duration::MAX);
+    let nonce = forever.mint(ip());
+
+    forever.rotate(NonceSecret::random(2));
+    assert_eq!(Ok(()), forever.validate(&nonce, ip()));
+}
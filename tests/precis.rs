@@ -0,0 +1,106 @@
+//! PRECIS OpaqueString profile for usernames, realms and passwords
+//!
+//! https://datatracker.ietf.org/doc/html/rfc8265#section-4.2
+
+use flashbang::message::{attributes::*, *};
+
+#[test]
+fn profile() {
+    // non-ASCII spaces are mapped to SPACE
+    assert_eq!(
+        "correct horse",
+        opaque_string("correct\u{3000}horse").unwrap()
+    );
+
+    // decomposed characters are normalized with NFC
+    assert_eq!(
+        "\u{00E9}t\u{00E9}",
+        opaque_string("e\u{0301}te\u{0301}").unwrap()
+    );
+
+    // fullwidth and halfwidth characters are preserved
+    assert_eq!(
+        "\u{FF30}\u{FF41}\u{FF53}\u{FF53}",
+        opaque_string("\u{FF30}\u{FF41}\u{FF53}\u{FF53}").unwrap()
+    );
+
+    let err = opaque_string("").unwrap_err();
+    assert!(matches!(err.ty, PrecisErrorTy::Empty));
+
+    for disallowed in [
+        "tab\t",
+        "soft\u{00AD}hyphen",
+        "\u{1100}",
+        "\u{FFFF}",
+        "\u{0378}",
+    ] {
+        let err = opaque_string(disallowed).unwrap_err();
+        assert!(
+            matches!(err.ty, PrecisErrorTy::Disallowed(_)),
+            "{disallowed:?}"
+        );
+    }
+
+    // default ignorable code points, including fillers and shorthand format controls
+    for c in [
+        '\u{115F}',
+        '\u{3164}',
+        '\u{FFA0}',
+        '\u{1BCA0}',
+        '\u{1BCA3}',
+        '\u{E0001}',
+    ] {
+        let err = opaque_string(&format!("a{c}b")).unwrap_err();
+        assert!(
+            matches!(err.ty, PrecisErrorTy::Disallowed(d) if d == c),
+            "{c:?}"
+        );
+    }
+}
+
+#[test]
+fn credentials() {
+    let decomposed = Userhash::new(Username::new("Jose\u{0301}"), Realm::new("example.org"));
+    let composed = Userhash::new(Username::new("Jos\u{00E9}"), Realm::new("example.org"));
+    assert_eq!(composed.unwrap(), decomposed.unwrap());
+
+    assert!(Userhash::new(Username::new("\u{0007}"), Realm::new("example.org")).is_err());
+
+    let Credentials::LongTerm {
+        username, password, ..
+    } = Credentials::new_long_term(
+        Username::new("Jose\u{0301}"),
+        Nonce::new("nonce"),
+        Realm::new("example.org"),
+        "pass\u{00A0}word",
+        false,
+        None,
+    )
+    .unwrap()
+    else {
+        panic!("Expected long-term credentials");
+    };
+
+    assert_eq!(Username::new("Jos\u{00E9}"), username);
+    assert_eq!("pass word", password.as_str());
+
+    assert!(Credentials::new_short_term(Username::new("user"), "\u{200B}").is_err());
+
+    // servers derive the same key from stored credentials that weren't prepared
+    assert_eq!(
+        LongTermKey::derive(
+            "Jos\u{00E9}",
+            "example.org",
+            "pass word",
+            &MD5_PASSWORD_ALGORITHM
+        )
+        .unwrap(),
+        LongTermKey::derive(
+            "Jose\u{0301}",
+            "example.org",
+            "pass\u{00A0}word",
+            &MD5_PASSWORD_ALGORITHM
+        )
+        .unwrap()
+    );
+}
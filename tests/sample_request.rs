@@ -7,7 +7,7 @@
 
 use flashbang::message::{
     attributes::*,
-    methods::{Binding, MethodTy},
+    methods::{Binding, Method, MethodTy},
     *,
 };
 
@@ -69,7 +69,8 @@ fn encode() {
                     "TheMatrIX",
                     true,
                     Some(SHA256_PASSWORD_ALGORITHM.clone()),
-                ),
+                )
+                .unwrap(),
                 integrity: Integrity::Sha256,
             }),
         },
@@ -86,25 +87,133 @@ fn encode() {
 fn decode() {
     let expected = IncomingMessage {
         transaction_id: TransactionId::new(0x78ad3433c6ad72c029da412e),
+        classic: None,
         body: ClassTy::Request {
             method: MethodTy::Binding(Binding),
-            authorization: Some(Authorization {
-                credentials: Credentials::new_long_term(
-                    Username::new("\u{30DE}\u{30C8}\u{30EA}\u{30C3}\u{30AF}\u{30B9}"),
-                    Nonce::new("obMatJos2AAACf//499k954d6OL34oL9FSTvy64sA"),
-                    Realm::new("example.org"),
-                    "TheMatrIX",
-                    true,
-                    Some(SHA256_PASSWORD_ALGORITHM.clone()),
-                ),
-                integrity: Integrity::Sha256,
+            authorization: Some(IncomingAuthorization {
+                user: Some(UserTy::Userhash(
+                    Userhash::new(
+                        Username::new("\u{30DE}\u{30C8}\u{30EA}\u{30C3}\u{30AF}\u{30B9}"),
+                        Realm::new("example.org"),
+                    )
+                    .unwrap(),
+                )),
+                realm: Some(Realm::new("example.org")),
+                nonce: Some(Nonce::new("obMatJos2AAACf//499k954d6OL34oL9FSTvy64sA")),
+                algorithms: None,
+                algorithm: Some(SHA256_PASSWORD_ALGORITHM.clone()),
+                integrity: Some(Integrity::Sha256),
             }),
         },
         software: None,
         fingerprint: None,
+        unknown_required: vec![],
+        unknown_optional: vec![],
+        custom: vec![],
     };
 
     let output = IncomingMessage::decode(MESSAGE).expect("Failed to decode message");
 
     assert_eq!(expected, output, "{expected:#x?}\nvs\n{output:#x?}");
 }
+
+#[test]
+fn verify() {
+    let key = LongTermKey::derive(
+        "\u{30DE}\u{30C8}\u{30EA}\u{30C3}\u{30AF}\u{30B9}",
+        "example.org",
+        "TheMatrIX",
+        &SHA256_PASSWORD_ALGORITHM,
+    )
+    .unwrap();
+
+    let verification = IncomingAuthorization::verify_key(MESSAGE, key.as_bytes())
+        .expect("Failed to decode message");
+
+    assert_eq!(Verification::Ok(Integrity::Sha256), verification);
+
+    let verification = IncomingAuthorization::verify(MESSAGE, |_| Some("wrong".into()))
+        .expect("Failed to decode message");
+
+    assert_eq!(Verification::Mismatch, verification);
+
+    let verification =
+        IncomingAuthorization::verify(MESSAGE, |_| None).expect("Failed to decode message");
+
+    assert_eq!(Verification::WrongKey, verification);
+}
+
+#[test]
+fn view() {
+    let message = MessageRef::new(MESSAGE).expect("Failed to validate message");
+
+    assert_eq!(
+        TransactionId::new(0x78ad3433c6ad72c029da412e),
+        message.transaction_id()
+    );
+    assert_eq!(Binding::METHOD, message.method());
+
+    let types: Vec<u16> = message.iter_raw().map(|(ty, _)| ty).collect();
+
+    assert_eq!(
+        vec![
+            Userhash::TY,
+            Nonce::TY,
+            Realm::TY,
+            PasswordAlgorithm::TY,
+            MessageIntegritySha256::TY
+        ],
+        types
+    );
+
+    let realm = message.get::<Realm>().expect("Missing REALM");
+
+    assert_eq!(Ok(Realm::new("example.org")), realm);
+    assert!(message.get::<Username>().is_none());
+    assert_eq!(1, message.get_all::<Nonce>().count());
+}
+
+#[test]
+fn dissect() {
+    let key = SHA256_PASSWORD_ALGORITHM
+        .hash("\u{30DE}\u{30C8}\u{30EA}\u{30C3}\u{30AF}\u{30B9}:example.org:TheMatrIX".as_bytes());
+
+    let dissection = flashbang::message::dissect(MESSAGE, Some(&key));
+
+    assert!(dissection.issues.is_empty(), "{dissection}");
+
+    let names: Vec<_> = dissection
+        .attributes
+        .iter()
+        .map(|attr| attribute_name(attr.ty).unwrap())
+        .collect();
+
+    assert_eq!(
+        vec![
+            "USERHASH",
+            "NONCE",
+            "REALM",
+            "PASSWORD-ALGORITHM",
+            "MESSAGE-INTEGRITY-SHA256"
+        ],
+        names
+    );
+
+    let nonce = &dissection.attributes[1];
+    assert_eq!(41, nonce.len);
+    assert_eq!(3, nonce.padding);
+    assert_eq!("\"obMatJos2AAACf//499k954d6OL34oL9FSTvy64sA\"", nonce.value);
+
+    assert_eq!("SHA-256", dissection.attributes[3].value);
+    assert_eq!(Some(true), dissection.attributes[4].valid);
+
+    let output = dissection.to_string();
+    assert!(output.starts_with("STUN Binding Request\n"));
+    assert!(output.contains("REALM (0x0014), length: 11 (+1 bytes of padding): \"example.org\""));
+
+    // with the wrong key
+    let dissection = flashbang::message::dissect(MESSAGE, Some(b"wrong"));
+
+    assert_eq!(Some(false), dissection.attributes[4].valid);
+    assert_eq!(1, dissection.issues.len());
+}
@@ -0,0 +1,137 @@
+//! Human-readable serde representation of messages
+#![cfg(feature = "serde")]
+
+use std::net::SocketAddr;
+
+use flashbang::message::{attributes::*, methods::Binding, *};
+use serde_json::json;
+
+#[test]
+fn round_trip() {
+    let message = OutgoingMessage {
+        transaction_id: TransactionId::new(0x0123456789abcdef01234567),
+        body: ErrorResponse {
+            method: Binding,
+            error_code: ErrorCode::UnknownAttribute,
+            unknown_attributes: Some(UnknownAttributes::new(vec![0x7FF0])),
+        },
+        software: true,
+        fingerprint: true,
+    };
+
+    let mut buf = message.encode().to_vec();
+
+    // insert an unknown comprehension-optional attribute before FINGERPRINT
+    let len = buf.len();
+    buf.splice(
+        (len - 8)..(len - 8),
+        [0xC0, 0xFF, 0x00, 0x02, 0xAB, 0xCD, 0x00, 0x00],
+    );
+    let len = u16::from_be_bytes([buf[2], buf[3]]) + 8;
+    buf[2..4].copy_from_slice(&len.to_be_bytes());
+    let offset = buf.len() - 4;
+    let fingerprint = crc32fast::hash(&buf[..(offset - 4)]) ^ 0x5354554E;
+    buf[offset..].copy_from_slice(&fingerprint.to_be_bytes());
+
+    let decoded = IncomingMessage::decode(&buf).expect("Failed to decode");
+
+    let value = serde_json::to_value(&decoded).expect("Failed to serialize");
+
+    assert_eq!(json!("0123456789abcdef01234567"), value["transaction_id"]);
+    assert_eq!(
+        json!({
+            "method": "Binding",
+            "error_code": { "code": 420, "reason": "Unknown Attribute" },
+            "unknown_attributes": [0x7FF0],
+        }),
+        value["body"]["ErrorResponse"]
+    );
+    assert_eq!(
+        json!(format!("flashbang {}", env!("CARGO_PKG_VERSION"))),
+        value["software"]
+    );
+    assert_eq!(json!(fingerprint), value["fingerprint"]);
+    assert_eq!(
+        json!([{ "type": 0xC0FF, "value": "abcd" }]),
+        value["unknown_optional"]
+    );
+
+    let output: IncomingMessage = serde_json::from_value(value).expect("Failed to deserialize");
+
+    assert_eq!(decoded, output);
+
+    // the deserialized message encodes to the same bytes
+    assert_eq!(buf, output.to_builder().encode());
+}
+
+#[test]
+fn round_trip_request() {
+    let message = OutgoingMessage {
+        transaction_id: TransactionId::new(0x4242),
+        body: Request {
+            method: Binding,
+            authorization: Some(Authorization {
+                credentials: Credentials::new_short_term(Username::new("user"), "password")
+                    .unwrap(),
+                integrity: Integrity::Both,
+            }),
+        },
+        software: true,
+        fingerprint: true,
+    };
+
+    let buf = message.encode();
+
+    let value = serde_json::to_value(IncomingMessage::decode(&buf).unwrap()).unwrap();
+    let output: IncomingMessage = serde_json::from_value(value).expect("Failed to deserialize");
+
+    // the integrity attributes are computed again with the key
+    let encoded = output
+        .to_builder()
+        .integrity(b"password", Integrity::Both)
+        .encode();
+
+    assert_eq!(buf, encoded);
+}
+
+#[test]
+fn attributes() {
+    let addr: SocketAddr = "[2001:db8::1]:3478".parse().unwrap();
+
+    assert_eq!(
+        json!("[2001:db8::1]:3478"),
+        serde_json::to_value(XorMappedAddress::new(addr)).unwrap()
+    );
+
+    let userhash = Userhash::new(Username::new("user"), Realm::new("realm")).unwrap();
+    let value = serde_json::to_value(&userhash).unwrap();
+    assert_eq!(64, value.as_str().unwrap().len());
+    assert_eq!(userhash, serde_json::from_value(value).unwrap());
+
+    let error_code: ErrorCode =
+        serde_json::from_value(json!({ "code": 499, "reason": "Custom" })).unwrap();
+    assert_eq!(ErrorCode::Other(499, "Custom".into()), error_code);
+
+    let algorithm: PasswordAlgorithm = serde_json::from_value(json!("SHA-256")).unwrap();
+    assert_eq!(*SHA256_PASSWORD_ALGORITHM, algorithm);
+
+    assert!(serde_json::to_value(MessageIntegrity::new(b"secret")).is_err());
+}
+
+#[test]
+fn credentials() {
+    let credentials = Credentials::new_long_term(
+        Username::new("user"),
+        Nonce::new("nonce"),
+        Realm::new("realm"),
+        "hunter2",
+        false,
+        None,
+    )
+    .unwrap();
+
+    let json = serde_json::to_string(&credentials).unwrap();
+
+    assert!(json.contains("\"user\""));
+    assert!(!json.contains("hunter2"));
+}
@@ -0,0 +1,534 @@
+//! Answering requests on the server
+//!
+//! https://datatracker.ietf.org/doc/html/rfc8489#section-6.3
+
+use std::{
+    collections::VecDeque,
+    io,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use bytes::Bytes;
+use flashbang::{
+    message::{
+        attributes::*,
+        methods::{Binding, Method},
+        *,
+    },
+    server::{
+        auth::Authenticator,
+        config::{AuthConfig, ServerConfig},
+        handle_message,
+        runtime::{ServerConn, ServerProcessor},
+    },
+    user::{CredentialStore, FileStore, MemoryStore, StoredCredentials},
+};
+
+fn source() -> SocketAddr {
+    "192.0.2.1:32853".parse().unwrap()
+}
+
+fn binding_request() -> Vec<u8> {
+    authenticated_request(None)
+}
+
+fn authenticated_request(authorization: Option<Authorization>) -> Vec<u8> {
+    let message = OutgoingMessage {
+        transaction_id: TransactionId::new(0x1234),
+        body: Request {
+            method: Binding,
+            authorization,
+        },
+        software: false,
+        fingerprint: true,
+    };
+
+    message.encode().to_vec()
+}
+
+/// Encodes a Binding request with a raw attribute.
+fn with_attribute(id: u128, ty: u16, value: &[u8]) -> Vec<u8> {
+    let mut buf = MessageBuilder::request(Binding::METHOD)
+        .transaction_id(TransactionId::new(id))
+        .encode()
+        .to_vec();
+
+    buf.extend_from_slice(&ty.to_be_bytes());
+    buf.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    buf.extend_from_slice(value);
+    buf.resize((buf.len() + 3) & !3, 0);
+
+    let len = (buf.len() - 20) as u16;
+    buf[2..4].copy_from_slice(&len.to_be_bytes());
+
+    buf
+}
+
+fn handle(buf: &[u8]) -> Option<Bytes> {
+    futures::executor::block_on(handle_message(
+        &ServerConfig::default(),
+        None,
+        buf,
+        source(),
+    ))
+}
+
+#[test]
+fn binding() {
+    let response = handle(&binding_request()).expect("Expected a response");
+
+    let view = MessageRef::new(&response).expect("Failed to decode");
+    assert_eq!(SUCCESS_RESPONSE_CLASS, view.class());
+    assert_eq!(TransactionId::new(0x1234), view.transaction_id());
+
+    let mapped = view.get::<XorMappedAddress>().unwrap().unwrap();
+    assert_eq!(source(), mapped.addr());
+
+    // the client used FINGERPRINT, so the response does as well
+    assert!(is_stun(&response));
+    assert!(view.get::<Fingerprint>().is_some());
+}
+
+#[test]
+fn rejected() {
+    // an unknown comprehension-required attribute
+    let unknown = with_attribute(0x5678, 0x7FF0, &[1, 2, 3, 4]);
+
+    let response = handle(&unknown).expect("Expected a response");
+    let response = IncomingMessage::decode(&response).expect("Failed to decode");
+
+    assert_eq!(TransactionId::new(0x5678), response.transaction_id);
+    let ClassTy::ErrorResponse {
+        error_code,
+        unknown_attributes,
+        ..
+    } = response.body
+    else {
+        panic!("Expected an error response");
+    };
+    assert_eq!(ErrorCode::UnknownAttribute, error_code);
+    assert_eq!(
+        Some(UnknownAttributes::new(vec![0x7FF0])),
+        unknown_attributes
+    );
+
+    // a USERNAME that isn't valid UTF-8
+    let malformed = with_attribute(0x9abc, Username::TY, &[0xff, 0xfe]);
+
+    let response = handle(&malformed).expect("Expected a response");
+    let response = IncomingMessage::decode(&response).expect("Failed to decode");
+
+    assert_eq!(TransactionId::new(0x9abc), response.transaction_id);
+    assert!(matches!(
+        response.body,
+        ClassTy::ErrorResponse {
+            error_code: ErrorCode::BadRequest,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn dropped() {
+    let indication = MessageBuilder::indication(Binding::METHOD).encode();
+    assert_eq!(None, handle(&indication));
+
+    let request = IncomingMessage::decode(&binding_request()).unwrap();
+    let response = MessageBuilder::success_response(&request).encode();
+    assert_eq!(None, handle(&response));
+
+    // a corrupt fingerprint
+    let mut request = binding_request();
+    let last = request.len() - 1;
+    request[last] ^= 0xff;
+    assert_eq!(None, handle(&request));
+
+    assert_eq!(None, handle(&[]));
+    assert_eq!(None, handle(&[0x16, 0xfe, 0xfd, 0x00]));
+}
+
+/// A connection that receives queued messages and records what was sent.
+struct MockConn {
+    incoming: VecDeque<Bytes>,
+    sent: Arc<Mutex<Vec<(Bytes, SocketAddr)>>>,
+}
+
+#[async_trait::async_trait]
+impl ServerConn for MockConn {
+    async fn send(&mut self, buf: &[u8], addr: SocketAddr) -> io::Result<()> {
+        self.sent
+            .lock()
+            .unwrap()
+            .push((Bytes::copy_from_slice(buf), addr));
+        Ok(())
+    }
+
+    async fn recv(&mut self) -> io::Result<(Bytes, SocketAddr)> {
+        match self.incoming.pop_front() {
+            Some(buf) => Ok((buf, source())),
+            None => Err(io::ErrorKind::UnexpectedEof.into()),
+        }
+    }
+}
+
+#[tokio::test]
+async fn processor() {
+    let sent = Arc::new(Mutex::new(vec![]));
+
+    let conn = MockConn {
+        incoming: VecDeque::from([
+            Bytes::from(binding_request()),
+            MessageBuilder::indication(Binding::METHOD).encode(),
+            Bytes::from(binding_request()),
+        ]),
+        sent: sent.clone(),
+    };
+
+    let mut processor = ServerProcessor::new(conn, ServerConfig::default().into());
+
+    let err = processor.process().await.unwrap_err();
+    assert_eq!(io::ErrorKind::UnexpectedEof, err.kind());
+
+    // both requests were answered, but not the indication
+    let sent = sent.lock().unwrap();
+    assert_eq!(2, sent.len());
+
+    for (response, addr) in sent.iter() {
+        assert_eq!(source(), *addr);
+
+        let view = MessageRef::new(response).expect("Failed to decode");
+        assert_eq!(SUCCESS_RESPONSE_CLASS, view.class());
+    }
+}
+
+fn authenticator() -> Authenticator {
+    let store = MemoryStore::new();
+    store.insert(StoredCredentials::long_term("alice", "example.org", "password").unwrap());
+    store.insert(StoredCredentials::short_term("bob", "secret").unwrap());
+
+    Authenticator::new(Arc::new(store))
+}
+
+fn long_term_config() -> ServerConfig {
+    ServerConfig {
+        auth: AuthConfig {
+            realm: Some("example.org".into()),
+            credentials: None,
+        },
+        ..Default::default()
+    }
+}
+
+fn error_code(response: &[u8]) -> ErrorCode {
+    match IncomingMessage::decode(response).unwrap().body {
+        ClassTy::ErrorResponse { error_code, .. } => error_code,
+        body => panic!("Expected an error response, got {body:?}"),
+    }
+}
+
+#[tokio::test]
+async fn long_term() {
+    let auth = authenticator();
+    let config = long_term_config();
+
+    let handle = |buf: Vec<u8>| {
+        let (auth, config) = (&auth, &config);
+        async move {
+            handle_message(config, Some(auth), &buf, source())
+                .await
+                .expect("Expected a response")
+        }
+    };
+
+    // requests without credentials are challenged
+    let response = handle(binding_request()).await;
+    assert_eq!(ErrorCode::Unauthenticated, error_code(&response));
+
+    let challenge = Challenge::decode(&response).unwrap().unwrap();
+    assert_eq!(Realm::new("example.org"), challenge.realm);
+
+    let request = |password: &str| {
+        let credentials = challenge
+            .credentials(Username::new("alice"), password)
+            .unwrap();
+
+        authenticated_request(Some(Authorization {
+            credentials,
+            integrity: Integrity::Both,
+        }))
+    };
+
+    // the response is signed with the same key
+    let response = handle(request("password")).await;
+
+    let view = MessageRef::new(&response).unwrap();
+    assert_eq!(SUCCESS_RESPONSE_CLASS, view.class());
+
+    let key = LongTermKey::derive(
+        "alice",
+        "example.org",
+        "password",
+        &SHA256_PASSWORD_ALGORITHM,
+    )
+    .unwrap();
+    let verification = IncomingAuthorization::verify_key(&response, key.as_bytes()).unwrap();
+    assert_eq!(Verification::Ok(Integrity::Sha256), verification);
+
+    // unknown comprehension-required attributes are only reported after authentication
+    let response = handle(with_attribute(0x5678, 0x7FF0, &[1, 2, 3, 4])).await;
+    assert_eq!(ErrorCode::Unauthenticated, error_code(&response));
+
+    let mut unknown = IncomingMessage::decode(&request("password")).unwrap();
+    unknown
+        .unknown_required
+        .push((0x7FF0, Bytes::from_static(&[1, 2, 3, 4])));
+
+    let buf = unknown
+        .to_builder()
+        .integrity(key.as_bytes(), Integrity::Both)
+        .encode();
+
+    let response = handle(buf.to_vec()).await;
+    assert_eq!(ErrorCode::UnknownAttribute, error_code(&response));
+
+    let verification = IncomingAuthorization::verify_key(&response, key.as_bytes()).unwrap();
+    assert_eq!(Verification::Ok(Integrity::Sha256), verification);
+
+    // a wrong password is challenged again
+    let response = handle(request("wrong")).await;
+    assert_eq!(ErrorCode::Unauthenticated, error_code(&response));
+    assert!(Challenge::decode(&response).unwrap().is_some());
+
+    // a nonce that wasn't minted by the server is stale
+    let mut forged = challenge.clone();
+    forged.nonce = Nonce::with_features(challenge.nonce.features().unwrap(), "forged");
+
+    let buf = authenticated_request(Some(Authorization {
+        credentials: forged
+            .credentials(Username::new("alice"), "password")
+            .unwrap(),
+        integrity: Integrity::Both,
+    }));
+
+    let response = handle(buf).await;
+    assert_eq!(ErrorCode::StaleNonce, error_code(&response));
+    assert!(Challenge::decode(&response).unwrap().is_some());
+}
+
+#[tokio::test]
+async fn md5_only() {
+    let key =
+        LongTermKey::derive("alice", "example.org", "password", &MD5_PASSWORD_ALGORITHM).unwrap();
+
+    let store = MemoryStore::new();
+    store.replace(FileStore::parse(&format!("alice:example.org:{}", *key.to_hex())).unwrap());
+
+    let auth = Authenticator::new(Arc::new(store));
+    let config = long_term_config();
+
+    // only the algorithm every user has a key for is advertised
+    let response = handle_message(&config, Some(&auth), &binding_request(), source())
+        .await
+        .unwrap();
+    let challenge = Challenge::decode(&response).unwrap().unwrap();
+
+    assert_eq!(
+        Some(PasswordAlgorithms::new(
+            vec![MD5_PASSWORD_ALGORITHM.clone()]
+        )),
+        challenge.algorithms
+    );
+
+    let buf = authenticated_request(Some(Authorization {
+        credentials: challenge
+            .credentials(Username::new("alice"), "password")
+            .unwrap(),
+        integrity: Integrity::Both,
+    }));
+
+    let response = handle_message(&config, Some(&auth), &buf, source())
+        .await
+        .unwrap();
+    assert_eq!(
+        SUCCESS_RESPONSE_CLASS,
+        MessageRef::new(&response).unwrap().class()
+    );
+}
+
+#[tokio::test]
+async fn anonymity() {
+    let store = Arc::new(MemoryStore::new());
+    store.insert(StoredCredentials::long_term("alice", "example.org", "password").unwrap());
+
+    let store_dyn: Arc<dyn CredentialStore> = store.clone();
+    let auth = Authenticator::new(store_dyn);
+    let config = long_term_config();
+
+    let response = handle_message(&config, Some(&auth), &binding_request(), source())
+        .await
+        .unwrap();
+
+    let challenge = Challenge::decode(&response).unwrap().unwrap();
+    assert!(challenge.nonce.features().unwrap().username_anonymity);
+
+    let buf = authenticated_request(Some(Authorization {
+        credentials: challenge
+            .credentials(Username::new("alice"), "password")
+            .unwrap(),
+        integrity: Integrity::Sha256,
+    }));
+
+    // the client sends USERHASH instead of USERNAME
+    let ClassTy::Request {
+        authorization: Some(authorization),
+        ..
+    } = IncomingMessage::decode(&buf).unwrap().body
+    else {
+        panic!("Request is missing the authorization attributes");
+    };
+    assert!(matches!(authorization.user, Some(UserTy::Userhash(_))));
+
+    let response = handle_message(&config, Some(&auth), &buf, source())
+        .await
+        .unwrap();
+    assert_eq!(
+        SUCCESS_RESPONSE_CLASS,
+        MessageRef::new(&response).unwrap().class()
+    );
+
+    // USERNAME is still accepted
+    let mut credentials = challenge
+        .credentials(Username::new("alice"), "password")
+        .unwrap();
+
+    if let Credentials::LongTerm { anonymity, .. } = &mut credentials {
+        *anonymity = false;
+    }
+
+    let username = authenticated_request(Some(Authorization {
+        credentials,
+        integrity: Integrity::Sha256,
+    }));
+
+    let response = handle_message(&config, Some(&auth), &username, source())
+        .await
+        .unwrap();
+    assert_eq!(
+        SUCCESS_RESPONSE_CLASS,
+        MessageRef::new(&response).unwrap().class()
+    );
+
+    // the USERHASH of a removed user no longer resolves
+    store.remove("alice", Some("example.org"));
+
+    let response = handle_message(&config, Some(&auth), &buf, source())
+        .await
+        .unwrap();
+    assert_eq!(ErrorCode::Unauthenticated, error_code(&response));
+}
+
+#[tokio::test]
+async fn unprepared_usernames() {
+    let store = MemoryStore::new();
+    store.insert(StoredCredentials::long_term("Zo\u{eb}", "example.org", "password").unwrap());
+    store.insert(StoredCredentials::short_term("Zo\u{eb}", "secret").unwrap());
+
+    let auth = Authenticator::new(Arc::new(store));
+    let config = long_term_config();
+
+    // a client that sends the decomposed form of the username
+    let decomposed = Username::new("Zoe\u{308}");
+
+    let response = handle_message(&config, Some(&auth), &binding_request(), source())
+        .await
+        .unwrap();
+    let challenge = Challenge::decode(&response).unwrap().unwrap();
+
+    let key = LongTermKey::derive(
+        "Zo\u{eb}",
+        "example.org",
+        "password",
+        &SHA256_PASSWORD_ALGORITHM,
+    )
+    .unwrap();
+    let mut credentials = challenge
+        .credentials_with_key(Username::new("Zo\u{eb}"), key)
+        .unwrap();
+
+    if let Credentials::LongTermKey {
+        username,
+        anonymity,
+        ..
+    } = &mut credentials
+    {
+        *username = decomposed.clone();
+        *anonymity = false;
+    }
+
+    let buf = authenticated_request(Some(Authorization {
+        credentials,
+        integrity: Integrity::Sha256,
+    }));
+
+    let response = handle_message(&config, Some(&auth), &buf, source())
+        .await
+        .unwrap();
+    assert_eq!(
+        SUCCESS_RESPONSE_CLASS,
+        MessageRef::new(&response).unwrap().class()
+    );
+
+    let mut credentials = Credentials::new_short_term(Username::new("Zo\u{eb}"), "secret").unwrap();
+
+    if let Credentials::ShortTerm { username, .. } = &mut credentials {
+        *username = decomposed;
+    }
+
+    let buf = authenticated_request(Some(Authorization {
+        credentials,
+        integrity: Integrity::Sha1,
+    }));
+
+    let response = handle_message(&ServerConfig::default(), Some(&auth), &buf, source())
+        .await
+        .unwrap();
+    assert_eq!(
+        SUCCESS_RESPONSE_CLASS,
+        MessageRef::new(&response).unwrap().class()
+    );
+}
+
+#[tokio::test]
+async fn short_term() {
+    let auth = authenticator();
+    let config = ServerConfig::default();
+
+    let request = |username: &str, password: &str| {
+        authenticated_request(Some(Authorization {
+            credentials: Credentials::new_short_term(Username::new(username), password).unwrap(),
+            integrity: Integrity::Sha1,
+        }))
+    };
+
+    let response = handle_message(&config, Some(&auth), &request("bob", "secret"), source())
+        .await
+        .unwrap();
+
+    let verification =
+        IncomingAuthorization::verify(&response, |_| Some(b"secret".as_slice().into())).unwrap();
+    assert_eq!(Verification::Ok(Integrity::Sha1), verification);
+
+    let response = handle_message(&config, Some(&auth), &request("bob", "wrong"), source())
+        .await
+        .unwrap();
+    assert_eq!(ErrorCode::Unauthenticated, error_code(&response));
+
+    // short-term credentials aren't challenged
+    assert!(Challenge::decode(&response).unwrap().is_none());
+
+    let response = handle_message(&config, Some(&auth), &binding_request(), source())
+        .await
+        .unwrap();
+    assert_eq!(ErrorCode::BadRequest, error_code(&response));
+}
@@ -0,0 +1,104 @@
+//! Truncated MESSAGE-INTEGRITY-SHA256
+//!
+//! https://datatracker.ietf.org/doc/html/rfc8489#section-14.6
+
+use flashbang::message::{attributes::*, methods::Binding, *};
+
+fn request(credentials: Credentials) -> Vec<u8> {
+    let message = OutgoingMessage {
+        transaction_id: TransactionId::new(0x5678),
+        body: Request {
+            method: Binding,
+            authorization: Some(Authorization {
+                credentials,
+                integrity: Integrity::Sha256,
+            }),
+        },
+        software: false,
+        fingerprint: true,
+    };
+
+    message.encode().to_vec()
+}
+
+fn credentials() -> Credentials {
+    Credentials::new_short_term(Username::new("user"), "password").unwrap()
+}
+
+#[test]
+fn truncated() {
+    for len in [16, 20, 24, 28, 32] {
+        let buf = request(credentials().truncate(len).unwrap());
+
+        let view = MessageRef::new(&buf).expect("Failed to decode");
+        let attr = view
+            .attributes()
+            .find(|attr| attr.ty == MessageIntegritySha256::TY)
+            .unwrap();
+        assert_eq!(len, attr.len);
+
+        let verification = IncomingAuthorization::verify(&buf, |_| Some("password".into()))
+            .expect("Failed to decode");
+        assert_eq!(Verification::Ok(Integrity::Sha256), verification);
+
+        let verification = IncomingAuthorization::verify(&buf, |_| Some("wrong".into()))
+            .expect("Failed to decode");
+        assert_eq!(Verification::Mismatch, verification);
+    }
+
+    // the full HMAC is used by default
+    let full = request(credentials());
+    assert_eq!(&full, &request(credentials().truncate(32).unwrap()));
+}
+
+#[test]
+fn invalid() {
+    for len in [0, 12, 18, 36] {
+        let err = credentials().truncate(len).unwrap_err();
+        assert_eq!(AttributeErrorTy::BadLength(len), err.ty);
+    }
+
+    // an HMAC truncated to 12 bytes is never valid
+    let mut buf = request(credentials().truncate(16).unwrap());
+    buf.truncate(buf.len() - 8 - 4);
+
+    let offset = buf.len() - 14;
+    buf[offset..(offset + 2)].copy_from_slice(&12u16.to_be_bytes());
+    let len = (buf.len() - 20) as u16;
+    buf[2..4].copy_from_slice(&len.to_be_bytes());
+
+    let verification =
+        IncomingAuthorization::verify(&buf, |_| Some("password".into())).expect("Failed to decode");
+    assert_eq!(Verification::Mismatch, verification);
+}
+
+#[test]
+fn clamped() {
+    // lengths set without `truncate` are clamped instead of overflowing the HMAC
+    for (len, clamped) in [(100, 32), (0, 16), (18, 16)] {
+        let mut credentials = credentials();
+
+        if let Credentials::ShortTerm { truncation, .. } = &mut credentials {
+            *truncation = Some(len);
+        }
+
+        let buf = request(credentials);
+
+        let view = MessageRef::new(&buf).expect("Failed to decode");
+        let attr = view
+            .attributes()
+            .find(|attr| attr.ty == MessageIntegritySha256::TY)
+            .unwrap();
+        assert_eq!(clamped, attr.len);
+
+        let verification = IncomingAuthorization::verify(&buf, |_| Some("password".into()))
+            .expect("Failed to decode");
+        assert_eq!(Verification::Ok(Integrity::Sha256), verification);
+    }
+
+    let attr = MessageIntegritySha256::Outgoing {
+        key: b"password".to_vec().into(),
+        len: 100,
+    };
+    assert_eq!(MessageIntegritySha256::FULL_LEN, attr.size());
+}